@@ -3,7 +3,6 @@ extern crate irc;
 extern crate regex;
 extern crate kuchiki;
 extern crate time;
-extern crate rustfm;
 extern crate serde;
 extern crate hyper;
 extern crate serde_json;
@@ -13,6 +12,7 @@ extern crate redis;
 
 #[macro_use] mod plugin;
 mod plugins;
+mod store;
 
 use std::env;
 use std::default::Default;
@@ -22,6 +22,16 @@ use irc::client::prelude::*;
 
 use plugin::Plugin;
 
+// Owners and plugin options (e.g. `url.cache_ttl`) come from the JSON file
+// pointed by GAUSS_CONFIG, while nickname, server and channels are always
+// taken from the command line.
+fn load_config() -> Config {
+    match env::var("GAUSS_CONFIG") {
+        Ok(path) => Config::load(&path).unwrap(),
+        Err(_)   => Default::default()
+    }
+}
+
 fn main() {
     let mut args = env::args();
 
@@ -34,21 +44,35 @@ fn main() {
         nickname: args.next(),
         server:   args.next(),
         channels: Some(args.collect::<Vec<String>>()),
-        ..Default::default()
+        ..load_config()
     };
 
     let server = IrcServer::from_config(config).unwrap();
     server.identify().unwrap();
 
     let plugins: Vec<Arc<Mutex<Plugin>>> = vec![
-        Arc::new(Mutex::new(plugins::h::H::new())),
+        Arc::new(Mutex::new(plugins::rules::Rules::new())),
         Arc::new(Mutex::new(plugins::url::Url::new())),
         Arc::new(Mutex::new(plugins::seen::Seen::new())),
         Arc::new(Mutex::new(plugins::lastfm::LastFM::new())),
         Arc::new(Mutex::new(plugins::tangorin::Tangorin::new())),
         Arc::new(Mutex::new(plugins::currency::Currency::new())),
+        Arc::new(Mutex::new(plugins::factoids::Factoids::new())),
+        Arc::new(Mutex::new(plugins::karma::Karma::new())),
+        Arc::new(Mutex::new(plugins::quotes::Quotes::new())),
+        Arc::new(Mutex::new(plugins::reminders::Reminders::new())),
+        Arc::new(Mutex::new(plugins::tell::Tell::new())),
+        Arc::new(Mutex::new(plugins::calc::Calc::new())),
+        Arc::new(Mutex::new(plugins::numbers::Numbers::new())),
+        Arc::new(Mutex::new(plugins::matrix::Matrices::new())),
+        Arc::new(Mutex::new(plugins::stats::Stats::new())),
+        Arc::new(Mutex::new(plugins::dice::Dice::new())),
     ];
 
+    for plugin in plugins.iter() {
+        plugin.lock().unwrap().configure(&server);
+    }
+
     for message in server.iter() {
         let message = Arc::new(message.unwrap());
 
@@ -78,10 +102,15 @@ mod tests {
     use irc::client::conn::MockConnection;
 
     pub fn make_server(cmd: &str) -> IrcServer {
+        make_server_with_options(cmd, &[])
+    }
+
+    pub fn make_server_with_options(cmd: &str, options: &[(&str, &str)]) -> IrcServer {
         let config = Config {
             nickname: Some("Gauss".into()),
             server:   Some("irc.test.net".into()),
             channels: Some(vec!["#test".into()]),
+            options:  Some(options.iter().map(|&(k, v)| (k.to_owned(), v.to_owned())).collect()),
             ..Default::default()
         };
 
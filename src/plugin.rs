@@ -5,6 +5,60 @@ use irc::client::prelude::*;
 pub trait Plugin: Send + Sync + fmt::Debug {
     fn is_allowed(&self, server: &IrcServer, Message: &Message)  -> bool;
     fn execute(&mut self, server: &IrcServer, Message: &Message) -> io::Result<()>;
+
+    // Called once at startup, before any message comes in.
+    fn configure(&mut self, _: &IrcServer) {}
+}
+
+// Every "!command" the plugins answer to, so that factoids recalled with "!key" don't shadow them.
+pub const COMMANDS: &'static [&'static str] = &[
+    "addlastfmuser", "lastfm", "np", "lastsong", "topartists", "toptracks", "weekly", "compare",
+    "tangorin", "kanji", "more", "kana", "romaji", "example",
+    "fx", "fxchange",
+    "learn", "forget", "undo", "history", "lock", "unlock",
+    "karma", "karmatop", "karmabottom",
+    "addquote", "quote", "delquote", "randquote",
+    "remind", "reminders", "unremind", "timezone",
+    "tell", "tells",
+    "calc",
+    "factor", "isprime", "nextprime", "gcd", "lcm", "modinv", "modpow", "totient", "legendre",
+    "mat", "stats", "roll", "seen", "flushurlcache"
+];
+
+pub fn get_option<'a>(server: &'a IrcServer, key: &str) -> Option<&'a str> {
+    match server.config().options {
+        Some(ref options) => options.get(key).map(|value| &value[..]),
+        None              => None
+    }
+}
+
+pub fn is_admin(server: &IrcServer, message: &Message) -> bool {
+    match (message.source_nickname(), server.config().owners.as_ref()) {
+        (Some(nickname), Some(owners)) => owners.iter().any(|owner| owner == nickname),
+        _                              => false
+    }
+}
+
+// Nicknames are case insensitive, and by RFC 1459 "[]\\~" are the uppercase of "{}|^".
+pub fn nick_key(nickname: &str) -> String {
+    nickname.to_lowercase().chars().map(|c| match c {
+        '['  => '{',
+        ']'  => '}',
+        '\\' => '|',
+        '~'  => '^',
+        c    => c
+    }).collect()
+}
+
+pub fn time_ago(seconds: i64) -> String {
+    let (amount, unit) = match seconds {
+        s if s < 60    => { return "just now".to_owned(); },
+        s if s < 3600  => (s / 60,    "minute"),
+        s if s < 86400 => (s / 3600,  "hour"),
+        s              => (s / 86400, "day")
+    };
+
+    format!("{} {}{} ago", amount, unit, if amount == 1 { "" } else { "s" })
 }
 
 #[macro_export]
@@ -20,16 +74,71 @@ macro_rules! register_plugin {
         }
     };
 
-    ($t:ident, $element: ident: $ty: ty) => {
+    ($t:ident, $($element: ident: $ty: ty),+) => {
         #[derive(Debug)]
         pub struct $t {
-            $element: $ty
+            $($element: $ty),+
         }
 
         impl $t {
             pub fn new() -> $t {
-                $t { $element: <$ty>::new() }
+                $t { $($element: <$ty>::new()),+ }
             }
         }
     };
 }
+
+#[cfg(test)]
+mod tests {
+    use irc::client::prelude::*;
+    use regex::Regex;
+
+    use ::tests::make_server;
+    use plugins;
+    use super::{Plugin, COMMANDS};
+
+    // Every word of a plugin's source, and every two words glued together for
+    // "!karma(top|bottom)", is tried as a command with a few kinds of arguments:
+    // whatever the plugin answers to, and wouldn't without the command, has to be
+    // in COMMANDS. Seen answers to everything, factoids to every "!key" that
+    // isn't taken and the rules only to their triggers, which factoids ask about.
+    #[test]
+    fn test_commands() {
+        let plugins: Vec<(&str, Box<Plugin>)> = vec![
+            (include_str!("plugins/url.rs"),            Box::new(plugins::url::Url::new())),
+            (include_str!("plugins/lastfm/mod.rs"),     Box::new(plugins::lastfm::LastFM::new())),
+            (include_str!("plugins/tangorin/mod.rs"),   Box::new(plugins::tangorin::Tangorin::new())),
+            (include_str!("plugins/currency/mod.rs"),   Box::new(plugins::currency::Currency::new())),
+            (include_str!("plugins/karma.rs"),          Box::new(plugins::karma::Karma::new())),
+            (include_str!("plugins/quotes.rs"),         Box::new(plugins::quotes::Quotes::new())),
+            (include_str!("plugins/reminders/mod.rs"),  Box::new(plugins::reminders::Reminders::new())),
+            (include_str!("plugins/tell.rs"),           Box::new(plugins::tell::Tell::new())),
+            (include_str!("plugins/calc/mod.rs"),       Box::new(plugins::calc::Calc::new())),
+            (include_str!("plugins/numbers/mod.rs"),    Box::new(plugins::numbers::Numbers::new())),
+            (include_str!("plugins/matrix.rs"),         Box::new(plugins::matrix::Matrices::new())),
+            (include_str!("plugins/stats.rs"),          Box::new(plugins::stats::Stats::new())),
+            (include_str!("plugins/dice/mod.rs"),       Box::new(plugins::dice::Dice::new())),
+        ];
+
+        let server    = make_server("");
+        let word      = Regex::new(r"[a-z]+").unwrap();
+        let arguments = ["", " x", " 1", " x y", " 1 2 3", " [1]", " x 1", " usd eur", " usd eur 1d",
+                         " me in 1h to x", " link x", " unlink"];
+
+        for &(source, ref plugin) in &plugins {
+            let words: Vec<&str> = word.find_iter(source).map(|(start, end)| &source[start..end]).collect();
+            let glued: Vec<String> = words.windows(2).map(|pair| pair.concat()).collect();
+
+            for command in words.iter().map(|word| word.to_string()).chain(glued.into_iter()) {
+                for argument in arguments.iter() {
+                    let message = |msg: String| Message::from(Command::PRIVMSG("#test".to_owned(), msg));
+                    let answered = plugin.is_allowed(&server, &message(format!("!{}{}", command, argument))) &&
+                                   !plugin.is_allowed(&server, &message(argument.trim().to_owned()));
+                    if answered && !COMMANDS.contains(&&command[..]) {
+                        panic!("!{} is missing from COMMANDS ({:?})", command, plugin);
+                    }
+                }
+            }
+        }
+    }
+}
@@ -0,0 +1,430 @@
+use std::cmp::{self, Ordering};
+use std::fmt;
+use std::ops::{Add, Sub, Mul, Neg};
+
+// Limbs are base 10^9 and little endian, so that printing is cheap.
+const BASE:        u64   = 1_000_000_000;
+const BASE_DIGITS: usize = 9;
+
+#[derive(Clone, PartialEq, Eq, Debug)]
+pub struct BigInt {
+    negative: bool,
+    limbs:    Vec<u32>
+}
+
+fn trim(limbs: &mut Vec<u32>) {
+    while limbs.last() == Some(&0) {
+        limbs.pop();
+    }
+}
+
+fn cmp_limbs(a: &[u32], b: &[u32]) -> Ordering {
+    if a.len() != b.len() {
+        return a.len().cmp(&b.len());
+    }
+
+    for (x, y) in a.iter().rev().zip(b.iter().rev()) {
+        if x != y {
+            return x.cmp(y);
+        }
+    }
+
+    Ordering::Equal
+}
+
+fn add_limbs(a: &[u32], b: &[u32]) -> Vec<u32> {
+    let mut sum   = Vec::with_capacity(cmp::max(a.len(), b.len()) + 1);
+    let mut carry = 0;
+    for i in 0..cmp::max(a.len(), b.len()) {
+        let digit = *a.get(i).unwrap_or(&0) as u64 + *b.get(i).unwrap_or(&0) as u64 + carry;
+        sum.push((digit % BASE) as u32);
+        carry = digit / BASE;
+    }
+
+    if carry > 0 {
+        sum.push(carry as u32);
+    }
+
+    sum
+}
+
+// a - b, with a >= b
+fn sub_limbs(a: &[u32], b: &[u32]) -> Vec<u32> {
+    let mut difference = Vec::with_capacity(a.len());
+    let mut borrow     = 0;
+    for i in 0..a.len() {
+        let mut digit = a[i] as i64 - *b.get(i).unwrap_or(&0) as i64 - borrow;
+        borrow = if digit < 0 { 1 } else { 0 };
+        if digit < 0 {
+            digit += BASE as i64;
+        }
+
+        difference.push(digit as u32);
+    }
+
+    trim(&mut difference);
+    difference
+}
+
+fn mul_limbs(a: &[u32], b: &[u32]) -> Vec<u32> {
+    let mut product = vec![0u64; a.len() + b.len()];
+    for (i, &x) in a.iter().enumerate() {
+        let mut carry = 0;
+        for (j, &y) in b.iter().enumerate() {
+            let digit = product[i + j] + x as u64 * y as u64 + carry;
+            product[i + j] = digit % BASE;
+            carry = digit / BASE;
+        }
+
+        let mut k = i + b.len();
+        while carry > 0 {
+            let digit = product[k] + carry;
+            product[k] = digit % BASE;
+            carry = digit / BASE;
+            k += 1;
+        }
+    }
+
+    let mut product: Vec<u32> = product.into_iter().map(|digit| digit as u32).collect();
+    trim(&mut product);
+    product
+}
+
+fn mul_small(a: &[u32], m: u64) -> Vec<u32> {
+    mul_limbs(a, &[m as u32])
+}
+
+// The leading three limbs as a float, and how many limbs were left out.
+fn leading_limbs(limbs: &[u32]) -> (f64, usize) {
+    let skipped = limbs.len().saturating_sub(3);
+    (limbs[skipped..].iter().rev().fold(0.0, |leading, &limb| leading * BASE as f64 + limb as f64), skipped)
+}
+
+// Schoolbook division. Each digit of the quotient is searched between the
+// bounds given by the leading digits, narrowed down by a floating point
+// estimate that is never off by more than one.
+fn divmod_limbs(a: &[u32], b: &[u32]) -> (Vec<u32>, Vec<u32>) {
+    if cmp_limbs(a, b) == Ordering::Less {
+        return (vec![], a.to_vec());
+    }
+
+    let top          = *b.last().unwrap() as u64;
+    let mut quotient = vec![0u32; a.len()];
+    let mut rest     = vec![];
+    for i in (0..a.len()).rev() {
+        rest.insert(0, a[i]);
+        trim(&mut rest);
+
+        let leading = if rest.len() > b.len() {
+            rest[rest.len() - 1] as u64 * BASE + rest[rest.len() - 2] as u64
+        }
+        else if rest.len() == b.len() {
+            rest[rest.len() - 1] as u64
+        }
+        else {
+            0
+        };
+
+        let (mut low, mut high) = (leading / (top + 1), cmp::min(leading / top, BASE - 1));
+        if !rest.is_empty() {
+            let ((dividend, dividend_skipped), (divisor, divisor_skipped)) = (leading_limbs(&rest), leading_limbs(b));
+            let estimate = (dividend / divisor * (BASE as f64).powi(dividend_skipped as i32 - divisor_skipped as i32)) as u64;
+            low  = cmp::max(low,  estimate.saturating_sub(2));
+            high = cmp::min(high, estimate + 2);
+        }
+        while low < high {
+            let middle = (low + high + 1) / 2;
+            if cmp_limbs(&mul_small(b, middle), &rest) != Ordering::Greater {
+                low = middle;
+            }
+            else {
+                high = middle - 1;
+            }
+        }
+
+        if low > 0 {
+            rest = sub_limbs(&rest, &mul_small(b, low));
+        }
+
+        quotient[i] = low as u32;
+    }
+
+    trim(&mut quotient);
+    (quotient, rest)
+}
+
+impl BigInt {
+    fn from_limbs(negative: bool, mut limbs: Vec<u32>) -> BigInt {
+        trim(&mut limbs);
+        BigInt {
+            negative: negative && !limbs.is_empty(),
+            limbs:    limbs
+        }
+    }
+
+    pub fn from_u64(mut n: u64) -> BigInt {
+        let mut limbs = vec![];
+        while n > 0 {
+            limbs.push((n % BASE) as u32);
+            n /= BASE;
+        }
+
+        BigInt::from_limbs(false, limbs)
+    }
+
+    pub fn from_i64(n: i64) -> BigInt {
+        let magnitude = BigInt::from_u64(n.wrapping_abs() as u64);
+        if n < 0 { -&magnitude } else { magnitude }
+    }
+
+    pub fn zero() -> BigInt {
+        BigInt::from_i64(0)
+    }
+
+    pub fn one() -> BigInt {
+        BigInt::from_i64(1)
+    }
+
+    // Decimal digits, without a sign.
+    pub fn parse(digits: &str) -> Option<BigInt> {
+        if digits.is_empty() || !digits.chars().all(|c| c.is_digit(10)) {
+            return None;
+        }
+
+        let mut limbs = vec![];
+        let mut end   = digits.len();
+        while end > 0 {
+            let start = end.saturating_sub(BASE_DIGITS);
+            limbs.push(digits[start..end].parse().unwrap());
+            end = start;
+        }
+
+        Some(BigInt::from_limbs(false, limbs))
+    }
+
+    pub fn is_zero(&self) -> bool {
+        self.limbs.is_empty()
+    }
+
+    pub fn is_negative(&self) -> bool {
+        self.negative
+    }
+
+    pub fn is_even(&self) -> bool {
+        self.limbs.first().map(|limb| limb % 2 == 0).unwrap_or(true)
+    }
+
+    pub fn abs(&self) -> BigInt {
+        BigInt::from_limbs(false, self.limbs.clone())
+    }
+
+    pub fn digits(&self) -> usize {
+        match self.limbs.last() {
+            Some(top) => (self.limbs.len() - 1) * BASE_DIGITS + top.to_string().len(),
+            None      => 1
+        }
+    }
+
+    // None for negative numbers too.
+    pub fn to_u64(&self) -> Option<u64> {
+        if self.negative {
+            return None;
+        }
+
+        let mut n: u64 = 0;
+        for &limb in self.limbs.iter().rev() {
+            n = try_option!(n.checked_mul(BASE).and_then(|n| n.checked_add(limb as u64)));
+        }
+
+        Some(n)
+    }
+
+    pub fn to_i64(&self) -> Option<i64> {
+        let magnitude = try_option!(self.abs().to_u64());
+        if magnitude > i64::max_value() as u64 {
+            None
+        }
+        else if self.negative {
+            Some(-(magnitude as i64))
+        }
+        else {
+            Some(magnitude as i64)
+        }
+    }
+
+    // The leading digits as a mantissa, and the power of ten they go with, so
+    // that numbers too large for a f64 can still be compared and divided.
+    pub fn approximate(&self) -> (f64, i32) {
+        let skipped  = self.limbs.len().saturating_sub(3);
+        let mantissa = self.limbs[skipped..].iter().rev().fold(0.0, |mantissa, &limb| mantissa * BASE as f64 + limb as f64);
+
+        (if self.negative { -mantissa } else { mantissa }, (skipped * BASE_DIGITS) as i32)
+    }
+
+    pub fn to_f64(&self) -> f64 {
+        let (mantissa, exponent) = self.approximate();
+        mantissa * 10f64.powi(exponent)
+    }
+
+    // Truncated like Rust's / and %, None when dividing by zero.
+    pub fn div_rem(&self, other: &BigInt) -> Option<(BigInt, BigInt)> {
+        if other.is_zero() {
+            return None;
+        }
+
+        let (quotient, rest) = divmod_limbs(&self.limbs, &other.limbs);
+        Some((BigInt::from_limbs(self.negative != other.negative, quotient),
+              BigInt::from_limbs(self.negative, rest)))
+    }
+
+    // Rounded towards negative infinity, with a remainder of the divisor's sign.
+    pub fn div_mod_floor(&self, other: &BigInt) -> Option<(BigInt, BigInt)> {
+        let (quotient, rest) = try_option!(self.div_rem(other));
+        if !rest.is_zero() && rest.negative != other.negative {
+            Some((&quotient - &BigInt::one(), &rest + other))
+        }
+        else {
+            Some((quotient, rest))
+        }
+    }
+
+    pub fn gcd(&self, other: &BigInt) -> BigInt {
+        let (mut a, mut b) = (self.abs(), other.abs());
+        while !b.is_zero() {
+            let rest = a.div_rem(&b).unwrap().1;
+            a = b;
+            b = rest;
+        }
+
+        a
+    }
+
+    pub fn pow(&self, mut exponent: u64) -> BigInt {
+        let mut base   = self.clone();
+        let mut result = BigInt::one();
+        while exponent > 0 {
+            if exponent % 2 == 1 {
+                result = &result * &base;
+            }
+
+            exponent /= 2;
+            if exponent > 0 {
+                base = &base * &base;
+            }
+        }
+
+        result
+    }
+}
+
+impl Ord for BigInt {
+    fn cmp(&self, other: &BigInt) -> Ordering {
+        match (self.negative, other.negative) {
+            (false, true) => Ordering::Greater,
+            (true, false) => Ordering::Less,
+            (false, false) => cmp_limbs(&self.limbs, &other.limbs),
+            (true, true)   => cmp_limbs(&other.limbs, &self.limbs)
+        }
+    }
+}
+
+impl PartialOrd for BigInt {
+    fn partial_cmp(&self, other: &BigInt) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl<'a> Neg for &'a BigInt {
+    type Output = BigInt;
+
+    fn neg(self) -> BigInt {
+        BigInt::from_limbs(!self.negative, self.limbs.clone())
+    }
+}
+
+impl<'a, 'b> Add<&'b BigInt> for &'a BigInt {
+    type Output = BigInt;
+
+    fn add(self, other: &BigInt) -> BigInt {
+        if self.negative == other.negative {
+            return BigInt::from_limbs(self.negative, add_limbs(&self.limbs, &other.limbs));
+        }
+
+        match cmp_limbs(&self.limbs, &other.limbs) {
+            Ordering::Less => BigInt::from_limbs(other.negative, sub_limbs(&other.limbs, &self.limbs)),
+            _              => BigInt::from_limbs(self.negative,  sub_limbs(&self.limbs, &other.limbs))
+        }
+    }
+}
+
+impl<'a, 'b> Sub<&'b BigInt> for &'a BigInt {
+    type Output = BigInt;
+
+    fn sub(self, other: &BigInt) -> BigInt {
+        self + &-other
+    }
+}
+
+impl<'a, 'b> Mul<&'b BigInt> for &'a BigInt {
+    type Output = BigInt;
+
+    fn mul(self, other: &BigInt) -> BigInt {
+        BigInt::from_limbs(self.negative != other.negative, mul_limbs(&self.limbs, &other.limbs))
+    }
+}
+
+impl fmt::Display for BigInt {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let mut limbs = self.limbs.iter().rev();
+        match limbs.next() {
+            Some(top) => try!(write!(f, "{}{}", if self.negative { "-" } else { "" }, top)),
+            None      => { return write!(f, "0"); }
+        }
+
+        for limb in limbs {
+            try!(write!(f, "{:09}", limb));
+        }
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::BigInt;
+
+    fn big(n: &str) -> BigInt {
+        if n.starts_with('-') { -&BigInt::parse(&n[1..]).unwrap() } else { BigInt::parse(n).unwrap() }
+    }
+
+    #[test]
+    fn test_arithmetic() {
+        assert_eq!("1267650600228229401496703205376", BigInt::from_i64(2).pow(100).to_string());
+        assert_eq!(big("1000000000000000000"), &big("999999999999999999") + &BigInt::one());
+        assert_eq!(big("-1"),                  &big("999999999") - &big("1000000000"));
+        assert_eq!(big("-121932631137021795226185032733622923332237463801111263526900"),
+                   &big("123456789012345678901234567890") * &big("-987654321098765432109876543210"));
+        assert_eq!(Some((big("123456789012345678901234567890"), big("42"))),
+                   (&(&big("123456789012345678901234567890") * &big("987654321098765432109876543210")) + &big("42"))
+                       .div_rem(&big("987654321098765432109876543210")));
+        assert_eq!(Some((big("-3"), big("-1"))), big("-7").div_rem(&big("2")));
+        assert_eq!(Some((big("-4"), big("1"))),  big("-7").div_mod_floor(&big("2")));
+        assert_eq!(None, big("7").div_rem(&BigInt::zero()));
+        assert_eq!(big("6"), big("-48").gcd(&big("18")));
+        assert_eq!(Some(-9000000000), big("-9000000000").to_i64());
+        assert_eq!(None, BigInt::from_i64(10).pow(19).to_i64());
+        assert_eq!(Some(18446744073709551615), BigInt::from_u64(u64::max_value()).to_u64());
+        assert_eq!(None, BigInt::from_u64(u64::max_value()).pow(2).to_u64());
+        assert_eq!(None, big("-1").to_u64());
+        assert!(big("-5") < big("3") && big("-5") < big("-4") && big("1000000000") > big("999999999"));
+    }
+
+    #[test]
+    fn test_display() {
+        assert_eq!("0",                    BigInt::zero().to_string());
+        assert_eq!("-1000000007",          big("-0001000000007").to_string());
+        assert_eq!(31,                     BigInt::from_i64(2).pow(100).digits());
+        assert_eq!(None,                   BigInt::parse("12a"));
+        assert!((BigInt::from_i64(2).pow(100).to_f64() / 2f64.powi(100) - 1.0).abs() < 1e-15);
+    }
+}
@@ -0,0 +1,338 @@
+use std::collections::HashMap;
+use std::f64::consts;
+use time;
+use super::bigint::BigInt;
+use super::number::{Number, Ratio, MAX_DIGITS};
+use super::parser::Expr;
+
+// 807! is the largest factorial with at most MAX_DIGITS digits
+const MAX_FACTORIAL: i64 = 807;
+
+pub const CONSTANTS: [&'static str; 4] = ["pi", "e", "tau", "phi"];
+
+pub const FUNCTIONS: [&'static str; 22] = [
+    "sin", "cos", "tan", "asin", "acos", "atan", "sinh", "cosh", "tanh",
+    "exp", "ln", "log", "log2", "sqrt", "cbrt", "abs", "floor", "ceil", "round", "gamma", "min", "max"
+];
+
+#[derive(Debug, Clone, Copy)]
+pub struct Limits {
+    pub max_steps: u64,
+    // in milliseconds
+    pub timeout:   u64
+}
+
+pub struct Evaluator<'a> {
+    variables: &'a HashMap<String, Number>,
+    max_steps: u64,
+    steps:     u64,
+    deadline:  u64
+}
+
+fn constant(name: &str) -> Option<f64> {
+    match name {
+        "pi"  => Some(consts::PI),
+        "e"   => Some(consts::E),
+        "tau" => Some(2.0 * consts::PI),
+        "phi" => Some((1.0 + 5f64.sqrt()) / 2.0),
+        _     => None
+    }
+}
+
+fn is_negative(n: &Number) -> bool {
+    match *n {
+        Number::Exact(ref ratio) => ratio.numerator().is_negative(),
+        Number::Real(value)      => value < 0.0
+    }
+}
+
+fn floor(n: &Number) -> Result<Number, String> {
+    match *n {
+        Number::Exact(ref ratio) => Ok(Number::Exact(Ratio::integer(ratio.floor()))),
+        Number::Real(value)      => Number::real(value.floor())
+    }
+}
+
+// The exact square root of small perfect squares, like 16 or 9/4.
+fn exact_sqrt(n: &BigInt) -> Option<BigInt> {
+    let n     = try_option!(n.to_i64());
+    let guess = (n as f64).sqrt().round() as i64;
+    (if guess > 0 { guess - 1 } else { 0 }..guess + 2)
+        .find(|root| root.checked_mul(*root) == Some(n))
+        .map(BigInt::from_i64)
+}
+
+fn sqrt(n: &Number) -> Result<Number, String> {
+    if is_negative(n) {
+        return Err("That is not a real number".to_owned());
+    }
+
+    if let Number::Exact(ref ratio) = *n {
+        if let (Some(numerator), Some(denominator)) = (exact_sqrt(ratio.numerator()), exact_sqrt(ratio.denominator())) {
+            return Ratio::new(numerator, denominator).map(Number::Exact);
+        }
+    }
+
+    Number::real(n.to_f64().sqrt())
+}
+
+// Lanczos approximation, with the reflection formula below 1/2.
+fn gamma(x: f64) -> Result<Number, String> {
+    const G: f64 = 7.0;
+    const COEFFICIENTS: [f64; 9] = [
+        0.99999999999980993, 676.5203681218851, -1259.1392167224028,
+        771.32342877765313, -176.61502916214059, 12.507343278686905,
+        -0.13857109526572012, 9.9843695780195716e-6, 1.5056327351493116e-7
+    ];
+
+    if x <= 0.0 && x == x.floor() {
+        return Err(format!("gamma is not defined for {}", x));
+    }
+
+    if x < 0.5 {
+        let reflected = try!(gamma(1.0 - x)).to_f64();
+        return Number::real(consts::PI / ((consts::PI * x).sin() * reflected));
+    }
+
+    let x = x - 1.0;
+    let t = x + G + 0.5;
+    let sum = COEFFICIENTS.iter().enumerate().skip(1)
+        .fold(COEFFICIENTS[0], |sum, (i, coefficient)| sum + coefficient / (x + i as f64));
+
+    Number::real((2.0 * consts::PI).sqrt() * t.powf(x + 0.5) * (-t).exp() * sum)
+}
+
+impl<'a> Evaluator<'a> {
+    pub fn new(variables: &'a HashMap<String, Number>, limits: Limits) -> Evaluator<'a> {
+        Evaluator {
+            variables: variables,
+            max_steps: limits.max_steps,
+            steps:     0,
+            deadline:  time::precise_time_ns() + limits.timeout * 1_000_000
+        }
+    }
+
+    // Called before every operation, so that nothing runs unbounded.
+    fn tick(&mut self) -> Result<(), String> {
+        self.steps += 1;
+        if self.steps > self.max_steps {
+            Err("That takes too many steps".to_owned())
+        }
+        else if time::precise_time_ns() > self.deadline {
+            Err("That takes too long".to_owned())
+        }
+        else {
+            Ok(())
+        }
+    }
+
+    pub fn evaluate(&mut self, expr: &Expr) -> Result<Number, String> {
+        try!(self.tick());
+
+        match *expr {
+            Expr::Number(ref literal) => Number::parse(literal),
+            Expr::Variable(ref name)  => match constant(name) {
+                Some(value) => Number::real(value),
+                None        => self.variables.get(name).cloned().ok_or(format!("Unknown variable {}", name))
+            },
+            Expr::Negate(ref operand) => Ok(try!(self.evaluate(operand)).neg()),
+            Expr::Binary(operator, ref left, ref right) => {
+                let (left, right) = (try!(self.evaluate(left)), try!(self.evaluate(right)));
+                match operator {
+                    '+' => left.add(&right),
+                    '-' => left.sub(&right),
+                    '*' => left.mul(&right),
+                    '/' => left.div(&right),
+                    '%' => left.modulo(&right),
+                    '^' => left.pow(&right),
+                    _   => Err(format!("I don't know what {} means", operator))
+                }
+            },
+            Expr::Factorial(ref operand) => {
+                let operand = try!(self.evaluate(operand));
+                self.factorial(&operand)
+            },
+            Expr::Call(ref name, ref arguments) => {
+                let mut values = vec![];
+                for argument in arguments {
+                    values.push(try!(self.evaluate(argument)));
+                }
+
+                self.call(name, &values)
+            }
+        }
+    }
+
+    fn factorial(&mut self, n: &Number) -> Result<Number, String> {
+        let n = match n.as_integer() {
+            Some(n) => n.clone(),
+            None    => { return gamma(n.to_f64() + 1.0); }
+        };
+
+        if n.is_negative() {
+            return Err("The factorial is not defined for negative integers".to_owned());
+        }
+
+        let n = try!(n.to_i64().and_then(|n| if n > MAX_FACTORIAL { None } else { Some(n) })
+            .ok_or("That number is too large".to_owned()));
+        let mut product = BigInt::one();
+        for i in 2..n + 1 {
+            try!(self.tick());
+            product = &product * &BigInt::from_i64(i);
+            if product.digits() > MAX_DIGITS {
+                return Err("That number is too large".to_owned());
+            }
+        }
+
+        Ok(Number::Exact(Ratio::integer(product)))
+    }
+
+    fn call(&mut self, name: &str, arguments: &[Number]) -> Result<Number, String> {
+        let arity = match name {
+            "log"         => 1..3,
+            "min" | "max" => 1..usize::max_value(),
+            _ if constant(name).is_some() => 0..1,
+            _ if FUNCTIONS.contains(&name) => 1..2,
+            _             => { return Err(format!("Unknown function {}", name)); }
+        };
+
+        if arguments.len() < arity.start || arguments.len() >= arity.end {
+            return Err(format!("Wrong number of arguments for {}", name));
+        }
+
+        if let Some(value) = constant(name) {
+            return Number::real(value);
+        }
+
+        let x = &arguments[0];
+        let logarithm = |base: f64| if is_negative(x) || x.is_zero() {
+            Err("The logarithm is only defined for positive numbers".to_owned())
+        }
+        else {
+            Number::real(x.to_f64().ln() / base.ln())
+        };
+
+        match name {
+            "sin"   => Number::real(x.to_f64().sin()),
+            "cos"   => Number::real(x.to_f64().cos()),
+            "tan"   => Number::real(x.to_f64().tan()),
+            "asin"  => Number::real(x.to_f64().asin()),
+            "acos"  => Number::real(x.to_f64().acos()),
+            "atan"  => Number::real(x.to_f64().atan()),
+            "sinh"  => Number::real(x.to_f64().sinh()),
+            "cosh"  => Number::real(x.to_f64().cosh()),
+            "tanh"  => Number::real(x.to_f64().tanh()),
+            "exp"   => Number::real(x.to_f64().exp()),
+            "ln"    => logarithm(consts::E),
+            "log2"  => logarithm(2.0),
+            "log"   => match arguments.get(1) {
+                Some(base) if is_negative(base) || base.is_zero() || base.to_f64() == 1.0 => Err(format!("{} is not a valid base", base)),
+                Some(base) => logarithm(base.to_f64()),
+                None       => logarithm(10.0)
+            },
+            "sqrt"  => sqrt(x),
+            "cbrt"  => Number::real(x.to_f64().cbrt()),
+            "abs"   => Ok(if is_negative(x) { x.neg() } else { x.clone() }),
+            "floor" => floor(x),
+            "ceil"  => floor(&x.neg()).map(|n| n.neg()),
+            "round" => floor(&try!(x.add(&try!(Number::parse("0.5"))))),
+            "gamma" => match x.as_integer() {
+                Some(n) if !n.is_negative() && !n.is_zero() => self.factorial(&try!(x.sub(&Number::integer(1)))),
+                _                                           => gamma(x.to_f64())
+            },
+            "min" | "max" => {
+                let mut best = x.clone();
+                for argument in &arguments[1..] {
+                    try!(self.tick());
+                    let smaller = is_negative(&try!(argument.sub(&best)));
+                    if smaller == (name == "min") {
+                        best = argument.clone();
+                    }
+                }
+
+                Ok(best)
+            },
+            _ => Err(format!("Unknown function {}", name))
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::HashMap;
+    use super::super::parser::{parse, Statement};
+    use super::{Evaluator, Limits};
+
+    const LIMITS: Limits = Limits { max_steps: 10000, timeout: 1000 };
+
+    fn calc(input: &str) -> Result<String, String> {
+        let mut variables = HashMap::new();
+        variables.insert("x".to_owned(), super::Number::integer(3));
+
+        match try!(parse(input)) {
+            Statement::Evaluate(expr) => Evaluator::new(&variables, LIMITS).evaluate(&expr).map(|n| n.to_string()),
+            Statement::Assign(_, _)   => Err("assignment".to_owned())
+        }
+    }
+
+    #[test]
+    fn test_arithmetic() {
+        assert_eq!(Ok("7".to_owned()),   calc("1 + 2 * 3"));
+        assert_eq!(Ok("-4".to_owned()),  calc("-2^2"));
+        assert_eq!(Ok("512".to_owned()), calc("2^3^2"));
+        assert_eq!(Ok("6".to_owned()),   calc("2x"));
+        assert_eq!(Ok("1/3 ≈ 0.333333333333".to_owned()), calc("1/3"));
+        assert_eq!(Ok("2".to_owned()),   calc("-7 mod 3"));
+        assert_eq!(Ok("0".to_owned()),   calc("0.1 + 0.2 - 0.3"));
+        assert_eq!(Ok("1".to_owned()),   calc("(2^100 + 1) mod 2^100"));
+        assert_eq!(Ok("1267650600228229401496703205376".to_owned()), calc("2^100"));
+        assert!(calc("1/0").is_err());
+        assert!(calc("y + 1").is_err());
+    }
+
+    #[test]
+    fn test_factorial() {
+        assert_eq!(Ok("3628800".to_owned()), calc("10!"));
+        assert_eq!(Ok("720".to_owned()),     calc("3!!"));
+        assert_eq!(Ok("24".to_owned()),      calc("gamma(5)"));
+        assert_eq!(Ok("0.886226925453".to_owned()), calc("0.5!"));
+        assert_eq!(Ok("1.772453850906".to_owned()), calc("gamma(0.5)"));
+        assert!(calc("(-1)!").is_err());
+        assert!(calc("100000!").is_err());
+        assert!(calc("9223372036854775807!").is_err());
+        assert!(calc("gamma(-2)").is_err());
+    }
+
+    #[test]
+    fn test_functions() {
+        assert_eq!(Ok("3/2 ≈ 1.5".to_owned()), calc("sqrt(9/4)"));
+        assert_eq!(Ok("1.414213562373".to_owned()), calc("sqrt(2)"));
+        assert_eq!(Ok("3".to_owned()),  calc("log(8, 2)"));
+        assert_eq!(Ok("2".to_owned()),  calc("log(100)"));
+        assert_eq!(Ok("0".to_owned()),  calc("sin(0)"));
+        assert_eq!(Ok("-1".to_owned()), calc("cos(pi)"));
+        assert_eq!(Ok("-2".to_owned()), calc("floor(-1.5)"));
+        assert_eq!(Ok("-1".to_owned()), calc("ceil(-1.5)"));
+        assert_eq!(Ok("3".to_owned()),  calc("round(2.5)"));
+        assert_eq!(Ok("1/2 ≈ 0.5".to_owned()), calc("abs(-1/2)"));
+        assert_eq!(Ok("-5".to_owned()), calc("min(3, -5, 2)"));
+        assert_eq!(Ok("3".to_owned()),  calc("max(x, 1/2)"));
+        assert!(calc("sqrt(-1)").is_err());
+        assert!(calc("ln(0)").is_err());
+        assert!(calc("sin(1, 2)").is_err());
+        assert!(calc("frobnicate(1)").is_err());
+    }
+
+    #[test]
+    fn test_limits() {
+        let variables = HashMap::new();
+        let expr = match parse("1 + 1 + 1 + 1").unwrap() {
+            Statement::Evaluate(expr) => expr,
+            _                         => unreachable!()
+        };
+
+        assert!(Evaluator::new(&variables, Limits { max_steps: 7, timeout: 1000 }).evaluate(&expr).is_ok());
+        assert!(Evaluator::new(&variables, Limits { max_steps: 6, timeout: 1000 }).evaluate(&expr).is_err());
+        assert!(calc("2^2^2^2^2").is_err());
+    }
+}
@@ -0,0 +1,189 @@
+use std::io;
+use std::collections::HashMap;
+use irc::client::prelude::*;
+use regex::Regex;
+use plugin::{self, Plugin};
+
+register_plugin!(Calc, variables: Variables);
+
+lazy_static! {
+    static ref CALC: Regex = Regex::new(r"^!calc\s+(\S.*?)\s*$").unwrap();
+}
+
+macro_rules! try_option {
+    ($e:expr) => {
+        match $e {
+            Some(v) => v,
+            None    => { return None; }
+        }
+    }
+}
+
+pub mod bigint;
+pub mod number;
+mod parser;
+mod eval;
+
+use self::eval::{Evaluator, Limits};
+use self::number::Number;
+use self::parser::Statement;
+
+// longer expressions are refused before parsing them
+const MAX_LENGTH: usize = 300;
+
+// how many variables each user can keep, ans included
+const MAX_VARIABLES: usize = 20;
+
+// Every user has their own variables, kept until the bot restarts. The
+// result of the last calculation is always in ans.
+#[derive(Debug)]
+struct Variables {
+    by_nick: HashMap<String, HashMap<String, Number>>
+}
+
+impl Variables {
+    fn new() -> Variables {
+        Variables {
+            by_nick: HashMap::new()
+        }
+    }
+
+    fn set(&mut self, nickname: &str, name: &str, value: Number) -> Result<(), String> {
+        if eval::CONSTANTS.contains(&name) || eval::FUNCTIONS.contains(&name) {
+            return Err(format!("{} is reserved", name));
+        }
+
+        let variables = self.by_nick.entry(plugin::nick_key(nickname)).or_insert(HashMap::new());
+        if !variables.contains_key(name) && variables.len() >= MAX_VARIABLES {
+            return Err(format!("You already have {} variables", MAX_VARIABLES));
+        }
+
+        variables.insert(name.to_owned(), value);
+        Ok(())
+    }
+}
+
+impl Calc {
+    fn limits(&self, server: &IrcServer) -> Limits {
+        Limits {
+            max_steps: plugin::get_option(server, "calc.max_steps")
+                .and_then(|steps| steps.parse().ok())
+                .unwrap_or(10000),
+            timeout:   plugin::get_option(server, "calc.timeout")
+                .and_then(|timeout| timeout.parse().ok())
+                .unwrap_or(1000)
+        }
+    }
+
+    fn run(&mut self, limits: Limits, nickname: &str, input: &str) -> Result<String, String> {
+        if input.len() > MAX_LENGTH {
+            return Err("That expression is too long".to_owned());
+        }
+
+        let statement = try!(parser::parse(input));
+        let value     = {
+            let empty     = HashMap::new();
+            let variables = self.variables.by_nick.get(&plugin::nick_key(nickname)).unwrap_or(&empty);
+            let expr      = match statement {
+                Statement::Assign(_, ref expr) | Statement::Evaluate(ref expr) => expr
+            };
+
+            try!(Evaluator::new(variables, limits).evaluate(expr))
+        };
+
+        match statement {
+            Statement::Assign(ref name, _) => {
+                try!(self.variables.set(nickname, name, value.clone()));
+                try!(self.variables.set(nickname, "ans", value.clone()));
+                Ok(format!("{} = {}", name, value))
+            },
+            Statement::Evaluate(_) => {
+                try!(self.variables.set(nickname, "ans", value.clone()));
+                Ok(value.to_string())
+            }
+        }
+    }
+}
+
+impl Plugin for Calc {
+    fn is_allowed(&self, _: &IrcServer, message: &Message) -> bool {
+        match message.command {
+            Command::PRIVMSG(_, ref msg) => CALC.is_match(msg),
+            _ => false
+        }
+    }
+
+    fn execute(&mut self, server: &IrcServer, message: &Message) -> io::Result<()> {
+        match message.command {
+            Command::PRIVMSG(ref target, ref msg) => {
+                let input    = CALC.captures(msg).unwrap().at(1).unwrap();
+                let nickname = message.source_nickname().unwrap_or(target);
+                let limits   = self.limits(server);
+
+                match self.run(limits, nickname, input) {
+                    Ok(result) => server.send_privmsg(target, &result),
+                    Err(e)     => server.send_privmsg(target, &e)
+                }
+            },
+            _ => Ok(())
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use ::tests::{make_server, make_server_with_options, get_server_value};
+
+    use irc::client::prelude::*;
+
+    use plugin::Plugin;
+    use super::Calc;
+
+    #[test]
+    fn test_calc() {
+        let     server = make_server("PRIVMSG #test :!calc 2^10 + 10! mod 7\r\n");
+        let mut plugin = Calc::new();
+
+        for message in server.iter() {
+            let message = message.unwrap();
+            assert!(plugin.is_allowed(&server, &message));
+            assert!(plugin.execute(&server, &message).is_ok());
+        }
+
+        assert_eq!("PRIVMSG #test :1024\r\n", &*get_server_value(&server));
+    }
+
+    #[test]
+    fn test_variables() {
+        let     server = make_server(":Holo!holo@example.com PRIVMSG #test :!calc x = 1/3\r\n\
+                                      :Holo!holo@example.com PRIVMSG #test :!calc 3x + ans\r\n\
+                                      :Lawrence!lawrence@example.com PRIVMSG #test :!calc x\r\n\
+                                      :Lawrence!lawrence@example.com PRIVMSG #test :!calc pi = 3\r\n");
+        let mut plugin = Calc::new();
+
+        for message in server.iter() {
+            let message = message.unwrap();
+            assert!(plugin.is_allowed(&server, &message));
+            assert!(plugin.execute(&server, &message).is_ok());
+        }
+
+        assert_eq!("PRIVMSG #test :x = 1/3 ≈ 0.333333333333\r\n\
+                    PRIVMSG #test :4/3 ≈ 1.333333333333\r\n\
+                    PRIVMSG #test :Unknown variable x\r\n\
+                    PRIVMSG #test :pi is reserved\r\n", &*get_server_value(&server));
+    }
+
+    #[test]
+    fn test_limits() {
+        let     server = make_server_with_options("PRIVMSG #test :!calc 1 + 2 + 3 + 4\r\n", &[("calc.max_steps", "5")]);
+        let mut plugin = Calc::new();
+
+        for message in server.iter() {
+            let message = message.unwrap();
+            assert!(plugin.is_allowed(&server, &message));
+            assert!(plugin.execute(&server, &message).is_ok());
+        }
+
+        assert_eq!("PRIVMSG #test :That takes too many steps\r\n", &*get_server_value(&server));
+    }
+}
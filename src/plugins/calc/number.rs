@@ -0,0 +1,335 @@
+use std::fmt;
+use super::bigint::BigInt;
+
+// Exact numbers larger than this are refused, which also bounds how long a
+// single operation may take.
+pub const MAX_DIGITS: usize = 2000;
+
+// longer numbers are shown in scientific notation
+const MAX_SHOWN: usize = 300;
+
+#[derive(Clone, PartialEq, Debug)]
+pub struct Ratio {
+    numerator:   BigInt,
+    denominator: BigInt
+}
+
+impl Ratio {
+    pub fn new(numerator: BigInt, denominator: BigInt) -> Result<Ratio, String> {
+        if denominator.is_zero() {
+            return Err("Division by zero".to_owned());
+        }
+
+        let gcd = numerator.gcd(&denominator);
+        let (mut numerator, mut denominator) = (numerator.div_rem(&gcd).unwrap().0, denominator.div_rem(&gcd).unwrap().0);
+        if denominator.is_negative() {
+            numerator   = -&numerator;
+            denominator = -&denominator;
+        }
+
+        if numerator.digits() > MAX_DIGITS || denominator.digits() > MAX_DIGITS {
+            return Err("That number is too large".to_owned());
+        }
+
+        Ok(Ratio {
+            numerator:   numerator,
+            denominator: denominator
+        })
+    }
+
+    pub fn integer(n: BigInt) -> Ratio {
+        Ratio {
+            numerator:   n,
+            denominator: BigInt::one()
+        }
+    }
+
+    pub fn numerator(&self) -> &BigInt {
+        &self.numerator
+    }
+
+    pub fn denominator(&self) -> &BigInt {
+        &self.denominator
+    }
+
+    pub fn is_integer(&self) -> bool {
+        self.denominator == BigInt::one()
+    }
+
+    pub fn to_f64(&self) -> f64 {
+        let (numerator,   numerator_exponent)   = self.numerator.approximate();
+        let (denominator, denominator_exponent) = self.denominator.approximate();
+
+        numerator / denominator * 10f64.powi(numerator_exponent - denominator_exponent)
+    }
+
+    pub fn floor(&self) -> BigInt {
+        self.numerator.div_mod_floor(&self.denominator).unwrap().0
+    }
+}
+
+#[derive(Clone, PartialEq, Debug)]
+pub enum Number {
+    Exact(Ratio),
+    Real(f64)
+}
+
+// How many digits the number has, more or less, minus one.
+fn log10(n: &BigInt) -> f64 {
+    let (mantissa, exponent) = n.approximate();
+    mantissa.abs().log10() + exponent as f64
+}
+
+fn real(value: f64) -> Result<Number, String> {
+    if value.is_nan() {
+        Err("That is not a real number".to_owned())
+    }
+    else if value.is_infinite() {
+        Err("That number is too large".to_owned())
+    }
+    else {
+        Ok(Number::Real(value))
+    }
+}
+
+impl Number {
+    pub fn integer(n: i64) -> Number {
+        Number::Exact(Ratio::integer(BigInt::from_i64(n)))
+    }
+
+    pub fn real(value: f64) -> Result<Number, String> {
+        real(value)
+    }
+
+    // "42", "1.5", "6.02e23" and "1e-3" are all exact.
+    pub fn parse(literal: &str) -> Result<Number, String> {
+        let literal = literal.to_lowercase();
+        let mut parts = literal.splitn(2, 'e');
+        let mantissa  = parts.next().unwrap_or("");
+        let exponent  = match parts.next() {
+            Some(exponent) => try!(exponent.parse::<i64>().map_err(|_| format!("{} is not a number", literal))),
+            None           => 0
+        };
+
+        // checked before anything else, so that "1e-9223372036854775808" can't overflow below
+        if exponent.checked_abs().map_or(true, |exponent| exponent > MAX_DIGITS as i64) {
+            return Err("That number is too large".to_owned());
+        }
+
+        let mut mantissa = mantissa.splitn(2, '.');
+        let integer      = mantissa.next().unwrap_or("");
+        let fraction     = mantissa.next().unwrap_or("");
+        let exponent     = exponent - fraction.len() as i64;
+        if (integer.len() + fraction.len()) as i64 + exponent.abs() > MAX_DIGITS as i64 {
+            return Err("That number is too large".to_owned());
+        }
+
+        let digits = try!(BigInt::parse(&format!("{}{}", integer, fraction)).ok_or(format!("{} is not a number", literal)));
+        let ten    = BigInt::from_i64(10);
+        let ratio  = if exponent < 0 {
+            try!(Ratio::new(digits, ten.pow(-exponent as u64)))
+        }
+        else {
+            Ratio::integer(&digits * &ten.pow(exponent as u64))
+        };
+
+        Ok(Number::Exact(ratio))
+    }
+
+    pub fn to_f64(&self) -> f64 {
+        match *self {
+            Number::Exact(ref ratio) => ratio.to_f64(),
+            Number::Real(value)      => value
+        }
+    }
+
+    pub fn as_integer(&self) -> Option<&BigInt> {
+        match *self {
+            Number::Exact(ref ratio) if ratio.is_integer() => Some(ratio.numerator()),
+            _                                              => None
+        }
+    }
+
+    pub fn is_zero(&self) -> bool {
+        match *self {
+            Number::Exact(ref ratio) => ratio.numerator().is_zero(),
+            Number::Real(value)      => value == 0.0
+        }
+    }
+
+    // Like to_string, without approximating fractions or shortening integers.
+    pub fn short(&self) -> String {
+        match *self {
+            Number::Exact(ref ratio) if ratio.is_integer() => ratio.numerator().to_string(),
+            Number::Exact(ref ratio)                       => format!("{}/{}", ratio.numerator(), ratio.denominator()),
+            Number::Real(value)                            => format_real(value)
+        }
+    }
+
+    pub fn neg(&self) -> Number {
+        match *self {
+            Number::Exact(ref ratio) => Number::Exact(Ratio {
+                numerator:   -ratio.numerator(),
+                denominator: ratio.denominator().clone()
+            }),
+            Number::Real(value) => Number::Real(-value)
+        }
+    }
+
+    pub fn add(&self, other: &Number) -> Result<Number, String> {
+        match (self, other) {
+            (&Number::Exact(ref a), &Number::Exact(ref b)) => Ratio::new(
+                &(a.numerator() * b.denominator()) + &(b.numerator() * a.denominator()),
+                a.denominator() * b.denominator()
+            ).map(Number::Exact),
+            _ => real(self.to_f64() + other.to_f64())
+        }
+    }
+
+    pub fn sub(&self, other: &Number) -> Result<Number, String> {
+        self.add(&other.neg())
+    }
+
+    pub fn mul(&self, other: &Number) -> Result<Number, String> {
+        match (self, other) {
+            (&Number::Exact(ref a), &Number::Exact(ref b)) => Ratio::new(
+                a.numerator() * b.numerator(),
+                a.denominator() * b.denominator()
+            ).map(Number::Exact),
+            _ => real(self.to_f64() * other.to_f64())
+        }
+    }
+
+    pub fn div(&self, other: &Number) -> Result<Number, String> {
+        if other.is_zero() {
+            return Err("Division by zero".to_owned());
+        }
+
+        match (self, other) {
+            (&Number::Exact(ref a), &Number::Exact(ref b)) => Ratio::new(
+                a.numerator() * b.denominator(),
+                a.denominator() * b.numerator()
+            ).map(Number::Exact),
+            _ => real(self.to_f64() / other.to_f64())
+        }
+    }
+
+    // a - b * floor(a / b), so that -7 mod 3 is 2.
+    pub fn modulo(&self, other: &Number) -> Result<Number, String> {
+        let quotient = try!(self.div(other));
+        match quotient {
+            Number::Exact(ref ratio) => self.sub(&try!(other.mul(&Number::Exact(Ratio::integer(ratio.floor()))))),
+            Number::Real(quotient)   => real(self.to_f64() - other.to_f64() * quotient.floor())
+        }
+    }
+
+    // Integer exponents keep exact numbers exact.
+    pub fn pow(&self, other: &Number) -> Result<Number, String> {
+        let exponent = match other.as_integer() {
+            Some(exponent) => exponent.to_i64(),
+            None           => { return real(self.to_f64().powf(other.to_f64())); }
+        };
+
+        match (self, exponent) {
+            (&Number::Exact(ref ratio), Some(exponent)) => {
+                let digits = (log10(ratio.numerator()) + log10(ratio.denominator())) * (exponent as f64).abs();
+                if digits > MAX_DIGITS as f64 {
+                    return Err("That number is too large".to_owned());
+                }
+
+                let (numerator, denominator) = (ratio.numerator().pow(exponent.abs() as u64), ratio.denominator().pow(exponent.abs() as u64));
+                if exponent < 0 {
+                    Ratio::new(denominator, numerator).map(Number::Exact)
+                }
+                else {
+                    Ratio::new(numerator, denominator).map(Number::Exact)
+                }
+            },
+            _ => real(self.to_f64().powf(other.to_f64()))
+        }
+    }
+}
+
+fn format_real(value: f64) -> String {
+    if value == 0.0 {
+        return "0".to_owned();
+    }
+
+    if value.abs() >= 1e15 || value.abs() < 1e-6 {
+        let formatted = format!("{:.10e}", value);
+        let mut parts = formatted.splitn(2, 'e');
+        let mantissa  = parts.next().unwrap().trim_right_matches('0').trim_right_matches('.');
+        return format!("{}e{}", mantissa, parts.next().unwrap_or("0"));
+    }
+
+    let formatted = format!("{:.12}", value);
+    let formatted = formatted.trim_right_matches('0').trim_right_matches('.');
+    if formatted == "-0" { "0".to_owned() } else { formatted.to_owned() }
+}
+
+impl fmt::Display for Number {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            Number::Exact(ref ratio) if ratio.is_integer() => {
+                if ratio.numerator().digits() > MAX_SHOWN {
+                    write!(f, "{} ({} digits)", format_real(ratio.to_f64()), ratio.numerator().digits())
+                }
+                else {
+                    write!(f, "{}", ratio.numerator())
+                }
+            },
+            Number::Exact(ref ratio) => {
+                if ratio.numerator().digits() + ratio.denominator().digits() > 60 {
+                    write!(f, "≈ {}", format_real(ratio.to_f64()))
+                }
+                else {
+                    write!(f, "{}/{} ≈ {}", ratio.numerator(), ratio.denominator(), format_real(ratio.to_f64()))
+                }
+            },
+            Number::Real(value) => write!(f, "{}", format_real(value))
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::Number;
+
+    fn number(literal: &str) -> Number {
+        if literal.starts_with('-') {
+            Number::parse(&literal[1..]).unwrap().neg()
+        }
+        else {
+            Number::parse(literal).unwrap()
+        }
+    }
+
+    #[test]
+    fn test_exact() {
+        assert_eq!("3/2 ≈ 1.5",  number("1.5").to_string());
+        assert_eq!("1/1000 ≈ 0.001", number("1e-3").to_string());
+        assert_eq!("602000000000000000000000", number("6.02e23").to_string());
+        assert_eq!(Ok(number("0.3")), number("0.1").add(&number("0.2")));
+        assert_eq!(Ok(number("2")),   number("-7").modulo(&number("3")));
+        assert_eq!(Ok(number("0.5")), number("3.5").modulo(&number("1.5")));
+        assert_eq!(Ok(number("0.125")), number("2").pow(&number("-3")));
+        assert_eq!("1/3 ≈ 0.333333333333", number("1").div(&number("3")).unwrap().to_string());
+        assert!(number("1").div(&number("0")).is_err());
+        assert!(number("10").pow(&number("3000")).is_err());
+        assert_eq!(Ok(Number::integer(1)), number("1").pow(&number("1000000000")));
+        assert!(number("1").div(&number("3")).unwrap().pow(&number("1000000000")).is_err());
+        assert!(Number::parse("1e5000").is_err());
+        assert!(Number::parse("1e-9223372036854775808").is_err());
+        assert!(Number::parse("1.5e-9223372036854775808").is_err());
+        assert!(Number::parse("1e9223372036854775807").is_err());
+        assert_eq!("-3/2", number("-1.5").short());
+    }
+
+    #[test]
+    fn test_real() {
+        assert_eq!("1.414213562373", number("2").pow(&number("0.5")).unwrap().to_string());
+        assert_eq!("1.0715086072e301 (302 digits)", number("2").pow(&number("1000")).unwrap().to_string());
+        assert_eq!("1e-7", Number::Real(1e-7).to_string());
+        assert!(number("-8").pow(&number("0.5")).is_err());
+    }
+}
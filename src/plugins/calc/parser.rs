@@ -0,0 +1,314 @@
+// Nested parentheses and chains of unary operators past this are refused,
+// rather than risking the stack.
+const MAX_DEPTH: usize = 64;
+
+#[derive(PartialEq, Debug, Clone)]
+enum Token {
+    Number(String),
+    Name(String),
+    Operator(char),
+    Open,
+    Close,
+    Comma,
+    Assign
+}
+
+#[derive(PartialEq, Debug, Clone)]
+pub enum Expr {
+    // literals are kept as they were written, so that "0.1" stays exact
+    Number(String),
+    Variable(String),
+    Negate(Box<Expr>),
+    // + - * / ^ and % for mod
+    Binary(char, Box<Expr>, Box<Expr>),
+    Factorial(Box<Expr>),
+    Call(String, Vec<Expr>)
+}
+
+#[derive(PartialEq, Debug)]
+pub enum Statement {
+    Assign(String, Expr),
+    Evaluate(Expr)
+}
+
+fn tokenize(input: &str) -> Result<Vec<Token>, String> {
+    let chars: Vec<char> = input.chars().collect();
+    let mut tokens = vec![];
+    let mut i      = 0;
+    while i < chars.len() {
+        let c = chars[i];
+        if c.is_whitespace() {
+            i += 1;
+        }
+        else if c.is_digit(10) || c == '.' {
+            let start = i;
+            while i < chars.len() && (chars[i].is_digit(10) || chars[i] == '.') {
+                i += 1;
+            }
+
+            // 6.02e23, but not 2e as in 2e (2 times e)
+            if i + 1 < chars.len() && (chars[i] == 'e' || chars[i] == 'E') {
+                let sign = if chars[i + 1] == '-' || chars[i + 1] == '+' { 1 } else { 0 };
+                if chars.get(i + 1 + sign).map(|c| c.is_digit(10)).unwrap_or(false) {
+                    i += 1 + sign;
+                    while i < chars.len() && chars[i].is_digit(10) {
+                        i += 1;
+                    }
+                }
+            }
+
+            tokens.push(Token::Number(chars[start..i].iter().cloned().collect()));
+        }
+        else if c.is_alphabetic() || c == '_' {
+            let start = i;
+            while i < chars.len() && (chars[i].is_alphanumeric() || chars[i] == '_') {
+                i += 1;
+            }
+
+            let name: String = chars[start..i].iter().cloned().collect();
+            tokens.push(if name == "mod" { Token::Operator('%') } else { Token::Name(name) });
+        }
+        else {
+            tokens.push(match c {
+                '*' if chars.get(i + 1) == Some(&'*') => { i += 1; Token::Operator('^') },
+                '+' | '-' | '*' | '/' | '^' | '%' | '!' => Token::Operator(c),
+                '×' | '·' => Token::Operator('*'),
+                '÷'       => Token::Operator('/'),
+                '('       => Token::Open,
+                ')'       => Token::Close,
+                ','       => Token::Comma,
+                '='       => Token::Assign,
+                _         => { return Err(format!("I don't know what {} means", c)); }
+            });
+            i += 1;
+        }
+    }
+
+    Ok(tokens)
+}
+
+struct Parser {
+    tokens:   Vec<Token>,
+    position: usize,
+    depth:    usize
+}
+
+// expression := term (("+" | "-") term)*
+// term       := unary (("*" | "/" | "mod") unary | implicit product)*
+// unary      := ("-" | "+") unary | power
+// power      := postfix ("^" unary)?
+// postfix    := primary "!"*
+// primary    := number | name | name "(" arguments ")" | "(" expression ")"
+impl Parser {
+    fn peek(&self) -> Option<&Token> {
+        self.tokens.get(self.position)
+    }
+
+    fn next(&mut self) -> Option<Token> {
+        let token = self.tokens.get(self.position).cloned();
+        self.position += 1;
+        token
+    }
+
+    fn expect(&mut self, expected: Token, what: &str) -> Result<(), String> {
+        match self.next() {
+            Some(ref token) if *token == expected => Ok(()),
+            _                                     => Err(format!("I was expecting {}", what))
+        }
+    }
+
+    fn enter(&mut self) -> Result<(), String> {
+        self.depth += 1;
+        if self.depth > MAX_DEPTH {
+            Err("That expression is nested too deeply".to_owned())
+        }
+        else {
+            Ok(())
+        }
+    }
+
+    fn expression(&mut self) -> Result<Expr, String> {
+        try!(self.enter());
+
+        let mut left = try!(self.term());
+        loop {
+            let operator = match self.peek() {
+                Some(&Token::Operator(operator)) if operator == '+' || operator == '-' => operator,
+                _                                                                     => { break; }
+            };
+
+            self.position += 1;
+            left = Expr::Binary(operator, Box::new(left), Box::new(try!(self.term())));
+        }
+
+        self.depth -= 1;
+        Ok(left)
+    }
+
+    fn term(&mut self) -> Result<Expr, String> {
+        let mut left = try!(self.unary());
+        loop {
+            let operator = match self.peek() {
+                Some(&Token::Operator(operator)) if operator == '*' || operator == '/' || operator == '%' => {
+                    self.position += 1;
+                    operator
+                },
+                // 2pi and 3(1 + 2)
+                Some(&Token::Name(_)) | Some(&Token::Open) => '*',
+                _                                          => { break; }
+            };
+
+            left = Expr::Binary(operator, Box::new(left), Box::new(try!(self.unary())));
+        }
+
+        Ok(left)
+    }
+
+    fn unary(&mut self) -> Result<Expr, String> {
+        try!(self.enter());
+
+        let unary = match self.peek() {
+            Some(&Token::Operator('-')) => {
+                self.position += 1;
+                Expr::Negate(Box::new(try!(self.unary())))
+            },
+            Some(&Token::Operator('+')) => {
+                self.position += 1;
+                try!(self.unary())
+            },
+            _ => try!(self.power())
+        };
+
+        self.depth -= 1;
+        Ok(unary)
+    }
+
+    // right associative, and tighter than a minus on its left: -2^2 is -4
+    fn power(&mut self) -> Result<Expr, String> {
+        let base = try!(self.postfix());
+        match self.peek() {
+            Some(&Token::Operator('^')) => {
+                self.position += 1;
+                Ok(Expr::Binary('^', Box::new(base), Box::new(try!(self.unary()))))
+            },
+            _ => Ok(base)
+        }
+    }
+
+    fn postfix(&mut self) -> Result<Expr, String> {
+        let mut primary = try!(self.primary());
+        while self.peek() == Some(&Token::Operator('!')) {
+            self.position += 1;
+            primary = Expr::Factorial(Box::new(primary));
+        }
+
+        Ok(primary)
+    }
+
+    fn primary(&mut self) -> Result<Expr, String> {
+        match self.next() {
+            Some(Token::Number(literal)) => Ok(Expr::Number(literal)),
+            Some(Token::Name(name)) => {
+                if self.peek() != Some(&Token::Open) {
+                    return Ok(Expr::Variable(name));
+                }
+
+                self.position += 1;
+                let mut arguments = vec![];
+                if self.peek() == Some(&Token::Close) {
+                    self.position += 1;
+                    return Ok(Expr::Call(name, arguments));
+                }
+
+                loop {
+                    arguments.push(try!(self.expression()));
+                    match self.next() {
+                        Some(Token::Comma) => {},
+                        Some(Token::Close) => { break; },
+                        _                  => { return Err(format!("I was expecting ) after the arguments of {}", name)); }
+                    }
+                }
+
+                Ok(Expr::Call(name, arguments))
+            },
+            Some(Token::Open) => {
+                let expression = try!(self.expression());
+                try!(self.expect(Token::Close, ")"));
+                Ok(expression)
+            },
+            Some(Token::Operator(operator)) => Err(format!("I wasn't expecting {}", operator)),
+            Some(_)                         => Err("I wasn't expecting that".to_owned()),
+            None                            => Err("That expression ends too soon".to_owned())
+        }
+    }
+}
+
+// "x = 2^10" binds x, anything else is just evaluated.
+pub fn parse(input: &str) -> Result<Statement, String> {
+    let tokens = try!(tokenize(input));
+
+    let assignment = match (tokens.get(0), tokens.get(1)) {
+        (Some(&Token::Name(ref name)), Some(&Token::Assign)) => Some(name.clone()),
+        _                                                     => None
+    };
+
+    let mut parser = Parser {
+        position: if assignment.is_some() { 2 } else { 0 },
+        tokens:   tokens,
+        depth:    0
+    };
+
+    let expression = try!(parser.expression());
+    if parser.position < parser.tokens.len() {
+        return Err("I wasn't expecting that".to_owned());
+    }
+
+    Ok(match assignment {
+        Some(name) => Statement::Assign(name, expression),
+        None       => Statement::Evaluate(expression)
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use std::iter::repeat;
+    use super::{parse, Expr, Statement};
+
+    fn number(literal: &str) -> Box<Expr> {
+        Box::new(Expr::Number(literal.to_owned()))
+    }
+
+    #[test]
+    fn test_precedence() {
+        assert_eq!(Ok(Statement::Evaluate(Expr::Binary('+', number("1"), Box::new(Expr::Binary('*', number("2"), number("3")))))),
+                   parse("1 + 2 * 3"));
+        assert_eq!(Ok(Statement::Evaluate(Expr::Negate(Box::new(Expr::Binary('^', number("2"), number("2")))))),
+                   parse("-2^2"));
+        assert_eq!(Ok(Statement::Evaluate(Expr::Binary('^', number("2"), Box::new(Expr::Binary('^', number("3"), number("2")))))),
+                   parse("2 ** 3 ^ 2"));
+        assert_eq!(Ok(Statement::Evaluate(Expr::Binary('%', Box::new(Expr::Factorial(number("10"))), number("7")))),
+                   parse("10! mod 7"));
+        assert_eq!(Ok(Statement::Evaluate(Expr::Binary('*', number("2"), Box::new(Expr::Variable("pi".to_owned()))))),
+                   parse("2pi"));
+        assert_eq!(Ok(Statement::Evaluate(Expr::Number("6.02e23".to_owned()))), parse("6.02e23"));
+    }
+
+    #[test]
+    fn test_calls() {
+        assert_eq!(Ok(Statement::Assign("x".to_owned(), Expr::Call("log".to_owned(), vec![Expr::Number("8".to_owned()), Expr::Number("2".to_owned())]))),
+                   parse("x = log(8, 2)"));
+        assert_eq!(Ok(Statement::Evaluate(Expr::Call("pi".to_owned(), vec![]))), parse("pi()"));
+    }
+
+    #[test]
+    fn test_errors() {
+        assert!(parse("1 +").is_err());
+        assert!(parse("(1 + 2").is_err());
+        assert!(parse("1 + 2)").is_err());
+        assert!(parse("sin(1,").is_err());
+        assert!(parse("2 $ 3").is_err());
+        let nested: String = repeat("(").take(100).chain(Some("1")).chain(repeat(")").take(100)).collect();
+        assert!(parse(&nested).is_err());
+        assert!(parse(&repeat("-").take(100).chain(Some("1")).collect::<String>()).is_err());
+    }
+}
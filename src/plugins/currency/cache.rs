@@ -0,0 +1,108 @@
+use std::cmp;
+use std::collections::HashMap;
+use super::provider::{self, ExchangeRateProvider, Rates};
+
+const DAY: i64 = 86400;
+
+// When the providers are down, stale rates are served and a new attempt is made after this many seconds.
+pub const RETRY_AFTER: i64 = 300;
+
+// Rates are refreshed once a day at `refresh_hour` (UTC), after the providers publish
+// the new ones, or when they are older than `max_age` seconds, whichever comes first.
+#[derive(Debug)]
+pub struct Schedule {
+    pub max_age:      i64,
+    pub refresh_hour: i64
+}
+
+impl Schedule {
+    pub fn expiry(&self, fetched_at: i64) -> i64 {
+        let mut refresh_at = fetched_at - fetched_at % DAY + self.refresh_hour * 3600;
+        if refresh_at <= fetched_at {
+            refresh_at += DAY;
+        }
+
+        cmp::min(fetched_at + self.max_age, refresh_at)
+    }
+}
+
+#[derive(Debug)]
+struct Entry {
+    rates:      Rates,
+    expires_at: i64
+}
+
+#[derive(Debug)]
+pub struct RateCache {
+    entries: HashMap<String, Entry>
+}
+
+impl RateCache {
+    pub fn new() -> RateCache {
+        RateCache { entries: HashMap::new() }
+    }
+
+    pub fn latest(&mut self, providers: &[Box<ExchangeRateProvider>], base: &str, schedule: &Schedule, now: i64) -> Result<Rates, String> {
+        if let Some(entry) = self.entries.get(base) {
+            if entry.expires_at > now {
+                return Ok(entry.rates.clone());
+            }
+        }
+
+        match provider::latest(providers, base) {
+            Ok(rates) => {
+                self.entries.insert(base.to_owned(), Entry {
+                    rates:      rates.clone(),
+                    expires_at: schedule.expiry(now)
+                });
+
+                Ok(rates)
+            },
+            Err(e) => match self.entries.get_mut(base) {
+                Some(entry) => {
+                    entry.expires_at = now + RETRY_AFTER;
+                    Ok(entry.rates.clone())
+                },
+                None => Err(e)
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{RateCache, Schedule};
+    use super::super::provider::{ExchangeRateProvider, FileProvider};
+
+    const SCHEDULE: Schedule = Schedule { max_age: 86400, refresh_hour: 15 };
+
+    #[test]
+    fn test_expiry() {
+        // 2016-10-14 10:00 UTC, 2016-10-14 15:00 UTC and 2016-10-14 16:00 UTC
+        assert_eq!(1476457200, SCHEDULE.expiry(1476439200));
+        assert_eq!(1476543600, SCHEDULE.expiry(1476457200));
+        assert_eq!(1476543600, SCHEDULE.expiry(1476460800));
+
+        let schedule = Schedule { max_age: 60, refresh_hour: 15 };
+        assert_eq!(1476439260, schedule.expiry(1476439200));
+    }
+
+    #[test]
+    fn test_stale_rates() {
+        let up:   Vec<Box<ExchangeRateProvider>> = vec![Box::new(FileProvider::new("fixtures/rates.json"))];
+        let down: Vec<Box<ExchangeRateProvider>> = vec![Box::new(FileProvider::new("fixtures/missing.json"))];
+
+        let mut cache = RateCache::new();
+        assert!(cache.latest(&down, "EUR", &SCHEDULE, 0).is_err());
+
+        let rates = cache.latest(&up, "EUR", &SCHEDULE, 0).unwrap();
+        assert_eq!("2016-10-14", rates.date);
+
+        // still fresh, providers are not even asked
+        assert_eq!(rates, cache.latest(&down, "EUR", &SCHEDULE, 60).unwrap());
+
+        // expired, but the providers are down
+        assert_eq!(rates, cache.latest(&down, "EUR", &SCHEDULE, 10 * 86400).unwrap());
+        assert!(cache.latest(&down, "USD", &SCHEDULE, 10 * 86400).is_err());
+    }
+}
@@ -0,0 +1,212 @@
+use std::collections::{BTreeMap, HashMap};
+use time::{self, Duration};
+use serde_json::{self, Value};
+use super::cache::{Schedule, RETRY_AFTER};
+use super::provider::{self, ExchangeRateProvider, Rates};
+
+const SPARKS: &'static [char] = &['▁', '▂', '▃', '▄', '▅', '▆', '▇', '█'];
+
+// Every day of rates, fetched once from the providers and kept in memory,
+// so that each !fx or !fxchange doesn't download the whole history again.
+#[derive(Debug)]
+pub struct RateHistory {
+    days:       BTreeMap<String, Rates>,
+    fetched_at: i64,
+    expires_at: i64
+}
+
+impl RateHistory {
+    pub fn new() -> RateHistory {
+        RateHistory {
+            days:       BTreeMap::new(),
+            fetched_at: 0,
+            expires_at: 0
+        }
+    }
+
+    // Shaped like `{"fetched_at": 1476439200, "days": [{"base": "EUR", "date": "2016-10-14", "rates": {"USD": 1.1008}}]}`.
+    pub fn to_json(&self) -> String {
+        let days = self.days.values().map(|rates| {
+            let mut day = BTreeMap::new();
+            day.insert("base".to_owned(),  Value::String(rates.base.clone()));
+            day.insert("date".to_owned(),  Value::String(rates.date.clone()));
+            day.insert("rates".to_owned(), Value::Object(rates.rates.iter().map(|(currency, &rate)| (currency.clone(), Value::F64(rate))).collect()));
+            Value::Object(day)
+        }).collect();
+
+        let mut document = BTreeMap::new();
+        document.insert("fetched_at".to_owned(), Value::I64(self.fetched_at));
+        document.insert("days".to_owned(),       Value::Array(days));
+
+        serde_json::to_string(&Value::Object(document)).unwrap_or(String::new())
+    }
+
+    // Reads what to_json saved, which expires as if it had just been fetched.
+    pub fn from_json(json: &str, schedule: &Schedule) -> Option<RateHistory> {
+        let document: Value = try_option!(serde_json::from_str(json).ok());
+        let fetched_at      = try_option!(document.find("fetched_at").and_then(|fetched_at| fetched_at.as_i64()));
+
+        let mut days = BTreeMap::new();
+        for day in try_option!(document.find("days").and_then(|days| days.as_array())) {
+            let mut rates = HashMap::new();
+            for (currency, rate) in try_option!(day.find("rates").and_then(|rates| rates.as_object())) {
+                rates.insert(currency.clone(), try_option!(rate.as_f64()));
+            }
+
+            let date = try_option!(day.find("date").and_then(|date| date.as_str())).to_owned();
+            days.insert(date.clone(), Rates {
+                base:  try_option!(day.find("base").and_then(|base| base.as_str())).to_owned(),
+                date:  date,
+                rates: rates
+            });
+        }
+
+        if days.is_empty() {
+            return None;
+        }
+
+        Some(RateHistory {
+            days:       days,
+            fetched_at: fetched_at,
+            expires_at: schedule.expiry(fetched_at)
+        })
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.days.is_empty()
+    }
+
+    pub fn fetched_at(&self) -> i64 {
+        self.fetched_at
+    }
+
+    pub fn refresh(&mut self, providers: &[Box<ExchangeRateProvider>], schedule: &Schedule, now: i64) -> Result<(), String> {
+        if !self.days.is_empty() && self.expires_at > now {
+            return Ok(());
+        }
+
+        match provider::history(providers) {
+            Ok(days) => {
+                self.days       = days.into_iter().map(|rates| (rates.date.clone(), rates)).collect();
+                self.fetched_at = now;
+                self.expires_at = schedule.expiry(now);
+                Ok(())
+            },
+            Err(e) => {
+                if self.days.is_empty() {
+                    Err(e)
+                }
+                else {
+                    self.expires_at = now + RETRY_AFTER;
+                    Ok(())
+                }
+            }
+        }
+    }
+
+    pub fn last_date(&self) -> Option<&str> {
+        self.days.keys().next_back().map(|date| &date[..])
+    }
+
+    // The rate on the given day or, for weekends and holidays, on the last day before it.
+    pub fn on(&self, date: &str, base: &str, target: &str) -> Option<(&str, f64)> {
+        self.days.iter().rev()
+            .filter(|&(day, _)| &day[..] <= date)
+            .filter_map(|(day, rates)| rates.cross(base, target).map(|rate| (&day[..], rate)))
+            .next()
+    }
+
+    pub fn since(&self, date: &str, base: &str, target: &str) -> Vec<(&str, f64)> {
+        self.days.iter()
+            .filter(|&(day, _)| &day[..] >= date)
+            .filter_map(|(day, rates)| rates.cross(base, target).map(|rate| (&day[..], rate)))
+            .collect()
+    }
+}
+
+pub fn days_before(date: &str, days: i64) -> Option<String> {
+    let day = try_option!(time::strptime(date, "%Y-%m-%d").ok());
+    let timespec = day.to_timespec() - Duration::days(days);
+
+    time::at_utc(timespec).strftime("%Y-%m-%d").ok().map(|date| date.to_string())
+}
+
+// At most `width` bars, picking evenly spaced values when there are more.
+pub fn sparkline(values: &[f64], width: usize) -> String {
+    if values.is_empty() || width == 0 {
+        return String::new();
+    }
+
+    let step   = if values.len() > width { values.len() as f64 / width as f64 } else { 1.0 };
+    let count  = if values.len() > width { width } else { values.len() };
+    let values: Vec<f64> = (0..count).map(|i| values[(i as f64 * step) as usize]).collect();
+
+    let min = values.iter().cloned().fold(::std::f64::INFINITY, f64::min);
+    let max = values.iter().cloned().fold(::std::f64::NEG_INFINITY, f64::max);
+
+    values.iter().map(|value| {
+        if max == min {
+            SPARKS[SPARKS.len() / 2]
+        }
+        else {
+            SPARKS[((value - min) / (max - min) * (SPARKS.len() - 1) as f64).round() as usize]
+        }
+    }).collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{RateHistory, days_before, sparkline};
+    use super::super::cache::Schedule;
+    use super::super::provider::{ExchangeRateProvider, FileProvider};
+
+    #[test]
+    fn test_history() {
+        let providers: Vec<Box<ExchangeRateProvider>> = vec![Box::new(FileProvider::new("fixtures/eurofxref-hist.xml"))];
+        let schedule = Schedule { max_age: 86400, refresh_hour: 15 };
+
+        let mut history = RateHistory::new();
+        assert!(history.refresh(&providers, &schedule, 0).is_ok());
+        assert_eq!(Some("2016-10-14"), history.last_date());
+
+        assert_eq!(Some(("2016-10-07", 114.84)), history.on("2016-10-09", "EUR", "JPY"));
+        assert_eq!(Some(("2016-10-14", 1.0)),    history.on("2017-01-01", "GBP", "GBP"));
+        assert_eq!(None,                         history.on("2016-10-01", "EUR", "JPY"));
+        assert_eq!(3,                            history.since("2016-10-12", "USD", "JPY").len());
+    }
+
+    #[test]
+    fn test_json() {
+        let providers: Vec<Box<ExchangeRateProvider>> = vec![Box::new(FileProvider::new("fixtures/eurofxref-hist.xml"))];
+        let down:      Vec<Box<ExchangeRateProvider>> = vec![Box::new(FileProvider::new("fixtures/missing.json"))];
+        let schedule = Schedule { max_age: 86400, refresh_hour: 15 };
+
+        let mut history = RateHistory::new();
+        assert!(history.refresh(&providers, &schedule, 1476439200).is_ok());
+
+        // still fresh after a restart, so the providers are not even asked
+        let mut saved = RateHistory::from_json(&history.to_json(), &schedule).unwrap();
+        assert!(saved.refresh(&down, &schedule, 1476439260).is_ok());
+        assert_eq!(1476439200,                   saved.fetched_at());
+        assert_eq!(history.last_date(),          saved.last_date());
+        assert_eq!(Some(("2016-10-07", 114.84)), saved.on("2016-10-09", "EUR", "JPY"));
+
+        assert!(RateHistory::from_json("{}", &schedule).is_none());
+        assert!(RateHistory::from_json(&RateHistory::new().to_json(), &schedule).is_none());
+    }
+
+    #[test]
+    fn test_days_before() {
+        assert_eq!(Some("2016-09-14".to_owned()), days_before("2016-10-14", 30));
+        assert_eq!(Some("2015-12-31".to_owned()), days_before("2016-01-01", 1));
+        assert_eq!(None,                          days_before("yesterday", 1));
+    }
+
+    #[test]
+    fn test_sparkline() {
+        assert_eq!("▁▂▃▄▅▆▇█", sparkline(&[1.0, 2.0, 3.0, 4.0, 5.0, 6.0, 7.0, 8.0], 10));
+        assert_eq!("▁█",       sparkline(&[1.0, 2.0, 3.0, 4.0], 2));
+        assert_eq!("▅▅",       sparkline(&[1.0, 1.0], 10));
+        assert_eq!("",         sparkline(&[], 10));
+    }
+}
@@ -0,0 +1,83 @@
+use std::cmp;
+
+// Active ISO 4217 codes, plus gold and silver.
+pub const CODES: &'static [&'static str] = &[
+    "AED", "AFN", "ALL", "AMD", "ANG", "AOA", "ARS", "AUD", "AWG", "AZN",
+    "BAM", "BBD", "BDT", "BGN", "BHD", "BIF", "BMD", "BND", "BOB", "BRL",
+    "BSD", "BTN", "BWP", "BYN", "BZD", "CAD", "CDF", "CHF", "CLP", "CNY",
+    "COP", "CRC", "CUP", "CVE", "CZK", "DJF", "DKK", "DOP", "DZD", "EGP",
+    "ERN", "ETB", "EUR", "FJD", "FKP", "GBP", "GEL", "GHS", "GIP", "GMD",
+    "GNF", "GTQ", "GYD", "HKD", "HNL", "HTG", "HUF", "IDR", "ILS", "INR",
+    "IQD", "IRR", "ISK", "JMD", "JOD", "JPY", "KES", "KGS", "KHR", "KMF",
+    "KPW", "KRW", "KWD", "KYD", "KZT", "LAK", "LBP", "LKR", "LRD", "LSL",
+    "LYD", "MAD", "MDL", "MGA", "MKD", "MMK", "MNT", "MOP", "MRU", "MUR",
+    "MVR", "MWK", "MXN", "MYR", "MZN", "NAD", "NGN", "NIO", "NOK", "NPR",
+    "NZD", "OMR", "PAB", "PEN", "PGK", "PHP", "PKR", "PLN", "PYG", "QAR",
+    "RON", "RSD", "RUB", "RWF", "SAR", "SBD", "SCR", "SDG", "SEK", "SGD",
+    "SHP", "SLE", "SOS", "SRD", "SSP", "STN", "SVC", "SYP", "SZL", "THB",
+    "TJS", "TMT", "TND", "TOP", "TRY", "TTD", "TWD", "TZS", "UAH", "UGX",
+    "USD", "UYU", "UZS", "VES", "VND", "VUV", "WST", "XAF", "XAG", "XAU",
+    "XCD", "XCG", "XOF", "XPF", "YER", "ZAR", "ZMW", "ZWG"
+];
+
+pub fn is_valid(code: &str) -> bool {
+    CODES.contains(&code)
+}
+
+pub fn from_symbol(symbol: &str) -> Option<&'static str> {
+    match symbol {
+        "$" => Some("USD"),
+        "€" => Some("EUR"),
+        "£" => Some("GBP"),
+        "¥" => Some("JPY"),
+        _   => None
+    }
+}
+
+pub fn distance(a: &str, b: &str) -> usize {
+    let b: Vec<char> = b.chars().collect();
+    let mut row: Vec<usize> = (0..b.len() + 1).collect();
+
+    for (i, ca) in a.chars().enumerate() {
+        let mut previous = row[0];
+        row[0] = i + 1;
+
+        for j in 0..b.len() {
+            let current = row[j + 1];
+            row[j + 1] = if ca == b[j] {
+                previous
+            }
+            else {
+                1 + cmp::min(previous, cmp::min(row[j], row[j + 1]))
+            };
+            previous = current;
+        }
+    }
+
+    row[b.len()]
+}
+
+// The closest known code, as long as it's just a typo away.
+pub fn did_you_mean(code: &str) -> Option<&'static str> {
+    CODES.iter()
+        .map(|candidate| (distance(code, candidate), *candidate))
+        .filter(|&(distance, _)| distance <= 1)
+        .min_by_key(|&(distance, _)| distance)
+        .map(|(_, candidate)| candidate)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{is_valid, distance, did_you_mean};
+
+    #[test]
+    fn test_did_you_mean() {
+        assert!(is_valid("EUR"));
+        assert!(!is_valid("EUT"));
+
+        assert_eq!(1,           distance("EUT", "EUR"));
+        assert_eq!(3,           distance("ABC", "XYZ"));
+        assert_eq!(Some("EUR"), did_you_mean("EUT"));
+        assert_eq!(None,        did_you_mean("QQQ"));
+    }
+}
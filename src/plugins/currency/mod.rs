@@ -0,0 +1,437 @@
+use std::io;
+use irc::client::prelude::*;
+use redis::{Commands, RedisResult};
+use regex::Regex;
+use time;
+use plugin::{self, Plugin};
+use store::Store;
+
+register_plugin!(Currency, cache: RateCache, history: RateHistory);
+
+lazy_static! {
+    static ref FX:        Regex = Regex::new(r"^!fx ([A-Za-z]{3}) ([A-Za-z]{3})(?: (\d{4}-\d{2}-\d{2}))?\s*$").unwrap();
+    static ref FX_CHANGE: Regex = Regex::new(r"^!fxchange ([A-Za-z]{3}) ([A-Za-z]{3}) ([0-9]{1,4})([dwmy])\s*$").unwrap();
+}
+
+macro_rules! try_option {
+    ($e:expr) => {
+        match $e {
+            Some(v) => v,
+            None    => { return None; }
+        }
+    }
+}
+
+mod provider;
+mod cache;
+mod parse;
+mod iso4217;
+mod history;
+mod units;
+
+use self::cache::{RateCache, Schedule};
+use self::history::RateHistory;
+use self::parse::Expression;
+
+impl Currency {
+    // Turns symbols and lowercase codes into ISO 4217 codes.
+    fn resolve(&self, currency: &str) -> Result<String, String> {
+        let code = match iso4217::from_symbol(currency) {
+            Some(code) => code.to_owned(),
+            None       => currency.to_uppercase()
+        };
+
+        if iso4217::is_valid(&code) {
+            Ok(code)
+        }
+        else {
+            match iso4217::did_you_mean(&code) {
+                Some(suggestion) => Err(format!("Unknown currency {}, did you mean {}?", code, suggestion)),
+                None             => Err(format!("Unknown currency {}", code))
+            }
+        }
+    }
+
+    fn schedule(&self, server: &IrcServer) -> Schedule {
+        Schedule {
+            max_age:      plugin::get_option(server, "currency.max_age")
+                .and_then(|max_age| max_age.parse().ok())
+                .unwrap_or(86400),
+            refresh_hour: plugin::get_option(server, "currency.refresh_hour")
+                .and_then(|hour| hour.parse().ok())
+                .unwrap_or(15)
+        }
+    }
+
+    fn convert(&mut self, server: &IrcServer, _: &Message, target: &str, msg: &str) -> io::Result<()> {
+        let expression = match parse::parse(msg) {
+            Some(expression) => expression,
+            None             => { return Ok(()); }
+        };
+
+        let currencies = Some(&expression.source).into_iter()
+            .chain(expression.targets.iter())
+            .all(|currency| self.resolve(currency).is_ok());
+
+        // CUP is both a cuban peso and a cup, so the targets decide
+        if currencies {
+            self.convert_currency(server, target, &expression)
+        }
+        // "give me 5 min to think" is not a conversion, but "10 cup to kg" is a wrong one
+        else if units::parse(&expression.source).is_some() {
+            if expression.targets.iter().any(|name| units::parse(name).is_some()) {
+                self.convert_units(server, target, &expression)
+            }
+            else {
+                Ok(())
+            }
+        }
+        // "10 EUT to USD" is a typo, "took 2 car to work" is just chatter
+        else if iso4217::from_symbol(&expression.source).is_some() ||
+                expression.targets.iter().any(|currency| self.resolve(currency).is_ok()) {
+            self.convert_currency(server, target, &expression)
+        }
+        else {
+            Ok(())
+        }
+    }
+
+    fn convert_units(&self, server: &IrcServer, target: &str, expression: &Expression) -> io::Result<()> {
+        let source = units::parse(&expression.source).unwrap();
+
+        let mut converted = vec![];
+        for name in &expression.targets {
+            let unit = match units::parse(name) {
+                Some(unit) => unit,
+                None       => { return server.send_privmsg(target, &*format!("Unknown unit {}", name)); }
+            };
+
+            match units::convert(expression.value, &source, &unit) {
+                Some(value) => converted.push(format!("{} {}", units::format_value(value), name)),
+                None        => {
+                    return server.send_privmsg(target, &*format!("Cannot convert {} ({}) to {} ({})",
+                                                                 expression.source, units::dimension_name(&source.dimensions),
+                                                                 name, units::dimension_name(&unit.dimensions)));
+                }
+            }
+        }
+
+        server.send_privmsg(target, &*format!("{} {} => {}",
+                                              units::format_value(expression.value), expression.source, converted.join(", ")))
+    }
+
+    fn convert_currency(&mut self, server: &IrcServer, target: &str, expression: &Expression) -> io::Result<()> {
+        let mut currencies = vec![];
+        for currency in Some(&expression.source).into_iter().chain(expression.targets.iter()) {
+            match self.resolve(currency) {
+                Ok(code) => currencies.push(code),
+                Err(e)   => { return server.send_privmsg(target, &e); }
+            }
+        }
+
+        let source   = currencies.remove(0);
+        let schedule = self.schedule(server);
+        let rates    = match self.cache.latest(&provider::from_config(server), &source, &schedule, time::get_time().sec) {
+            Ok(rates) => rates,
+            Err(_)    => { return server.send_privmsg(target, "Error while converting given currency"); }
+        };
+
+        let converted: Vec<String> = currencies.iter().map(|currency| match rates.convert(expression.value, currency) {
+            Some(value) => format!("{:.4} {}", value, currency),
+            None        => format!("no rate for {}", currency)
+        }).collect();
+
+        let date = if rates.date.is_empty() { String::new() } else { format!(" (as of {})", rates.date) };
+        server.send_privmsg(target, &*format!("{} {} => {}{}",
+                                              expression.value, source, converted.join(", "), date))
+    }
+
+    // The history is also saved in Redis, so that a restart doesn't download it all again.
+    fn refresh_history(&mut self, server: &IrcServer) -> Result<(), String> {
+        let schedule = self.schedule(server);
+        let store    = match Store::open("currency") {
+            Ok(store) => Some(store),
+            Err(e)    => {
+                println!("Currency: cannot keep the rate history in Redis ({:?})", e);
+                None
+            }
+        };
+
+        if let Some(ref store) = store {
+            if self.history.is_empty() {
+                let saved: Option<String> = store.get(store.key("history")).unwrap_or(None);
+                if let Some(history) = saved.and_then(|json| RateHistory::from_json(&json, &schedule)) {
+                    self.history = history;
+                }
+            }
+        }
+
+        let fetched_at = self.history.fetched_at();
+        try!(self.history.refresh(&provider::from_config(server), &schedule, time::get_time().sec));
+
+        if let Some(ref store) = store {
+            if self.history.fetched_at() != fetched_at {
+                let saved: RedisResult<()> = store.set(store.key("history"), self.history.to_json());
+                if let Err(e) = saved {
+                    println!("Currency: cannot save the rate history ({:?})", e);
+                }
+            }
+        }
+
+        Ok(())
+    }
+
+    fn fx(&mut self, server: &IrcServer, _: &Message, target: &str, msg: &str) -> io::Result<()> {
+        let captures = match FX.captures(msg) {
+            Some(captures) => captures,
+            None           => { return Ok(()); }
+        };
+
+        let (base, quote) = match (self.resolve(captures.at(1).unwrap()), self.resolve(captures.at(2).unwrap())) {
+            (Ok(base), Ok(quote)) => (base, quote),
+            (Err(e), _) | (_, Err(e)) => { return server.send_privmsg(target, &e); }
+        };
+
+        if let Err(_) = self.refresh_history(server) {
+            return server.send_privmsg(target, "Error while retrieving historical rates");
+        }
+
+        let date = match captures.at(3).or(self.history.last_date()) {
+            Some(date) => date.to_owned(),
+            None       => { return server.send_privmsg(target, "Error while retrieving historical rates"); }
+        };
+
+        match self.history.on(&date, &base, &quote) {
+            Some((day, rate)) => server.send_privmsg(target, &*format!("1 {} = {:.4} {} on {}", base, rate, quote, day)),
+            None              => server.send_privmsg(target, &*format!("No rates for {}/{} on {}", base, quote, date))
+        }
+    }
+
+    fn fx_change(&mut self, server: &IrcServer, _: &Message, target: &str, msg: &str) -> io::Result<()> {
+        let captures = match FX_CHANGE.captures(msg) {
+            Some(captures) => captures,
+            None           => { return Ok(()); }
+        };
+
+        let (base, quote) = match (self.resolve(captures.at(1).unwrap()), self.resolve(captures.at(2).unwrap())) {
+            (Ok(base), Ok(quote)) => (base, quote),
+            (Err(e), _) | (_, Err(e)) => { return server.send_privmsg(target, &e); }
+        };
+
+        let period = captures.at(3).unwrap().parse::<i64>().unwrap() * match captures.at(4).unwrap() {
+            "w" => 7,
+            "m" => 30,
+            "y" => 365,
+            _   => 1
+        };
+
+        if let Err(_) = self.refresh_history(server) {
+            return server.send_privmsg(target, "Error while retrieving historical rates");
+        }
+
+        // the period ends with the most recent rates, not today
+        let since  = self.history.last_date().and_then(|date| history::days_before(date, period));
+        let series = match since {
+            Some(since) => self.history.since(&since, &base, &quote),
+            None        => vec![]
+        };
+
+        if series.len() < 2 {
+            return server.send_privmsg(target, &*format!("Not enough rates for {}/{}", base, quote));
+        }
+
+        let (first, last) = (series[0].1, series[series.len() - 1].1);
+        let rates: Vec<f64> = series.iter().map(|&(_, rate)| rate).collect();
+
+        server.send_privmsg(target, &*format!("{}/{} over {}{}: {:.4} → {:.4} ({:+.2}%) {}",
+                                              base, quote,
+                                              captures.at(3).unwrap(), captures.at(4).unwrap(),
+                                              first, last, (last / first - 1.0) * 100.0,
+                                              history::sparkline(&rates, 20)))
+    }
+}
+
+impl Plugin for Currency {
+    fn is_allowed(&self, _: &IrcServer, message: &Message) -> bool {
+        match message.command {
+            Command::PRIVMSG(_, ref msg) => FX.is_match(msg) || FX_CHANGE.is_match(msg) || parse::parse(msg).is_some(),
+            _ => false
+        }
+    }
+
+    fn execute(&mut self, server: &IrcServer, message: &Message) -> io::Result<()> {
+        match message.command {
+            Command::PRIVMSG(ref target, ref msg) => {
+                if FX.is_match(msg) {
+                    self.fx(server, message, target, msg)
+                }
+                else if FX_CHANGE.is_match(msg) {
+                    self.fx_change(server, message, target, msg)
+                }
+                else {
+                    self.convert(server, message, target, msg)
+                }
+            },
+            _ => Ok(())
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use ::tests::{make_server, make_server_with_options, get_server_value};
+
+    use irc::client::prelude::*;
+
+    use plugin::Plugin;
+    use regex::Regex;
+    use super::Currency;
+
+    #[test]
+    fn test_big_jpy_to_eur() {
+        let     server = make_server("PRIVMSG test :5000000 JPY to EUR\r\n");
+        let mut plugin = Currency::new();
+
+        for message in server.iter() {
+            let message = message.unwrap();
+            assert!(plugin.is_allowed(&server, &message));
+            assert!(plugin.execute(&server, &message).is_ok());
+        }
+
+        let regex = Regex::new(r"=> ([0-9]{2})").unwrap();
+        let msg = get_server_value(&server);
+        let captures = regex.captures(&*msg).unwrap();
+        assert!(captures.at(0).is_some());
+
+        let result = captures.at(0).unwrap()
+            .split_whitespace()
+            .last().unwrap()
+            .parse::<i32>();
+        assert!(result.is_ok());
+
+        let value = result.unwrap();
+        assert!(value > 10 && value < 100);
+    }
+
+    #[test]
+    fn test_rates_file() {
+        let     server = make_server_with_options("PRIVMSG test :10 EUR to usd\r\n",
+                                                  &[("currency.providers", "file"),
+                                                    ("currency.file.path", "fixtures/rates.json")]);
+        let mut plugin = Currency::new();
+
+        for message in server.iter() {
+            let message = message.unwrap();
+            assert!(plugin.is_allowed(&server, &message));
+            assert!(plugin.execute(&server, &message).is_ok());
+        }
+
+        assert_eq!("PRIVMSG test :10 EUR => 11.0080 USD (as of 2016-10-14)\r\n", &*get_server_value(&server));
+    }
+
+    #[test]
+    fn test_several_targets() {
+        let     server = make_server_with_options("PRIVMSG test :€1,5k in $, gbp, JPY\r\n",
+                                                  &[("currency.providers", "file"),
+                                                    ("currency.file.path", "fixtures/rates.json")]);
+        let mut plugin = Currency::new();
+
+        for message in server.iter() {
+            let message = message.unwrap();
+            assert!(plugin.is_allowed(&server, &message));
+            assert!(plugin.execute(&server, &message).is_ok());
+        }
+
+        assert_eq!("PRIVMSG test :1500 EUR => 1651.2000 USD, 1353.5250 GBP, 171765.0000 JPY (as of 2016-10-14)\r\n",
+                   &*get_server_value(&server));
+    }
+
+    #[test]
+    fn test_unknown_currency() {
+        let     server = make_server("PRIVMSG test :10 EUT to USD\r\n");
+        let mut plugin = Currency::new();
+
+        for message in server.iter() {
+            let message = message.unwrap();
+            assert!(plugin.is_allowed(&server, &message));
+            assert!(plugin.execute(&server, &message).is_ok());
+        }
+
+        assert_eq!("PRIVMSG test :Unknown currency EUT, did you mean EUR?\r\n", &*get_server_value(&server));
+    }
+
+    #[test]
+    fn test_fx() {
+        let     server = make_server_with_options("PRIVMSG test :!fx eur JPY 2016-10-09\r\n",
+                                                  &[("currency.providers", "file"),
+                                                    ("currency.file.path", "fixtures/eurofxref-hist.xml")]);
+        let mut plugin = Currency::new();
+
+        for message in server.iter() {
+            let message = message.unwrap();
+            assert!(plugin.is_allowed(&server, &message));
+            assert!(plugin.execute(&server, &message).is_ok());
+        }
+
+        assert_eq!("PRIVMSG test :1 EUR = 114.8400 JPY on 2016-10-07\r\n", &*get_server_value(&server));
+    }
+
+    #[test]
+    fn test_fx_change() {
+        let     server = make_server_with_options("PRIVMSG test :!fxchange EUR USD 1w\r\n",
+                                                  &[("currency.providers", "file"),
+                                                    ("currency.file.path", "fixtures/eurofxref-hist.xml")]);
+        let mut plugin = Currency::new();
+
+        for message in server.iter() {
+            let message = message.unwrap();
+            assert!(plugin.is_allowed(&server, &message));
+            assert!(plugin.execute(&server, &message).is_ok());
+        }
+
+        assert_eq!("PRIVMSG test :EUR/USD over 1w: 1.1140 → 1.1008 (-1.18%) ██▅▁▃▁\r\n", &*get_server_value(&server));
+    }
+
+    #[test]
+    fn test_units() {
+        let     server = make_server("PRIVMSG test :100km/h in mph, m/s\r\n");
+        let mut plugin = Currency::new();
+
+        for message in server.iter() {
+            let message = message.unwrap();
+            assert!(plugin.is_allowed(&server, &message));
+            assert!(plugin.execute(&server, &message).is_ok());
+        }
+
+        assert_eq!("PRIVMSG test :100 km/h => 62.1371 mph, 27.7778 m/s\r\n", &*get_server_value(&server));
+    }
+
+    #[test]
+    fn test_units_mismatch() {
+        let     server = make_server("PRIVMSG test :10 cup to kg\r\n");
+        let mut plugin = Currency::new();
+
+        for message in server.iter() {
+            let message = message.unwrap();
+            assert!(plugin.is_allowed(&server, &message));
+            assert!(plugin.execute(&server, &message).is_ok());
+        }
+
+        assert_eq!("PRIVMSG test :Cannot convert cup (volume) to kg (mass)\r\n", &*get_server_value(&server));
+    }
+
+    #[test]
+    fn test_not_a_conversion() {
+        let     server = make_server("PRIVMSG test :I have 2 cats in kitchen\r\nPRIVMSG test :give me 5 min to think\r\n\
+                                      PRIVMSG test :wait 2 days to reply\r\nPRIVMSG test :1 L^100 to m\r\n\
+                                      PRIVMSG test :took 2 car to work\r\n");
+        let mut plugin = Currency::new();
+
+        for message in server.iter() {
+            let message = message.unwrap();
+            assert!(plugin.execute(&server, &message).is_ok());
+        }
+
+        assert_eq!("", &*get_server_value(&server));
+    }
+}
@@ -0,0 +1,126 @@
+use regex::Regex;
+
+const UNIT: &'static str = r"(?:[$€£¥]|[^\s,?!.0-9](?:[^\s,?!]*[^\s,?!.])?)";
+
+lazy_static! {
+    // [symbol]amount[k|m|b] [unit] to|in unit[, unit...], where a unit is a currency
+    // code or symbol, or any physical unit such as km/h or °C
+    static ref RE: Regex = Regex::new(&format!(
+        r"(?i)(?:^|\s)([$€£¥])?([0-9][0-9.,]*)([kmb])?(\s*)({unit})?\s+(?:to|in)\s+({unit}(?:\s*,\s*{unit})*)\s*[?.!]*$",
+        unit = UNIT
+    )).unwrap();
+}
+
+#[derive(PartialEq, Debug)]
+pub struct Expression {
+    pub value:   f64,
+    pub source:  String,
+    pub targets: Vec<String>
+}
+
+// Accepts both 1,234.5 and 1.234,5. A lone separator followed by exactly
+// three digits is taken as a thousands separator, so 1,000 is a thousand.
+pub fn parse_number(number: &str) -> Option<f64> {
+    if number.ends_with('.') || number.ends_with(',') {
+        return None;
+    }
+
+    let decimal = match (number.rfind('.'), number.rfind(',')) {
+        (Some(dot), Some(comma)) => Some(if dot > comma { '.' } else { ',' }),
+        (Some(last), None) | (None, Some(last)) => {
+            let separator = number[last..].chars().next().unwrap();
+            if number.matches(separator).count() > 1 || number.len() - last == 4 {
+                None
+            }
+            else {
+                Some(separator)
+            }
+        },
+        (None, None) => None
+    };
+
+    if let Some(decimal) = decimal {
+        if number.matches(decimal).count() > 1 {
+            return None;
+        }
+    }
+
+    let normalised: String = number.chars()
+        .filter_map(|c| if c.is_digit(10) { Some(c) } else if Some(c) == decimal { Some('.') } else { None })
+        .collect();
+
+    normalised.parse().ok()
+}
+
+fn multiplier(suffix: Option<&str>) -> f64 {
+    match &*suffix.unwrap_or("").to_lowercase() {
+        "k" => 1e3,
+        "m" => 1e6,
+        "b" => 1e9,
+        _   => 1.0
+    }
+}
+
+pub fn parse(msg: &str) -> Option<Expression> {
+    let captures = try_option!(RE.captures(msg));
+
+    let number = try_option!(captures.at(2).and_then(parse_number));
+    let suffix = captures.at(3);
+    let spaced = captures.at(4).map_or(false, |space| !space.is_empty());
+
+    // 5k EUR is five thousand euros, while 5kg and 100m are just units
+    let (value, source) = match (suffix, captures.at(5)) {
+        (Some(suffix), Some(unit)) if !spaced => (number, format!("{}{}", suffix, unit)),
+        (Some(suffix), None) if captures.at(1).is_none() => (number, suffix.to_owned()),
+        (_, Some(unit)) => (number * multiplier(suffix), unit.to_owned()),
+        (_, None)       => (number * multiplier(suffix), try_option!(captures.at(1)).to_owned())
+    };
+
+    Some(Expression {
+        value:   value,
+        source:  source,
+        targets: try_option!(captures.at(6)).split(',').map(|target| target.trim().to_owned()).collect()
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{parse, parse_number, Expression};
+
+    #[test]
+    fn test_parse_number() {
+        assert_eq!(Some(12.5),      parse_number("12.5"));
+        assert_eq!(Some(12.5),      parse_number("12,5"));
+        assert_eq!(Some(1000.0),    parse_number("1,000"));
+        assert_eq!(Some(1000.0),    parse_number("1.000"));
+        assert_eq!(Some(1234567.0), parse_number("1,234,567"));
+        assert_eq!(Some(1234.56),   parse_number("1,234.56"));
+        assert_eq!(Some(1234.56),   parse_number("1.234,56"));
+        assert_eq!(None,            parse_number("1.2.3,4,5"));
+        assert_eq!(None,            parse_number("12."));
+    }
+
+    #[test]
+    fn test_parse() {
+        assert_eq!(Some(Expression { value: 5000000.0, source: "JPY".to_owned(), targets: vec!["EUR".to_owned()] }),
+                   parse("5000000 JPY to EUR"));
+        assert_eq!(Some(Expression { value: 5000.0, source: "$".to_owned(), targets: vec!["eur".to_owned()] }),
+                   parse("how much is $5k in eur?"));
+        assert_eq!(Some(Expression { value: 1200000.0, source: "€".to_owned(), targets: vec!["£".to_owned()] }),
+                   parse("1.2M € to £"));
+        assert_eq!(Some(Expression { value: 100.5, source: "EUR".to_owned(), targets: vec!["USD".to_owned(), "JPY".to_owned(), "GBP".to_owned()] }),
+                   parse("100,5 EUR to USD, JPY,GBP"));
+
+        assert_eq!(Some(Expression { value: 100.0, source: "km/h".to_owned(), targets: vec!["mph".to_owned()] }),
+                   parse("100km/h in mph"));
+        assert_eq!(Some(Expression { value: 300.0, source: "K".to_owned(), targets: vec!["°C".to_owned(), "F".to_owned()] }),
+                   parse("300K to °C, F"));
+        assert_eq!(Some(Expression { value: 5.0, source: "mxn".to_owned(), targets: vec!["eur".to_owned()] }),
+                   parse("5mxn to eur."));
+
+        assert_eq!(None, parse("5 EUR"));
+        assert_eq!(None, parse("went from 3 to 4"));
+        assert_eq!(None, parse("I spent 20 eur in the shop"));
+        assert_eq!(None, parse("1,000 to USD"));
+    }
+}
@@ -0,0 +1,374 @@
+use std::fmt;
+use std::fs::File;
+use std::io::Read;
+use std::collections::HashMap;
+use irc::client::prelude::*;
+use regex::Regex;
+use hyper::client::Client;
+use hyper::header::Connection;
+use serde_json::{self, Value};
+use plugin;
+
+const ECB_DAILY_URL:   &'static str = "https://www.ecb.europa.eu/stats/eurofxref/eurofxref-daily.xml";
+const ECB_HISTORY_URL: &'static str = "https://www.ecb.europa.eu/stats/eurofxref/eurofxref-hist.xml";
+
+lazy_static! {
+    static ref ECB_DATE: Regex = Regex::new(r#"time=['"](\d{4}-\d{2}-\d{2})['"]"#).unwrap();
+    static ref ECB_RATE: Regex = Regex::new(r#"currency=['"]([A-Z]{3})['"]\s+rate=['"]([0-9.]+)['"]"#).unwrap();
+}
+
+#[derive(PartialEq, Debug, Clone)]
+pub struct Rates {
+    pub base:  String,
+    pub date:  String,
+    pub rates: HashMap<String, f64>
+}
+
+impl Rates {
+    // Expresses every rate against another base, which must be one of the known currencies.
+    pub fn rebase(mut self, base: &str) -> Option<Rates> {
+        if self.base == base {
+            return Some(self);
+        }
+
+        self.rates.insert(self.base.clone(), 1.0);
+        let factor = try_option!(self.rates.get(base).cloned());
+
+        let rates = self.rates.into_iter()
+            .filter(|&(ref currency, _)| currency != base)
+            .map(|(currency, rate)| (currency, rate / factor))
+            .collect();
+
+        Some(Rates {
+            base:  base.to_owned(),
+            date:  self.date,
+            rates: rates
+        })
+    }
+
+    // How many `target` one `base` is worth, without rebasing the whole set.
+    pub fn cross(&self, base: &str, target: &str) -> Option<f64> {
+        let rate = |currency: &str| if currency == self.base { Some(1.0) } else { self.rates.get(currency).cloned() };
+        Some(try_option!(rate(target)) / try_option!(rate(base)))
+    }
+
+    pub fn convert(&self, value: f64, target: &str) -> Option<f64> {
+        if target == self.base {
+            Some(value)
+        }
+        else {
+            self.rates.get(target).map(|rate| value * rate)
+        }
+    }
+}
+
+pub trait ExchangeRateProvider: Send + Sync + fmt::Debug {
+    fn name(&self) -> &str;
+    fn latest(&self, base: &str) -> Result<Rates, String>;
+
+    // Every day of rates the provider knows about, in any base.
+    fn history(&self) -> Result<Vec<Rates>, String> {
+        Err("no historical rates".to_owned())
+    }
+}
+
+fn fetch(url: &str) -> Result<String, String> {
+    let client = Client::new();
+    let mut response = try!(client.get(url)
+        .header(Connection::close())
+        .send()
+        .map_err(|e| e.to_string()));
+
+    if !response.status.is_success() {
+        return Err(format!("{} answered {}", url, response.status));
+    }
+
+    let mut body = String::new();
+    try!(response.read_to_string(&mut body).map_err(|e| e.to_string()));
+    Ok(body)
+}
+
+// Both the daily feed and the historical ones, which have a `<Cube time="...">` for each day.
+pub fn parse_ecb_history(xml: &str) -> Vec<Rates> {
+    let days: Vec<(usize, &str)> = ECB_DATE.captures_iter(xml)
+        .filter_map(|captures| match (captures.pos(0), captures.at(1)) {
+            (Some((start, _)), Some(date)) => Some((start, date)),
+            _                              => None
+        })
+        .collect();
+
+    days.iter().enumerate().filter_map(|(i, &(start, date))| {
+        let end   = days.get(i + 1).map(|&(next, _)| next).unwrap_or(xml.len());
+        let mut rates = HashMap::new();
+
+        for captures in ECB_RATE.captures_iter(&xml[start..end]) {
+            let currency = try_option!(captures.at(1));
+            let rate     = try_option!(captures.at(2).and_then(|rate| rate.parse().ok()));
+            rates.insert(currency.to_owned(), rate);
+        }
+
+        if rates.is_empty() {
+            None
+        }
+        else {
+            Some(Rates {
+                base:  "EUR".to_owned(),
+                date:  date.to_owned(),
+                rates: rates
+            })
+        }
+    }).collect()
+}
+
+pub fn parse_ecb(xml: &str) -> Option<Rates> {
+    parse_ecb_history(xml).into_iter().next()
+}
+
+// Paths are dot-separated, as in `data.rates`.
+pub fn parse_json(json: &str, rates_path: &str, base_path: &str, date_path: &str, default_base: &str) -> Option<Rates> {
+    let document: Value = try_option!(serde_json::from_str(json).ok());
+
+    let mut rates = HashMap::new();
+    for (currency, rate) in try_option!(document.lookup(rates_path).and_then(|rates| rates.as_object())) {
+        rates.insert(currency.to_uppercase(), try_option!(rate.as_f64()));
+    }
+
+    Some(Rates {
+        base:  document.lookup(base_path).and_then(|base| base.as_str()).unwrap_or(default_base).to_uppercase(),
+        date:  document.lookup(date_path).and_then(|date| date.as_str()).unwrap_or("").to_owned(),
+        rates: rates
+    })
+}
+
+// Daily reference rates published by the European Central Bank, based on EUR.
+#[derive(Debug)]
+pub struct EcbProvider;
+
+impl ExchangeRateProvider for EcbProvider {
+    fn name(&self) -> &str {
+        "ecb"
+    }
+
+    fn latest(&self, base: &str) -> Result<Rates, String> {
+        let xml = try!(fetch(ECB_DAILY_URL));
+
+        parse_ecb(&xml)
+            .ok_or("malformed ECB feed".to_owned())
+            .and_then(|rates| rates.rebase(base).ok_or(format!("unknown currency {}", base)))
+    }
+
+    fn history(&self) -> Result<Vec<Rates>, String> {
+        let xml  = try!(fetch(ECB_HISTORY_URL));
+        let days = parse_ecb_history(&xml);
+
+        if days.is_empty() { Err("malformed ECB feed".to_owned()) } else { Ok(days) }
+    }
+}
+
+// Rates read from a local file, either a copy of an ECB feed (.xml) or
+// a JSON file shaped like `{"base": "EUR", "date": "2016-10-01", "rates": {"USD": 1.12}}`.
+#[derive(Debug)]
+pub struct FileProvider {
+    path: String
+}
+
+impl FileProvider {
+    pub fn new(path: &str) -> FileProvider {
+        FileProvider { path: path.to_owned() }
+    }
+
+    fn read(&self) -> Result<Vec<Rates>, String> {
+        let mut content = String::new();
+        try!(File::open(&self.path)
+             .and_then(|mut file| file.read_to_string(&mut content))
+             .map_err(|e| format!("{}: {}", self.path, e)));
+
+        let days = if self.path.ends_with(".xml") {
+            parse_ecb_history(&content)
+        }
+        else {
+            parse_json(&content, "rates", "base", "date", "EUR").into_iter().collect()
+        };
+
+        if days.is_empty() { Err(format!("malformed rates file {}", self.path)) } else { Ok(days) }
+    }
+}
+
+impl ExchangeRateProvider for FileProvider {
+    fn name(&self) -> &str {
+        "file"
+    }
+
+    fn latest(&self, base: &str) -> Result<Rates, String> {
+        let days = try!(self.read());
+
+        days.into_iter()
+            .max_by_key(|rates| rates.date.clone())
+            .and_then(|rates| rates.rebase(base))
+            .ok_or(format!("unknown currency {}", base))
+    }
+
+    fn history(&self) -> Result<Vec<Rates>, String> {
+        self.read()
+    }
+}
+
+// Any HTTP API answering with JSON. `{base}` in the URL is replaced with the requested base currency.
+#[derive(Debug)]
+pub struct JsonProvider {
+    url:        String,
+    rates_path: String,
+    base_path:  String,
+    date_path:  String
+}
+
+impl JsonProvider {
+    pub fn new(url: &str, rates_path: &str, base_path: &str, date_path: &str) -> JsonProvider {
+        JsonProvider {
+            url:        url.to_owned(),
+            rates_path: rates_path.to_owned(),
+            base_path:  base_path.to_owned(),
+            date_path:  date_path.to_owned()
+        }
+    }
+}
+
+impl ExchangeRateProvider for JsonProvider {
+    fn name(&self) -> &str {
+        "json"
+    }
+
+    fn latest(&self, base: &str) -> Result<Rates, String> {
+        let json = try!(fetch(&self.url.replace("{base}", base)));
+
+        parse_json(&json, &self.rates_path, &self.base_path, &self.date_path, base)
+            .ok_or("malformed JSON response".to_owned())
+            .and_then(|rates| rates.rebase(base).ok_or(format!("unknown currency {}", base)))
+    }
+}
+
+// Builds the providers listed in `currency.providers` (by default just `ecb`), in order of preference.
+pub fn from_config(server: &IrcServer) -> Vec<Box<ExchangeRateProvider>> {
+    let names = plugin::get_option(server, "currency.providers").unwrap_or("ecb");
+
+    names.split(',').filter_map(|name| match name.trim() {
+        "ecb"  => Some(Box::new(EcbProvider) as Box<ExchangeRateProvider>),
+        "file" => plugin::get_option(server, "currency.file.path")
+            .map(|path| Box::new(FileProvider::new(path)) as Box<ExchangeRateProvider>),
+        "json" => plugin::get_option(server, "currency.json.url")
+            .map(|url| Box::new(JsonProvider::new(url,
+                                                  plugin::get_option(server, "currency.json.rates_path").unwrap_or("rates"),
+                                                  plugin::get_option(server, "currency.json.base_path").unwrap_or("base"),
+                                                  plugin::get_option(server, "currency.json.date_path").unwrap_or("date")))
+                 as Box<ExchangeRateProvider>),
+        _ => None
+    }).collect()
+}
+
+// Every day of rates from the first provider that has a history.
+pub fn history(providers: &[Box<ExchangeRateProvider>]) -> Result<Vec<Rates>, String> {
+    let mut errors = vec![];
+
+    for provider in providers {
+        match provider.history() {
+            Ok(days) => { return Ok(days); },
+            Err(e)   => errors.push(format!("{}: {}", provider.name(), e))
+        }
+    }
+
+    if errors.is_empty() {
+        Err("no exchange rate provider configured".to_owned())
+    }
+    else {
+        Err(errors.join(", "))
+    }
+}
+
+// Asks each provider in turn, until one of them answers.
+pub fn latest(providers: &[Box<ExchangeRateProvider>], base: &str) -> Result<Rates, String> {
+    let mut errors = vec![];
+
+    for provider in providers {
+        match provider.latest(base) {
+            Ok(rates) => { return Ok(rates); },
+            Err(e)    => errors.push(format!("{}: {}", provider.name(), e))
+        }
+    }
+
+    if errors.is_empty() {
+        Err("no exchange rate provider configured".to_owned())
+    }
+    else {
+        Err(errors.join(", "))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{parse_ecb, parse_ecb_history, parse_json, latest, history, ExchangeRateProvider, FileProvider};
+
+    const ECB_FEED: &'static str = r#"<gesmes:Envelope>
+        <Cube>
+            <Cube time='2016-10-14'>
+                <Cube currency='USD' rate='1.1008'/>
+                <Cube currency='JPY' rate='114.51'/>
+            </Cube>
+        </Cube>
+    </gesmes:Envelope>"#;
+
+    #[test]
+    fn test_parse_ecb() {
+        let rates = parse_ecb(ECB_FEED).unwrap();
+        assert_eq!("EUR",        rates.base);
+        assert_eq!("2016-10-14", rates.date);
+        assert_eq!(Some(1.1008), rates.convert(1.0, "USD"));
+
+        let rates = rates.rebase("USD").unwrap();
+        assert!((rates.convert(1.1008, "EUR").unwrap() - 1.0).abs() < 1e-12);
+        assert!(rates.rates.contains_key("JPY"));
+        assert!(!rates.rates.contains_key("USD"));
+        assert_eq!(Some(114.51 / 1.1008), rates.cross("USD", "JPY"));
+    }
+
+    #[test]
+    fn test_parse_ecb_history() {
+        let days = parse_ecb_history(&ECB_FEED.replace("<Cube>", "<Cube><Cube time='2016-10-13'><Cube currency='USD' rate='1.1'/></Cube>"));
+        assert_eq!(2,            days.len());
+        assert_eq!("2016-10-13", days[0].date);
+        assert_eq!(1,            days[0].rates.len());
+        assert_eq!("2016-10-14", days[1].date);
+        assert_eq!(2,            days[1].rates.len());
+    }
+
+    #[test]
+    fn test_parse_json() {
+        let json  = r#"{"data": {"quotes": {"usd": 2.0}, "day": "2016-10-14"}}"#;
+        let rates = parse_json(json, "data.quotes", "data.base", "data.day", "GBP").unwrap();
+
+        assert_eq!("GBP",        rates.base);
+        assert_eq!("2016-10-14", rates.date);
+        assert_eq!(Some(4.0),    rates.convert(2.0, "USD"));
+    }
+
+    #[test]
+    fn test_failover() {
+        let providers: Vec<Box<ExchangeRateProvider>> = vec![
+            Box::new(FileProvider::new("fixtures/missing.json")),
+            Box::new(FileProvider::new("fixtures/rates.json"))
+        ];
+
+        let rates = latest(&providers, "EUR").unwrap();
+        assert_eq!("2016-10-14", rates.date);
+        assert!(latest(&providers[..1], "EUR").is_err());
+        assert!(latest(&[], "EUR").is_err());
+
+        let providers: Vec<Box<ExchangeRateProvider>> = vec![
+            Box::new(FileProvider::new("fixtures/rates.json")),
+            Box::new(FileProvider::new("fixtures/eurofxref-hist.xml"))
+        ];
+
+        assert_eq!(1, history(&providers).unwrap().len());
+        assert_eq!(8, history(&providers[1..]).unwrap().len());
+        assert_eq!("2016-10-14", latest(&providers[1..], "USD").unwrap().date);
+    }
+}
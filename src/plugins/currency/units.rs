@@ -0,0 +1,296 @@
+// Physical units, for the same "X unit to unit" phrasing used for currencies.
+// Every unit is expressed as a factor (and, for temperatures, an offset) over the
+// base units of its dimensions: metre, kilogram, second, kelvin and byte.
+
+type Dimensions = [i8; 5];
+
+const NONE:        Dimensions = [0, 0, 0, 0, 0];
+const LENGTH:      Dimensions = [1, 0, 0, 0, 0];
+const AREA:        Dimensions = [2, 0, 0, 0, 0];
+const VOLUME:      Dimensions = [3, 0, 0, 0, 0];
+const MASS:        Dimensions = [0, 1, 0, 0, 0];
+const TIME:        Dimensions = [0, 0, 1, 0, 0];
+const TEMPERATURE: Dimensions = [0, 0, 0, 1, 0];
+const DATA:        Dimensions = [0, 0, 0, 0, 1];
+const SPEED:       Dimensions = [1, 0, -1, 0, 0];
+
+const BASE_UNITS: [&'static str; 5] = ["m", "kg", "s", "K", "B"];
+
+const DIMENSION_NAMES: &'static [(Dimensions, &'static str)] = &[
+    (NONE,                "dimensionless"),
+    (LENGTH,              "length"),
+    (AREA,                "area"),
+    (VOLUME,              "volume"),
+    (MASS,                "mass"),
+    (TIME,                "time"),
+    (TEMPERATURE,         "temperature"),
+    (DATA,                "data size"),
+    (SPEED,               "speed"),
+    ([1, 0, -2, 0, 0],    "acceleration"),
+    ([0, 0, -1, 0, 1],    "data rate")
+];
+
+struct Definition {
+    names:      &'static [&'static str],
+    factor:     f64,
+    offset:     f64,
+    dimensions: Dimensions,
+    prefixed:   bool
+}
+
+macro_rules! unit {
+    ($names: expr, $factor: expr, $dimensions: expr) => {
+        Definition { names: $names, factor: $factor, offset: 0.0, dimensions: $dimensions, prefixed: false }
+    };
+
+    ($names: expr, $factor: expr, $dimensions: expr, prefixed) => {
+        Definition { names: $names, factor: $factor, offset: 0.0, dimensions: $dimensions, prefixed: true }
+    };
+}
+
+static DEFINITIONS: &'static [Definition] = &[
+    unit!(&["m", "meter", "meters", "metre", "metres"],            1.0,            LENGTH, prefixed),
+    unit!(&["in", "inch", "inches", "\""],                        0.0254,         LENGTH),
+    unit!(&["ft", "foot", "feet", "'"],                           0.3048,         LENGTH),
+    unit!(&["yd", "yard", "yards"],                               0.9144,         LENGTH),
+    unit!(&["mi", "mile", "miles"],                               1609.344,       LENGTH),
+    unit!(&["nmi"],                                               1852.0,         LENGTH),
+    unit!(&["au"],                                                149597870700.0, LENGTH),
+    unit!(&["ly", "lightyear", "lightyears"],                     9460730472580800.0, LENGTH),
+
+    unit!(&["ha", "hectare", "hectares"],                         1e4,            AREA),
+    unit!(&["acre", "acres"],                                     4046.8564224,   AREA),
+
+    unit!(&["l", "L", "liter", "liters", "litre", "litres"],      1e-3,           VOLUME, prefixed),
+    unit!(&["gal", "gallon", "gallons"],                          3.785411784e-3, VOLUME),
+    unit!(&["qt", "quart", "quarts"],                             9.46352946e-4,  VOLUME),
+    unit!(&["pt", "pint", "pints"],                               4.73176473e-4,  VOLUME),
+    unit!(&["cup", "cups"],                                       2.365882365e-4, VOLUME),
+    unit!(&["floz", "fl.oz"],                                     2.95735295625e-5, VOLUME),
+    unit!(&["tbsp", "tablespoon", "tablespoons"],                 1.478676478125e-5, VOLUME),
+    unit!(&["tsp", "teaspoon", "teaspoons"],                      4.92892159375e-6, VOLUME),
+
+    unit!(&["g", "gram", "grams"],                                1e-3,           MASS, prefixed),
+    unit!(&["t", "tonne", "tonnes"],                              1e3,            MASS),
+    unit!(&["lb", "lbs", "pound", "pounds"],                      0.45359237,     MASS),
+    unit!(&["oz", "ounce", "ounces"],                             0.028349523125, MASS),
+    unit!(&["st", "stone", "stones"],                             6.35029318,     MASS),
+
+    unit!(&["s", "sec", "second", "seconds"],                     1.0,            TIME, prefixed),
+    unit!(&["min", "minute", "minutes"],                          60.0,           TIME),
+    unit!(&["h", "hr", "hour", "hours"],                          3600.0,         TIME),
+    unit!(&["d", "day", "days"],                                  86400.0,        TIME),
+    unit!(&["wk", "week", "weeks"],                               604800.0,       TIME),
+    unit!(&["yr", "year", "years"],                               31557600.0,     TIME),
+
+    unit!(&["mph"],                                               0.44704,        SPEED),
+    unit!(&["kph", "kmh"],                                        1.0 / 3.6,      SPEED),
+    unit!(&["kn", "kt", "knot", "knots"],                         1852.0 / 3600.0, SPEED),
+
+    unit!(&["B", "byte", "bytes"],                                1.0,            DATA, prefixed),
+    unit!(&["b", "bit", "bits"],                                  0.125,          DATA, prefixed),
+    unit!(&["kb", "KB"],                                          1e3,            DATA),
+    unit!(&["mb"],                                                1e6,            DATA),
+    unit!(&["gb"],                                                1e9,            DATA),
+    unit!(&["tb"],                                                1e12,           DATA),
+
+    Definition { names: &["K", "kelvin"],                         factor: 1.0,       offset: 0.0,    dimensions: TEMPERATURE, prefixed: false },
+    Definition { names: &["C", "°C", "celsius", "degC"],          factor: 1.0,       offset: 273.15, dimensions: TEMPERATURE, prefixed: false },
+    Definition { names: &["F", "°F", "fahrenheit", "degF"],       factor: 5.0 / 9.0, offset: 459.67, dimensions: TEMPERATURE, prefixed: false }
+];
+
+// m^9 is already far beyond anything useful, and keeps the dimensions within an i8
+const MAX_EXPONENT: i32 = 9;
+
+// Binary prefixes come first, so that Ki is not read as kilo-i.
+const PREFIXES: &'static [(&'static str, f64)] = &[
+    ("Ki", 1024.0), ("Mi", 1048576.0), ("Gi", 1073741824.0), ("Ti", 1099511627776.0),
+    ("da", 1e1),
+    ("E", 1e18), ("P", 1e15), ("T", 1e12), ("G", 1e9), ("M", 1e6), ("k", 1e3), ("h", 1e2),
+    ("d", 1e-1), ("c", 1e-2), ("m", 1e-3), ("µ", 1e-6), ("u", 1e-6), ("n", 1e-9), ("p", 1e-12), ("f", 1e-15)
+];
+
+#[derive(PartialEq, Debug, Clone)]
+pub struct Unit {
+    pub factor:     f64,
+    pub offset:     f64,
+    pub dimensions: Dimensions
+}
+
+fn find(name: &str) -> Option<&'static Definition> {
+    DEFINITIONS.iter().find(|definition| definition.names.contains(&name))
+}
+
+fn lookup(name: &str) -> Option<Unit> {
+    if let Some(definition) = find(name) {
+        return Some(Unit {
+            factor:     definition.factor,
+            offset:     definition.offset,
+            dimensions: definition.dimensions
+        });
+    }
+
+    for &(prefix, multiplier) in PREFIXES {
+        if name.len() > prefix.len() && name.starts_with(prefix) {
+            if let Some(definition) = find(&name[prefix.len()..]) {
+                if definition.prefixed {
+                    return Some(Unit {
+                        factor:     definition.factor * multiplier,
+                        offset:     0.0,
+                        dimensions: definition.dimensions
+                    });
+                }
+            }
+        }
+    }
+
+    let lowercase = name.to_lowercase();
+    if lowercase != name { lookup(&lowercase) } else { None }
+}
+
+// A single unit with an optional exponent, as in m, m^2, m² or s^-1.
+fn parse_term(term: &str) -> Option<(Unit, i32)> {
+    let (name, exponent): (&str, i32) = if term.ends_with('²') {
+        (&term[..term.len() - '²'.len_utf8()], 2)
+    }
+    else if term.ends_with('³') {
+        (&term[..term.len() - '³'.len_utf8()], 3)
+    }
+    else {
+        match term.find('^') {
+            Some(i) => (&term[..i], try_option!(term[i + 1..].parse().ok())),
+            None    => (term, 1)
+        }
+    };
+
+    if exponent.abs() > MAX_EXPONENT {
+        return None;
+    }
+
+    lookup(name).map(|unit| (unit, exponent))
+}
+
+// Compound units are read left to right, so km/h, m/s^2 and kW*h all work.
+pub fn parse(unit: &str) -> Option<Unit> {
+    if let Some(unit) = lookup(unit) {
+        return Some(unit);
+    }
+
+    let mut terms = vec![];
+    let mut start = 0;
+    let mut sign  = 1;
+    for (i, c) in unit.char_indices() {
+        if c == '/' || c == '*' || c == '·' {
+            terms.push((sign, &unit[start..i]));
+            sign  = if c == '/' { -1 } else { 1 };
+            start = i + c.len_utf8();
+        }
+    }
+    terms.push((sign, &unit[start..]));
+
+    let mut result = Unit {
+        factor:     1.0,
+        offset:     0.0,
+        dimensions: NONE
+    };
+
+    for (sign, term) in terms {
+        let (unit, exponent) = try_option!(parse_term(term));
+        let exponent = sign * exponent;
+
+        // °C/s or °F² make no sense
+        if unit.offset != 0.0 {
+            return None;
+        }
+
+        result.factor *= unit.factor.powi(exponent);
+        for (dimension, power) in result.dimensions.iter_mut().zip(unit.dimensions.iter()) {
+            *dimension = try_option!(power.checked_mul(exponent as i8).and_then(|power| dimension.checked_add(power)));
+        }
+    }
+
+    Some(result)
+}
+
+pub fn convert(value: f64, from: &Unit, to: &Unit) -> Option<f64> {
+    if from.dimensions != to.dimensions {
+        return None;
+    }
+
+    Some((value + from.offset) * from.factor / to.factor - to.offset)
+}
+
+pub fn dimension_name(dimensions: &Dimensions) -> String {
+    if let Some(&(_, name)) = DIMENSION_NAMES.iter().find(|&&(known, _)| known == *dimensions) {
+        return name.to_owned();
+    }
+
+    let parts: Vec<String> = BASE_UNITS.iter().zip(dimensions.iter())
+        .filter(|&(_, power)| *power != 0)
+        .map(|(unit, power)| if *power == 1 { unit.to_string() } else { format!("{}^{}", unit, power) })
+        .collect();
+
+    parts.join("·")
+}
+
+pub fn format_value(value: f64) -> String {
+    if value != 0.0 && (value.abs() < 1e-4 || value.abs() >= 1e15) {
+        return format!("{:.4e}", value);
+    }
+
+    let formatted = format!("{:.4}", value);
+    formatted.trim_right_matches('0').trim_right_matches('.').to_owned()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{parse, convert, dimension_name, format_value};
+
+    fn convert_str(value: f64, from: &str, to: &str) -> Option<String> {
+        convert(value, &parse(from).unwrap(), &parse(to).unwrap()).map(format_value)
+    }
+
+    #[test]
+    fn test_parse() {
+        assert!(parse("km").is_some());
+        assert!(parse("µs").is_some());
+        assert!(parse("KiB").is_some());
+        assert!(parse("m/s^2").is_some());
+        assert!(parse("Meters").is_some());
+        assert!(parse("°C/s").is_none());
+        assert!(parse("ft/°C").is_none());
+        assert!(parse("EUR").is_none());
+        assert!(parse("xyz").is_none());
+        assert!(parse("L^100").is_none());
+        assert!(parse("m^9*m^9*m^9*m^9*m^9*m^9*m^9*m^9*m^9*m^9*m^9*m^9*m^9*m^9*m^9").is_none());
+    }
+
+    #[test]
+    fn test_convert() {
+        assert_eq!(Some("62.1371".to_owned()), convert_str(100.0, "km/h", "mph"));
+        assert_eq!(Some("212".to_owned()),     convert_str(100.0, "°C", "F"));
+        assert_eq!(Some("-40".to_owned()),     convert_str(-40.0, "F", "C"));
+        assert_eq!(Some("0".to_owned()),       convert_str(273.15, "K", "C"));
+        assert_eq!(Some("5280".to_owned()),    convert_str(1.0, "mi", "ft"));
+        assert_eq!(Some("1024".to_owned()),    convert_str(1.0, "MiB", "KiB"));
+        assert_eq!(Some("12.5".to_owned()),    convert_str(100.0, "Mb/s", "MB/s"));
+        assert_eq!(Some("1000".to_owned()),    convert_str(1.0, "m³", "L"));
+        assert_eq!(Some("90".to_owned()),      convert_str(1.5, "h", "min"));
+        assert_eq!(Some("2.2046".to_owned()),  convert_str(1.0, "kg", "lb"));
+        assert_eq!(None,                       convert_str(1.0, "kg", "m"));
+    }
+
+    #[test]
+    fn test_dimension_name() {
+        assert_eq!("speed",      dimension_name(&parse("mph").unwrap().dimensions));
+        assert_eq!("mass",       dimension_name(&parse("oz").unwrap().dimensions));
+        assert_eq!("m·kg·s^-2",  dimension_name(&parse("kg*m/s^2").unwrap().dimensions));
+    }
+
+    #[test]
+    fn test_format_value() {
+        assert_eq!("1.5",        format_value(1.5));
+        assert_eq!("0",          format_value(0.0));
+        assert_eq!("1.0000e-6",  format_value(0.000001));
+    }
+}
@@ -0,0 +1,116 @@
+use std::io;
+use irc::client::prelude::*;
+use regex::Regex;
+use plugin::Plugin;
+
+register_plugin!(Dice, rng: Rng);
+
+lazy_static! {
+    static ref ROLL: Regex = Regex::new(r"^!roll\s+(\S.*?)\s*$").unwrap();
+}
+
+mod rng;
+mod notation;
+
+use self::rng::Rng;
+
+// what's left of the 512 bytes of a line after the prefix and the target
+const MAX_REPLY: usize = 400;
+
+impl Dice {
+    // "Holo rolls initiative: 2d20kh1 [17, (4)] + 5 = 22", without listing
+    // the dice when there are too many of them.
+    fn roll(&mut self, nickname: Option<&str>, input: &str) -> Result<String, String> {
+        let roll = try!(notation::parse(input));
+        let (detailed, short, total) = roll.roll(&mut self.rng);
+
+        let who = match (nickname, roll.name) {
+            (Some(nickname), Some(name)) => format!("{} rolls {}: ", nickname, name),
+            (Some(nickname), None)       => format!("{} rolls ", nickname),
+            (None, Some(name))           => format!("{}: ", name),
+            (None, None)                 => String::new()
+        };
+
+        let reply = format!("{}{} = {}", who, detailed, total);
+        if reply.len() > MAX_REPLY {
+            Ok(format!("{}{} = {}", who, short, total))
+        }
+        else {
+            Ok(reply)
+        }
+    }
+}
+
+impl Plugin for Dice {
+    fn is_allowed(&self, _: &IrcServer, message: &Message) -> bool {
+        match message.command {
+            Command::PRIVMSG(_, ref msg) => ROLL.is_match(msg),
+            _ => false
+        }
+    }
+
+    fn execute(&mut self, server: &IrcServer, message: &Message) -> io::Result<()> {
+        match message.command {
+            Command::PRIVMSG(ref target, ref msg) => {
+                let input = ROLL.captures(msg).unwrap().at(1).unwrap();
+                match self.roll(message.source_nickname(), input) {
+                    Ok(result) => server.send_privmsg(target, &result),
+                    Err(e)     => server.send_privmsg(target, &e)
+                }
+            },
+            _ => Ok(())
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::iter::repeat;
+    use ::tests::{make_server, get_server_value};
+
+    use irc::client::prelude::*;
+
+    use plugin::Plugin;
+    use super::{Dice, MAX_REPLY};
+    use super::rng::Rng;
+
+    fn replies(server: IrcServer) -> String {
+        let mut plugin = Dice { rng: Rng::seeded(42) };
+
+        for message in server.iter() {
+            let message = message.unwrap();
+            assert!(plugin.is_allowed(&server, &message));
+            assert!(plugin.execute(&server, &message).is_ok());
+        }
+
+        get_server_value(&server)
+    }
+
+    #[test]
+    fn test_roll() {
+        assert_eq!("PRIVMSG #test :Holo rolls 3d6 [1, 3, 3] + 2 = 9\r\n\
+                    PRIVMSG #test :Holo rolls initiative: 2d20kh1 [(16), 19] + 5 = 24\r\n\
+                    PRIVMSG #test :strength: 4d6kh3 [6, (1), 6, 5] = 17\r\n\
+                    PRIVMSG #test :1d10! [9] = 9\r\n\
+                    PRIVMSG #test :Try something like 3d6+2, 4d6kh3 or adv d20+5\r\n\
+                    PRIVMSG #test :I don't understand 2d6+\r\n",
+                   &*replies(make_server(":Holo!holo@example.com PRIVMSG #test :!roll 3d6+2\r\n\
+                                          :Holo!holo@example.com PRIVMSG #test :!roll adv d20 + 5 initiative\r\n\
+                                          PRIVMSG #test :!roll 4d6kh3 strength\r\n\
+                                          PRIVMSG #test :!roll d10!\r\n\
+                                          PRIVMSG #test :!roll fireball\r\n\
+                                          PRIVMSG #test :!roll 2d6+\r\n")));
+    }
+
+    #[test]
+    fn test_too_many_dice() {
+        let reply = replies(make_server("PRIVMSG #test :!roll 100d1000 + 100d1000\r\n"));
+        assert!(reply.starts_with("PRIVMSG #test :100d1000 + 100d1000 = "));
+
+        let name  = repeat("x").take(50).collect::<String>();
+        let reply = replies(make_server(&format!(":{}!holo@example.com PRIVMSG #test :!roll {} {}\r\n",
+                                                 repeat("h").take(30).collect::<String>(), vec!["100d1000"; 10].join(" + "), name)));
+        assert!(reply.contains(&name));
+        assert!(reply.len() <= MAX_REPLY + "PRIVMSG #test :\r\n".len());
+    }
+}
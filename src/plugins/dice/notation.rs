@@ -0,0 +1,357 @@
+use std::cmp;
+use regex::Regex;
+use super::rng::Rng;
+
+lazy_static! {
+    // 3d6, d%, 4d6kh3, d10!, 8d6dl2! or just 2
+    static ref TERM: Regex = Regex::new(r"(?i)^\s*([+-])?\s*(?:(\d*)d(\d+|%)(!)?(?:(kh|kl|k|dh|dl|d)(\d+))?(!)?|(\d+))").unwrap();
+}
+
+const MAX_DICE:     usize = 100;
+const MAX_SIDES:    u64   = 1000;
+const MAX_TERMS:    usize = 10;
+const MAX_CONSTANT: i64   = 1_000_000;
+
+// together with the other limits, keeps even the shortest reply within a line
+const MAX_NAME: usize = 50;
+
+// Exploding dice stop adding more once this many have been rolled in total.
+const MAX_EXPLOSIONS: usize = 100;
+
+#[derive(PartialEq, Debug, Clone, Copy)]
+enum Keep {
+    All,
+    Highest(usize),
+    Lowest(usize)
+}
+
+#[derive(PartialEq, Debug, Clone, Copy)]
+enum Mode {
+    Normal,
+    Advantage,
+    Disadvantage
+}
+
+#[derive(PartialEq, Debug, Clone)]
+struct Dice {
+    count:   usize,
+    sides:   u64,
+    keep:    Keep,
+    explode: bool
+}
+
+#[derive(PartialEq, Debug, Clone)]
+enum Term {
+    Dice(Dice),
+    Constant(i64)
+}
+
+#[derive(PartialEq, Debug)]
+pub struct Roll {
+    // each term with its sign
+    terms:    Vec<(bool, Term)>,
+    pub name: Option<String>
+}
+
+#[derive(PartialEq, Debug)]
+struct Die {
+    value:    u64,
+    kept:     bool,
+    exploded: bool
+}
+
+fn mode(word: &str) -> Option<Mode> {
+    match &*word.to_lowercase() {
+        "adv" | "advantage"    => Some(Mode::Advantage),
+        "dis" | "disadvantage" => Some(Mode::Disadvantage),
+        _                      => None
+    }
+}
+
+// Splits "adv rest" and "rest" alike.
+fn split_mode(input: &str) -> (Mode, &str) {
+    let input = input.trim();
+    let (first, rest) = match input.find(char::is_whitespace) {
+        Some(space) => (&input[..space], &input[space..]),
+        None        => (input, "")
+    };
+
+    match mode(first) {
+        Some(mode) => (mode, rest.trim()),
+        None       => (Mode::Normal, input)
+    }
+}
+
+fn dice(count: Option<&str>, sides: &str, keep: Option<(&str, &str)>, explode: bool) -> Result<Dice, String> {
+    let count = match count {
+        Some("") | None => 1,
+        Some(count)     => try!(count.parse().map_err(|_| "Too many dice".to_owned()))
+    };
+
+    let sides = if sides == "%" { 100 } else { try!(sides.parse().map_err(|_| "Too many sides".to_owned())) };
+    if count == 0 || count > MAX_DICE {
+        return Err(format!("You can roll between 1 and {} dice", MAX_DICE));
+    }
+
+    if sides == 0 || sides > MAX_SIDES {
+        return Err(format!("Dice can have between 1 and {} sides", MAX_SIDES));
+    }
+
+    if explode && sides == 1 {
+        return Err("A d1 would explode forever".to_owned());
+    }
+
+    let keep = match keep {
+        Some((modifier, amount)) => {
+            let amount: usize = cmp::min(try!(amount.parse().map_err(|_| "Too many dice".to_owned())), count);
+            match &*modifier.to_lowercase() {
+                "kh" | "k" => Keep::Highest(amount),
+                "kl"       => Keep::Lowest(amount),
+                "dh"       => Keep::Lowest(count - amount),
+                _          => Keep::Highest(count - amount)
+            }
+        },
+        None => Keep::All
+    };
+
+    Ok(Dice {
+        count:   count,
+        sides:   sides,
+        keep:    keep,
+        explode: explode
+    })
+}
+
+// "adv d20+5 initiative": the terms, then the name of the roll, with
+// advantage or disadvantage either first or right after the terms.
+pub fn parse(input: &str) -> Result<Roll, String> {
+    let (mut mode, mut rest) = split_mode(input);
+    let mut terms            = vec![];
+
+    while let Some(captures) = TERM.captures(rest) {
+        let sign = captures.at(1);
+        if !terms.is_empty() && sign.is_none() {
+            break;
+        }
+
+        let term = if let Some(constant) = captures.at(8) {
+            let constant = try!(constant.parse().ok().and_then(|constant| if constant > MAX_CONSTANT { None } else { Some(constant) })
+                .ok_or("That number is too large".to_owned()));
+            Term::Constant(constant)
+        }
+        else {
+            let keep = captures.at(5).and_then(|modifier| captures.at(6).map(|amount| (modifier, amount)));
+            Term::Dice(try!(dice(captures.at(2), captures.at(3).unwrap(), keep, captures.at(4).or(captures.at(7)).is_some())))
+        };
+
+        terms.push((sign != Some("-"), term));
+        if terms.len() > MAX_TERMS {
+            return Err(format!("You can add at most {} terms", MAX_TERMS));
+        }
+
+        rest = &rest[captures.pos(0).unwrap().1..];
+        if !rest.is_empty() && !rest.starts_with(char::is_whitespace) && !rest.starts_with(|c| c == '+' || c == '-') {
+            return Err(format!("I don't understand {}", input.trim()));
+        }
+    }
+
+    if terms.is_empty() {
+        return Err("Try something like 3d6+2, 4d6kh3 or adv d20+5".to_owned());
+    }
+
+    if mode == Mode::Normal {
+        let (after, name) = split_mode(rest);
+        mode = after;
+        rest = name;
+    }
+
+    if mode != Mode::Normal {
+        let mut applied = false;
+        for &mut (_, ref mut term) in terms.iter_mut() {
+            if let Term::Dice(ref mut dice) = *term {
+                if dice.count == 1 && dice.keep == Keep::All {
+                    dice.count = 2;
+                    dice.keep  = if mode == Mode::Advantage { Keep::Highest(1) } else { Keep::Lowest(1) };
+                    applied    = true;
+                    break;
+                }
+            }
+        }
+
+        if !applied {
+            return Err("Advantage needs a single die, like d20".to_owned());
+        }
+    }
+
+    // "2d6+" is a roll missing its last term, not a roll called "+"
+    let name = rest.trim();
+    if name.starts_with(|c| c == '+' || c == '-' || c == '*' || c == '/') {
+        return Err(format!("I don't understand {}", input.trim()));
+    }
+
+    if name.chars().count() > MAX_NAME {
+        return Err(format!("Names can be at most {} characters long", MAX_NAME));
+    }
+
+    Ok(Roll {
+        terms: terms,
+        name:  if name.is_empty() { None } else { Some(name.to_owned()) }
+    })
+}
+
+impl Dice {
+    fn notation(&self) -> String {
+        let keep = match self.keep {
+            Keep::All            => String::new(),
+            Keep::Highest(count) => format!("kh{}", count),
+            Keep::Lowest(count)  => format!("kl{}", count)
+        };
+
+        format!("{}d{}{}{}", self.count, self.sides, if self.explode { "!" } else { "" }, keep)
+    }
+
+    fn roll(&self, rng: &mut Rng, rolled: &mut usize) -> Vec<Die> {
+        let mut dice = vec![];
+        for _ in 0..self.count {
+            loop {
+                let value    = rng.die(self.sides);
+                let exploded = self.explode && value == self.sides && *rolled < MAX_EXPLOSIONS;
+                *rolled += 1;
+
+                dice.push(Die { value: value, kept: true, exploded: exploded });
+                if !exploded {
+                    break;
+                }
+            }
+        }
+
+        // exploded dice count as more dice for keeping the highest or lowest
+        let mut order: Vec<usize> = (0..dice.len()).collect();
+        order.sort_by_key(|&i| dice[i].value);
+        let dropped = match self.keep {
+            Keep::All            => vec![],
+            Keep::Highest(count) => order[..dice.len().saturating_sub(count)].to_vec(),
+            Keep::Lowest(count)  => order[cmp::min(count, dice.len())..].to_vec()
+        };
+
+        for i in dropped {
+            dice[i].kept = false;
+        }
+
+        dice
+    }
+}
+
+impl Roll {
+    // The terms with every die rolled, the terms alone and the total, like
+    // "4d6kh3 [6, 5, 3, (1)] + 2", "4d6kh3 + 2" and 16.
+    pub fn roll(&self, rng: &mut Rng) -> (String, String, i64) {
+        let mut detailed = String::new();
+        let mut short    = String::new();
+        let mut total    = 0;
+        let mut rolled   = 0;
+
+        for (i, &(positive, ref term)) in self.terms.iter().enumerate() {
+            let sign = match (i, positive) {
+                (0, true)  => "",
+                (0, false) => "-",
+                (_, true)  => " + ",
+                (_, false) => " - "
+            };
+
+            let (notation, dice, value) = match *term {
+                Term::Constant(constant) => (constant.to_string(), String::new(), constant),
+                Term::Dice(ref dice) => {
+                    let rolls  = dice.roll(rng, &mut rolled);
+                    let value  = rolls.iter().filter(|die| die.kept).fold(0, |sum, die| sum + die.value as i64);
+                    let faces: Vec<String> = rolls.iter().map(|die| match (die.kept, die.exploded) {
+                        (true,  true)  => format!("{}!", die.value),
+                        (true,  false) => die.value.to_string(),
+                        (false, true)  => format!("({}!)", die.value),
+                        (false, false) => format!("({})", die.value)
+                    }).collect();
+
+                    (dice.notation(), format!(" [{}]", faces.join(", ")), value)
+                }
+            };
+
+            detailed.push_str(&format!("{}{}{}", sign, notation, dice));
+            short.push_str(&format!("{}{}", sign, notation));
+            total += if positive { value } else { -value };
+        }
+
+        (detailed, short, total)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::iter::repeat;
+    use super::super::rng::Rng;
+    use super::{parse, Roll, Term, Dice, Keep};
+
+    fn dice(count: usize, sides: u64, keep: Keep, explode: bool) -> Term {
+        Term::Dice(Dice { count: count, sides: sides, keep: keep, explode: explode })
+    }
+
+    #[test]
+    fn test_parse() {
+        assert_eq!(Ok(Roll { terms: vec![(true, dice(3, 6, Keep::All, false)), (true, Term::Constant(2))], name: None }),
+                   parse("3d6+2"));
+        assert_eq!(Ok(Roll { terms: vec![(true, dice(4, 6, Keep::Highest(3), false))], name: Some("strength".to_owned()) }),
+                   parse("4d6kh3 strength"));
+        assert_eq!(Ok(Roll { terms: vec![(true, dice(1, 10, Keep::All, true)), (false, dice(2, 100, Keep::Lowest(1), false))], name: None }),
+                   parse("d10! - 2d%dh1"));
+        assert_eq!(Ok(Roll { terms: vec![(true, dice(2, 20, Keep::Highest(1), false)), (true, Term::Constant(5))], name: Some("initiative".to_owned()) }),
+                   parse("adv d20 + 5 initiative"));
+        assert_eq!(Ok(Roll { terms: vec![(true, dice(2, 20, Keep::Lowest(1), false))], name: Some("stealth check".to_owned()) }),
+                   parse("1d20 dis stealth check"));
+        assert_eq!(Ok(Roll { terms: vec![(true, dice(4, 6, Keep::Highest(3), false))], name: None }), parse("4D6d1"));
+    }
+
+    #[test]
+    fn test_parse_errors() {
+        assert!(parse("fireball").is_err());
+        assert!(parse("3d6x").is_err());
+        assert!(parse("1000d6").is_err());
+        assert!(parse("0d6").is_err());
+        assert!(parse("d0").is_err());
+        assert!(parse("d100000").is_err());
+        assert!(parse("d1!").is_err());
+        assert!(parse("adv 3d6").is_err());
+        assert!(parse("99999999999999999999d6").is_err());
+        assert!(parse("1+1+1+1+1+1+1+1+1+1+1").is_err());
+        assert!(parse("2d6+").is_err());
+        assert!(parse("2d6 - ").is_err());
+        assert!(parse("d20 adv *2").is_err());
+        assert!(parse(&format!("d20 {}", repeat("x").take(51).collect::<String>())).is_err());
+        assert!(parse(&format!("d20 {}", repeat("x").take(50).collect::<String>())).is_ok());
+    }
+
+    #[test]
+    fn test_roll() {
+        let mut rng = Rng::seeded(42);
+        for _ in 0..100 {
+            let (_, _, total) = parse("4d6kh3 + 2").unwrap().roll(&mut rng);
+            assert!(total >= 5 && total <= 20);
+
+            let (detailed, _, _) = parse("4d6kh3").unwrap().roll(&mut rng);
+            assert_eq!(1, detailed.matches('(').count());
+
+            let (_, _, total) = parse("d6! - 1").unwrap().roll(&mut rng);
+            assert!(total >= 0);
+        }
+
+        let (detailed, short, _) = parse("8d1000!").unwrap().roll(&mut rng);
+        assert!(detailed.starts_with("8d1000! ["));
+        assert_eq!("8d1000!", short);
+    }
+
+    #[test]
+    fn test_explosions() {
+        let mut rng = Rng::seeded(1);
+        let (detailed, _, total) = parse("100d2!").unwrap().roll(&mut rng);
+        assert!(detailed.matches(',').count() + 1 <= 200);
+        assert!(total >= 100);
+    }
+}
@@ -0,0 +1,62 @@
+use time;
+
+// xorshift64*, which is plenty for dice and can be seeded to make the tests
+// repeatable.
+#[derive(Debug)]
+pub struct Rng {
+    state: u64
+}
+
+impl Rng {
+    pub fn new() -> Rng {
+        Rng::seeded(time::precise_time_ns())
+    }
+
+    pub fn seeded(seed: u64) -> Rng {
+        Rng {
+            // a zero state would only ever give zeros
+            state: if seed == 0 { 0x9e3779b97f4a7c15 } else { seed }
+        }
+    }
+
+    fn next(&mut self) -> u64 {
+        self.state ^= self.state >> 12;
+        self.state ^= self.state << 25;
+        self.state ^= self.state >> 27;
+        self.state.wrapping_mul(0x2545f4914f6cdd1d)
+    }
+
+    // Between 1 and sides, both included. The values past the last multiple
+    // of sides are thrown away, or the low faces would come up more often.
+    pub fn die(&mut self, sides: u64) -> u64 {
+        let zone = u64::max_value() - u64::max_value() % sides;
+        loop {
+            let value = self.next();
+            if value < zone {
+                return value % sides + 1;
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::Rng;
+
+    #[test]
+    fn test_die() {
+        let mut rng    = Rng::seeded(42);
+        let mut counts = [0; 6];
+        for _ in 0..6000 {
+            let value = rng.die(6);
+            assert!(value >= 1 && value <= 6);
+            counts[value as usize - 1] += 1;
+        }
+
+        assert!(counts.iter().all(|&count| count > 850 && count < 1150));
+
+        let (mut a, mut b) = (Rng::seeded(7), Rng::seeded(7));
+        assert!((0..100).all(|_| a.die(20) == b.die(20)));
+        assert_eq!(1, Rng::seeded(0).die(1));
+    }
+}
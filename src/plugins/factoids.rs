@@ -0,0 +1,451 @@
+use std::io;
+use std::collections::HashMap;
+use std::sync::Mutex;
+use irc::client::prelude::*;
+use regex::Regex;
+use redis::{Commands, RedisResult};
+use time;
+use plugin::{self, Plugin};
+use plugins::rules::Rules;
+use store::Store;
+
+lazy_static! {
+    static ref LEARN:  Regex        = Regex::new(r"^!learn\s+(?:(-g)\s+)?(.+?)\s+is\s+(.+?)\s*$").unwrap();
+    static ref MANAGE: Regex        = Regex::new(r"^!(forget|undo|history|lock|unlock)\s+(?:(-g)\s+)?(.+?)\s*$").unwrap();
+    static ref RECALL: Regex        = Regex::new(r"^(?:\?\?\s*(\S.*?)|!([^\s!]+))\s*$").unwrap();
+    static ref STORE:  Mutex<Store> = Mutex::new(Store::open("factoids").unwrap());
+}
+
+// Factoids learned in a channel are only known there, the ones learned with -g
+// or in a private message everywhere. Channels look at their own ones first.
+const GLOBAL: &'static str = "global";
+
+// aliases pointing to aliases are followed this many times at most
+const MAX_ALIASES: usize = 5;
+
+#[derive(PartialEq, Debug)]
+enum Request {
+    Learn(bool, String, String),
+    Forget(bool, String),
+    Undo(bool, String),
+    History(bool, String),
+    Lock(bool, String, bool),
+    // `?? key` says when the key is unknown, `!key` doesn't, and is left to the other plugins' commands
+    Recall(String, bool)
+}
+
+fn normalise(key: &str) -> String {
+    key.split_whitespace().collect::<Vec<&str>>().join(" ").to_lowercase()
+}
+
+fn parse(msg: &str) -> Option<Request> {
+    if let Some(captures) = LEARN.captures(msg) {
+        return Some(Request::Learn(captures.at(1).is_some(),
+                                   normalise(captures.at(2).unwrap()),
+                                   captures.at(3).unwrap().to_owned()));
+    }
+
+    if let Some(captures) = MANAGE.captures(msg) {
+        let global = captures.at(2).is_some();
+        let key    = normalise(captures.at(3).unwrap());
+
+        return match captures.at(1).unwrap() {
+            "forget"  => Some(Request::Forget(global, key)),
+            "undo"    => Some(Request::Undo(global, key)),
+            "history" => Some(Request::History(global, key)),
+            "lock"    => Some(Request::Lock(global, key, true)),
+            _         => Some(Request::Lock(global, key, false))
+        };
+    }
+
+    RECALL.captures(msg).and_then(|captures| match (captures.at(1), captures.at(2)) {
+        (Some(key), _) => Some(Request::Recall(normalise(key), true)),
+        (_, key)       => {
+            let key = normalise(key.unwrap_or(""));
+            if plugin::COMMANDS.contains(&&key[..]) { None } else { Some(Request::Recall(key, false)) }
+        }
+    })
+}
+
+// Where a factoid is looked for, in order.
+fn scopes(target: &str, global: bool) -> Vec<String> {
+    if !global && (target.starts_with('#') || target.starts_with('&')) {
+        vec![target.to_lowercase(), GLOBAL.to_owned()]
+    }
+    else {
+        vec![GLOBAL.to_owned()]
+    }
+}
+
+fn factoid_key(store: &Store, scope: &str, key: &str) -> String {
+    store.key(&format!("factoid:{}:{}", scope, key))
+}
+
+fn history_key(store: &Store, scope: &str, key: &str) -> String {
+    store.key(&format!("history:{}:{}", scope, key))
+}
+
+#[derive(PartialEq, Debug, Clone)]
+struct Factoid {
+    text:   String,
+    author: String,
+    time:   i64,
+    locked: bool
+}
+
+#[derive(PartialEq, Debug)]
+enum Reply {
+    Say(String),
+    Action(String),
+    Alias(String)
+}
+
+impl Factoid {
+    fn from_fields(fields: &HashMap<String, String>) -> Option<Factoid> {
+        fields.get("text").map(|text| Factoid {
+            text:   text.clone(),
+            author: fields.get("author").cloned().unwrap_or(String::new()),
+            time:   fields.get("time").and_then(|time| time.parse().ok()).unwrap_or(0),
+            locked: fields.get("locked").map(|locked| locked == "1").unwrap_or(false)
+        })
+    }
+
+    fn fields(&self) -> Vec<(&'static str, String)> {
+        vec![("text",   self.text.clone()),
+             ("author", self.author.clone()),
+             ("time",   self.time.to_string()),
+             ("locked", if self.locked { "1" } else { "0" }.to_owned())]
+    }
+
+    // Previous versions are kept as "<time>\t<author>\t<text>", and are never locked.
+    fn encode(&self) -> String {
+        format!("{}\t{}\t{}", self.time, self.author, self.text)
+    }
+
+    fn decode(version: &str) -> Option<Factoid> {
+        let fields: Vec<&str> = version.splitn(3, '\t').collect();
+        if fields.len() < 3 {
+            return None;
+        }
+
+        Some(Factoid {
+            text:   fields[2].to_owned(),
+            author: fields[1].to_owned(),
+            time:   fields[0].parse().unwrap_or(0),
+            locked: false
+        })
+    }
+
+    // "<reply>" drops the "key is" part, "<action>" makes it a /me and "<alias>"
+    // points to another factoid.
+    fn reply(&self, key: &str) -> Reply {
+        if self.text.starts_with("<reply>") {
+            Reply::Say(self.text[7..].trim().to_owned())
+        }
+        else if self.text.starts_with("<action>") {
+            Reply::Action(self.text[8..].trim().to_owned())
+        }
+        else if self.text.starts_with("<alias>") {
+            Reply::Alias(normalise(&self.text[7..]))
+        }
+        else {
+            Reply::Say(format!("{} is {}", key, self.text))
+        }
+    }
+}
+
+fn find(store: &Store, scopes: &[String], key: &str) -> Option<(String, Factoid)> {
+    scopes.iter()
+        .filter_map(|scope| {
+            let fields: HashMap<String, String> = store.hgetall(factoid_key(store, scope, key)).unwrap_or(HashMap::new());
+            Factoid::from_fields(&fields).map(|factoid| (scope.clone(), factoid))
+        })
+        .next()
+}
+
+// Forgotten factoids only have a history left, and can still be brought back.
+fn find_history(store: &Store, scopes: &[String], key: &str) -> Option<String> {
+    scopes.iter()
+        .find(|scope| store.llen::<_, usize>(history_key(store, scope, key)).unwrap_or(0) > 0)
+        .cloned()
+}
+
+// The rules are loaded again here, so that "!key" doesn't shadow their triggers.
+register_plugin!(Factoids, rules: Rules);
+
+impl Factoids {
+    fn locked(&self, server: &IrcServer, target: &str, key: &str) -> io::Result<()> {
+        server.send_privmsg(target, &*format!("{} is locked, only admins can change it", key))
+    }
+
+    fn learn(&self, server: &IrcServer, message: &Message, target: &str, global: bool, key: &str, text: &str) -> io::Result<()> {
+        let nickname = match message.source_nickname() {
+            Some(nickname) => nickname,
+            None           => { return Ok(()); }
+        };
+
+        let scopes   = scopes(target, global);
+        let store    = STORE.lock().unwrap();
+        let existing = find(&store, &scopes, key);
+
+        if existing.as_ref().map(|&(_, ref factoid)| factoid.locked).unwrap_or(false) && !plugin::is_admin(server, message) {
+            return self.locked(server, target, key);
+        }
+
+        // editing a factoid keeps it where it is
+        let scope   = existing.as_ref().map(|&(ref scope, _)| scope.clone()).unwrap_or(scopes[0].clone());
+        let factoid = Factoid {
+            text:   text.to_owned(),
+            author: nickname.to_owned(),
+            time:   time::get_time().sec,
+            locked: existing.as_ref().map(|&(_, ref factoid)| factoid.locked).unwrap_or(false)
+        };
+
+        let saved: RedisResult<()> = match existing {
+            Some((_, ref previous)) => store.rpush(history_key(&store, &scope, key), previous.encode()),
+            None                    => Ok(())
+        }.and_then(|_: ()| store.hset_multiple(factoid_key(&store, &scope, key), &factoid.fields()));
+
+        match (saved, existing) {
+            (Ok(()), Some(_)) => server.send_privmsg(target, &*format!("Okay, {} has been updated", key)),
+            (Ok(()), None)    => server.send_privmsg(target, &*format!("Okay, I'll remember {}", key)),
+            (Err(e), _)       => server.send_privmsg(target, &*format!("Something bad happened: {:?}", e))
+        }
+    }
+
+    fn forget(&self, server: &IrcServer, message: &Message, target: &str, global: bool, key: &str) -> io::Result<()> {
+        let store = STORE.lock().unwrap();
+
+        let (scope, factoid) = match find(&store, &scopes(target, global), key) {
+            Some(found) => found,
+            None        => { return server.send_privmsg(target, &*format!("I don't know anything about {}", key)); }
+        };
+
+        if factoid.locked && !plugin::is_admin(server, message) {
+            return self.locked(server, target, key);
+        }
+
+        let forgotten: RedisResult<()> = store.rpush(history_key(&store, &scope, key), factoid.encode())
+            .and_then(|_: ()| store.del(factoid_key(&store, &scope, key)));
+
+        match forgotten {
+            Ok(()) => server.send_privmsg(target, &*format!("I forgot {}, !undo {} brings it back", key, key)),
+            Err(e) => server.send_privmsg(target, &*format!("Something bad happened: {:?}", e))
+        }
+    }
+
+    fn undo(&self, server: &IrcServer, message: &Message, target: &str, global: bool, key: &str) -> io::Result<()> {
+        let scopes  = scopes(target, global);
+        let store   = STORE.lock().unwrap();
+        let current = find(&store, &scopes, key);
+
+        if current.as_ref().map(|&(_, ref factoid)| factoid.locked).unwrap_or(false) && !plugin::is_admin(server, message) {
+            return self.locked(server, target, key);
+        }
+
+        let scope = match current {
+            Some((ref scope, _)) => Some(scope.clone()),
+            None                 => find_history(&store, &scopes, key)
+        };
+
+        let previous: Option<String> = match scope {
+            Some(ref scope) => store.rpop(history_key(&store, scope, key)).unwrap_or(None),
+            None            => None
+        };
+
+        let mut factoid = match previous.as_ref().and_then(|previous| Factoid::decode(previous)) {
+            Some(factoid) => factoid,
+            None          => { return server.send_privmsg(target, &*format!("There is nothing to undo for {}", key)); }
+        };
+
+        factoid.locked = current.map(|(_, current)| current.locked).unwrap_or(false);
+
+        let restored: RedisResult<()> = store.hset_multiple(factoid_key(&store, &scope.unwrap(), key), &factoid.fields());
+        match restored {
+            Ok(()) => server.send_privmsg(target, &*format!("Okay, {} is back to what {} said", key, factoid.author)),
+            Err(e) => server.send_privmsg(target, &*format!("Something bad happened: {:?}", e))
+        }
+    }
+
+    fn history(&self, server: &IrcServer, target: &str, global: bool, key: &str) -> io::Result<()> {
+        let scopes  = scopes(target, global);
+        let store   = STORE.lock().unwrap();
+        let current = find(&store, &scopes, key);
+        let now     = time::get_time().sec;
+
+        let scope = match current {
+            Some((ref scope, _)) => Some(scope.clone()),
+            None                 => find_history(&store, &scopes, key)
+        };
+
+        let versions: Vec<String> = match scope {
+            Some(ref scope) => store.lrange(history_key(&store, scope, key), 0, -1).unwrap_or(vec![]),
+            None            => { return server.send_privmsg(target, &*format!("I don't know anything about {}", key)); }
+        };
+
+        let mut changes = vec![match current {
+            Some((_, ref factoid)) => format!("set by {} {}", factoid.author, plugin::time_ago(now - factoid.time)),
+            None                   => "forgotten".to_owned()
+        }];
+
+        changes.extend(versions.iter().rev().take(5).filter_map(|version| Factoid::decode(version)).map(|factoid| {
+            format!("before that by {} {}", factoid.author, plugin::time_ago(now - factoid.time))
+        }));
+
+        server.send_privmsg(target, &*format!("{}: {}", key, changes.join(", ")))
+    }
+
+    fn lock(&self, server: &IrcServer, message: &Message, target: &str, global: bool, key: &str, locked: bool) -> io::Result<()> {
+        if !plugin::is_admin(server, message) {
+            return server.send_privmsg(target, "Only admins can lock and unlock factoids");
+        }
+
+        let store = STORE.lock().unwrap();
+
+        let scope = match find(&store, &scopes(target, global), key) {
+            Some((scope, _)) => scope,
+            None             => { return server.send_privmsg(target, &*format!("I don't know anything about {}", key)); }
+        };
+
+        let saved: RedisResult<()> = store.hset(factoid_key(&store, &scope, key), "locked", if locked { "1" } else { "0" });
+        match saved {
+            Ok(()) => server.send_privmsg(target, &*format!("{} is now {}", key, if locked { "locked" } else { "unlocked" })),
+            Err(e) => server.send_privmsg(target, &*format!("Something bad happened: {:?}", e))
+        }
+    }
+
+    // "!key" is left to a rule that answers the same message.
+    fn request(&self, server: &IrcServer, message: &Message, msg: &str) -> Option<Request> {
+        match parse(msg) {
+            Some(Request::Recall(_, false)) if self.rules.is_allowed(server, message) => None,
+            request                                                               => request
+        }
+    }
+
+    fn recall(&self, server: &IrcServer, target: &str, key: &str, explicit: bool) -> io::Result<()> {
+        let scopes = scopes(target, false);
+        let store  = STORE.lock().unwrap();
+
+        let mut key = key.to_owned();
+        for _ in 0..MAX_ALIASES {
+            let factoid = match find(&store, &scopes, &key) {
+                Some((_, factoid)) => factoid,
+                None if explicit   => { return server.send_privmsg(target, &*format!("I don't know anything about {}", key)); },
+                None               => { return Ok(()); }
+            };
+
+            match factoid.reply(&key) {
+                Reply::Say(text)    => { return server.send_privmsg(target, &text); },
+                Reply::Action(text) => { return server.send_action(target, &text); },
+                Reply::Alias(alias) => { key = alias; }
+            }
+        }
+
+        server.send_privmsg(target, &*format!("Too many aliases to follow for {}", key))
+    }
+}
+
+impl Plugin for Factoids {
+    fn configure(&mut self, server: &IrcServer) {
+        self.rules.configure(server);
+    }
+
+    fn is_allowed(&self, server: &IrcServer, message: &Message) -> bool {
+        match message.command {
+            Command::PRIVMSG(_, ref msg) => self.request(server, message, msg).is_some(),
+            _ => false
+        }
+    }
+
+    fn execute(&mut self, server: &IrcServer, message: &Message) -> io::Result<()> {
+        match message.command {
+            Command::PRIVMSG(ref target, ref msg) => match self.request(server, message, msg) {
+                Some(Request::Learn(global, key, text))   => self.learn(server, message, target, global, &key, &text),
+                Some(Request::Forget(global, key))        => self.forget(server, message, target, global, &key),
+                Some(Request::Undo(global, key))          => self.undo(server, message, target, global, &key),
+                Some(Request::History(global, key))       => self.history(server, target, global, &key),
+                Some(Request::Lock(global, key, locked))  => self.lock(server, message, target, global, &key, locked),
+                Some(Request::Recall(key, explicit))      => self.recall(server, target, &key, explicit),
+                None                                      => Ok(())
+            },
+            _ => Ok(())
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use ::tests::{make_server, make_server_with_options, get_server_value};
+
+    use irc::client::prelude::*;
+
+    use plugin::Plugin;
+    use super::{Factoids, Factoid, Request, Reply, parse, scopes};
+
+    #[test]
+    fn test_parse() {
+        assert_eq!(Some(Request::Learn(false, "rust".to_owned(), "a language is it not".to_owned())),
+                   parse("!learn Rust is a language is it not"));
+        assert_eq!(Some(Request::Learn(true, "the answer".to_owned(), "<reply>42".to_owned())),
+                   parse("!learn -g The  Answer is <reply>42"));
+        assert_eq!(Some(Request::Forget(false, "rust".to_owned())), parse("!forget rust"));
+        assert_eq!(Some(Request::Undo(true, "rust".to_owned())),    parse("!undo -g rust"));
+        assert_eq!(Some(Request::Lock(false, "rust".to_owned(), false)), parse("!unlock rust"));
+        assert_eq!(Some(Request::Recall("the answer".to_owned(), true)), parse("?? the answer"));
+        assert_eq!(Some(Request::Recall("rust".to_owned(), false)),      parse("!Rust"));
+        assert_eq!(Some(Request::Recall("np".to_owned(), true)),         parse("?? np"));
+        assert_eq!(None, parse("!np"));
+        assert_eq!(None, parse("!Reminders"));
+        assert_eq!(None, parse("!forget"));
+        assert_eq!(None, parse("!rust is great"));
+        assert_eq!(None, parse("!learn rust"));
+    }
+
+    #[test]
+    fn test_reply() {
+        let factoid = |text: &str| Factoid { text: text.to_owned(), author: "holo".to_owned(), time: 0, locked: false };
+
+        assert_eq!(Reply::Say("rust is a language".to_owned()), factoid("a language").reply("rust"));
+        assert_eq!(Reply::Say("42".to_owned()),                 factoid("<reply> 42").reply("answer"));
+        assert_eq!(Reply::Action("waves".to_owned()),           factoid("<action>waves").reply("hi"));
+        assert_eq!(Reply::Alias("the answer".to_owned()),       factoid("<alias>The Answer").reply("42"));
+
+        let version = factoid("a\tlanguage").encode();
+        assert_eq!(Some(factoid("a\tlanguage")), Factoid::decode(&version));
+        assert_eq!(None, Factoid::decode("broken"));
+    }
+
+    #[test]
+    fn test_scopes() {
+        assert_eq!(vec!["#test".to_owned(), "global".to_owned()], scopes("#Test", false));
+        assert_eq!(vec!["global".to_owned()],                     scopes("#test", true));
+        assert_eq!(vec!["global".to_owned()],                     scopes("Gauss", false));
+    }
+
+    #[test]
+    fn test_lock_needs_admin() {
+        let     server = make_server("PRIVMSG #test :!lock rust\r\n");
+        let mut plugin = Factoids::new();
+
+        for message in server.iter() {
+            let message = message.unwrap();
+            assert!(plugin.is_allowed(&server, &message));
+            assert!(plugin.execute(&server, &message).is_ok());
+        }
+
+        assert_eq!("PRIVMSG #test :Only admins can lock and unlock factoids\r\n", &*get_server_value(&server));
+    }
+
+    #[test]
+    fn test_rule_triggers() {
+        let     server = make_server_with_options("PRIVMSG #test :!weather\r\nPRIVMSG #test :!rust\r\n",
+                                                  &[("rules.storage",          "false"),
+                                                    ("rules.weather.trigger",  "!weather"),
+                                                    ("rules.weather.response", "{botnick} has no idea about the weather")]);
+        let mut plugin = Factoids::new();
+
+        plugin.configure(&server);
+        let allowed: Vec<bool> = server.iter().map(|message| plugin.is_allowed(&server, &message.unwrap())).collect();
+        assert_eq!(vec![false, true], allowed);
+    }
+}
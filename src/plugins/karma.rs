@@ -0,0 +1,231 @@
+use std::io;
+use std::collections::HashMap;
+use std::sync::Mutex;
+use irc::client::prelude::*;
+use regex::Regex;
+use redis::{Commands, RedisResult};
+use time;
+use plugin::{self, Plugin};
+use store::Store;
+
+lazy_static! {
+    static ref KARMA:   Regex        = Regex::new(r"^!karma\s+(.+?)\s*$").unwrap();
+    static ref RANKING: Regex        = Regex::new(r"^!karma(top|bottom)\s*$").unwrap();
+    static ref GROUPED: Regex        = Regex::new(r"\(([^()]+)\)(\+\+|--)").unwrap();
+    static ref STORE:   Mutex<Store> = Mutex::new(Store::open("karma").unwrap());
+}
+
+// item -> karma, and item -> how it was last written, for the rankings
+const SCORES: &'static str = "scores";
+const NAMES:  &'static str = "names";
+
+// Items are compared like nicknames, so that Holo++ and holo++ go to the same place.
+fn item_key(item: &str) -> String {
+    plugin::nick_key(&item.split_whitespace().collect::<Vec<&str>>().join(" "))
+}
+
+// `thing++`, `thing--` and `(multi word thing)++`, each with its item and its delta.
+// Items need a letter or a digit, so that arrows like <-- and --> aren't votes.
+fn votes(msg: &str) -> Vec<(String, i64)> {
+    let delta = |sign: &str| if sign == "++" { 1 } else { -1 };
+    let named = |item: &str| item.chars().any(|c| c.is_alphanumeric());
+
+    let mut votes: Vec<(String, i64)> = GROUPED.captures_iter(msg)
+        .map(|captures| (captures.at(1).unwrap().trim().to_owned(), delta(captures.at(2).unwrap())))
+        .filter(|&(ref item, _)| named(item))
+        .collect();
+
+    for word in GROUPED.replace_all(msg, " ").split_whitespace() {
+        let word = word.trim_right_matches(|c: char| ",.;:!?".contains(c));
+        if word.len() > 2 && (word.ends_with("++") || word.ends_with("--")) {
+            let item = &word[..word.len() - 2];
+            if named(item) {
+                votes.push((item.to_owned(), delta(&word[word.len() - 2..])));
+            }
+        }
+    }
+
+    votes
+}
+
+// When each voter last voted for each item.
+#[derive(Debug)]
+pub struct Cooldowns {
+    voted_at: HashMap<(String, String), i64>
+}
+
+impl Cooldowns {
+    pub fn new() -> Cooldowns {
+        Cooldowns { voted_at: HashMap::new() }
+    }
+
+    fn ready(&mut self, voter: &str, item: &str, now: i64, cooldown: i64) -> bool {
+        let key = (plugin::nick_key(voter), item_key(item));
+        match self.voted_at.get(&key) {
+            Some(&voted_at) if now - voted_at < cooldown => { return false; },
+            _                                            => {}
+        }
+
+        self.voted_at.insert(key, now);
+        true
+    }
+}
+
+register_plugin!(Karma, cooldowns: Cooldowns);
+
+impl Karma {
+    fn vote(&mut self, server: &IrcServer, message: &Message, target: &str, msg: &str) -> io::Result<()> {
+        let voter = match message.source_nickname() {
+            Some(voter) => voter,
+            None        => { return Ok(()); }
+        };
+
+        let cooldown = plugin::get_option(server, "karma.cooldown")
+            .and_then(|cooldown| cooldown.parse().ok())
+            .unwrap_or(60);
+        let now = time::get_time().sec;
+
+        let (own, others): (Vec<(String, i64)>, Vec<(String, i64)>) = votes(msg).into_iter()
+            .partition(|&(ref item, _)| item_key(item) == plugin::nick_key(voter));
+
+        if !own.is_empty() {
+            try!(server.send_privmsg(target, &*format!("You can't change your own karma, {}", voter)));
+        }
+
+        let votes: Vec<(String, i64)> = others.into_iter()
+            .filter(|&(ref item, _)| self.cooldowns.ready(voter, item, now, cooldown))
+            .collect();
+        if votes.is_empty() {
+            return Ok(());
+        }
+
+        let store = STORE.lock().unwrap();
+        for (item, delta) in votes {
+            let saved: RedisResult<()> = store.zincr(store.key(SCORES), item_key(&item), delta)
+                .and_then(|_: f64| store.hset(store.key(NAMES), item_key(&item), &*item));
+
+            if let Err(e) = saved {
+                return server.send_privmsg(target, &*format!("Something bad happened: {:?}", e));
+            }
+        }
+
+        Ok(())
+    }
+
+    fn karma(&self, server: &IrcServer, target: &str, item: &str) -> io::Result<()> {
+        let store = STORE.lock().unwrap();
+
+        let score: Option<i64> = store.zscore(store.key(SCORES), item_key(item)).unwrap_or(None);
+        match score {
+            Some(score) => server.send_privmsg(target, &*format!("{} has a karma of {}", item, score)),
+            None        => server.send_privmsg(target, &*format!("{} has no karma yet", item))
+        }
+    }
+
+    fn ranking(&self, server: &IrcServer, target: &str, top: bool) -> io::Result<()> {
+        let size = plugin::get_option(server, "karma.ranking")
+            .and_then(|size| size.parse::<isize>().ok())
+            .unwrap_or(5);
+
+        let store = STORE.lock().unwrap();
+
+        let scores: Vec<(String, i64)> = if top {
+            store.zrevrange_withscores(store.key(SCORES), 0, size - 1)
+        }
+        else {
+            store.zrange_withscores(store.key(SCORES), 0, size - 1)
+        }.unwrap_or(vec![]);
+
+        if scores.is_empty() {
+            return server.send_privmsg(target, "Nobody has any karma yet");
+        }
+
+        let ranking: Vec<String> = scores.iter().map(|&(ref key, score)| {
+            let name: Option<String> = store.hget(store.key(NAMES), &**key).unwrap_or(None);
+            format!("{} ({})", name.unwrap_or(key.clone()), score)
+        }).collect();
+
+        server.send_privmsg(target, &*format!("{} karma: {}", if top { "Top" } else { "Bottom" }, ranking.join(", ")))
+    }
+}
+
+impl Plugin for Karma {
+    fn is_allowed(&self, _: &IrcServer, message: &Message) -> bool {
+        match message.command {
+            Command::PRIVMSG(_, ref msg) => KARMA.is_match(msg) || RANKING.is_match(msg) || !votes(msg).is_empty(),
+            _ => false
+        }
+    }
+
+    fn execute(&mut self, server: &IrcServer, message: &Message) -> io::Result<()> {
+        match message.command {
+            Command::PRIVMSG(ref target, ref msg) => {
+                if let Some(captures) = KARMA.captures(msg) {
+                    self.karma(server, target, captures.at(1).unwrap())
+                }
+                else if let Some(captures) = RANKING.captures(msg) {
+                    self.ranking(server, target, captures.at(1) == Some("top"))
+                }
+                else {
+                    self.vote(server, message, target, msg)
+                }
+            },
+            _ => Ok(())
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use ::tests::{make_server, get_server_value};
+
+    use irc::client::prelude::*;
+
+    use plugin::Plugin;
+    use super::{Karma, Cooldowns, votes};
+
+    #[test]
+    fn test_votes() {
+        assert_eq!(vec![("multi word thing".to_owned(), 1), ("holo".to_owned(), 1), ("gauss".to_owned(), -1)],
+                   votes("(multi word thing)++ holo++ and gauss--, really"));
+        assert_eq!(vec![("c".to_owned(), 1)], votes("I like c++"));
+        assert!(votes("i++ -- ++ +++ x + y").iter().all(|&(ref item, _)| item == "i"));
+        assert!(votes("no karma here").is_empty());
+        assert!(votes("go back <-- there, (:-)++ and ---> that way").is_empty());
+    }
+
+    #[test]
+    fn test_cooldown() {
+        let mut cooldowns = Cooldowns::new();
+
+        assert!(cooldowns.ready("holo", "rust", 0, 60));
+        assert!(!cooldowns.ready("Holo", "Rust", 30, 60));
+        assert!(cooldowns.ready("gauss", "rust", 30, 60));
+        assert!(cooldowns.ready("holo", "rust", 60, 60));
+    }
+
+    #[test]
+    fn test_self_vote() {
+        let     server = make_server(":Holo[]!holo@example.com PRIVMSG #test :holo{}++ (HOLO[])--\r\n");
+        let mut plugin = Karma::new();
+
+        for message in server.iter() {
+            let message = message.unwrap();
+            assert!(plugin.is_allowed(&server, &message));
+            assert!(plugin.execute(&server, &message).is_ok());
+        }
+
+        assert_eq!("PRIVMSG #test :You can't change your own karma, Holo[]\r\n", &*get_server_value(&server));
+    }
+
+    #[test]
+    fn test_not_allowed() {
+        let server = make_server("PRIVMSG #test :1 + 1 -- 2\r\nPRIVMSG #test :<-- this one\r\n");
+        let plugin = Karma::new();
+
+        for message in server.iter() {
+            let message = message.unwrap();
+            assert!(!plugin.is_allowed(&server, &message));
+        }
+    }
+}
@@ -0,0 +1,280 @@
+// The Last.fm methods the plugin needs, requested against a configurable root (handy for tests).
+
+use std::io::Read;
+use hyper::Url;
+use hyper::client::Client;
+use hyper::header::Connection;
+use hyper::status::StatusCode;
+use serde_json::{self, Value};
+
+pub const API_ROOT: &'static str = "http://ws.audioscrobbler.com/2.0/";
+
+// https://www.last.fm/api/errorcodes
+pub const INVALID_PARAMETERS:  i64 = 6;
+pub const INVALID_API_KEY:     i64 = 10;
+pub const SUSPENDED_API_KEY:   i64 = 26;
+pub const RATE_LIMIT_EXCEEDED: i64 = 29;
+
+#[derive(Debug)]
+pub enum Error {
+    HTTPError(String),
+    ParsingError,
+    LastFMError(i64, String)
+}
+
+impl Error {
+    pub fn code(&self) -> Option<i64> {
+        match *self {
+            Error::LastFMError(code, _) => Some(code),
+            _                           => None
+        }
+    }
+}
+
+#[derive(PartialEq, Debug, Clone)]
+pub struct Artist {
+    pub name:      String,
+    pub playcount: u64
+}
+
+#[derive(PartialEq, Debug, Clone)]
+pub struct Track {
+    pub name:      String,
+    pub artist:    String,
+    pub playcount: u64
+}
+
+// `played_at` is None for the track being scrobbled right now.
+#[derive(PartialEq, Debug, Clone)]
+pub struct RecentTrack {
+    pub name:      String,
+    pub artist:    String,
+    pub album:     String,
+    pub played_at: Option<i64>
+}
+
+#[derive(Debug)]
+pub struct Api {
+    root:    String,
+    api_key: String
+}
+
+// Last.fm sends numbers as strings, and a lone item instead of a list of one.
+fn items<'a>(document: &'a Value, path: &str) -> Vec<&'a Value> {
+    match document.lookup(path) {
+        Some(&Value::Array(ref items)) => items.iter().collect(),
+        Some(item @ &Value::Object(_)) => vec![item],
+        _                              => vec![]
+    }
+}
+
+fn string(item: &Value, path: &str) -> Option<String> {
+    item.lookup(path).and_then(|value| value.as_str()).map(|value| value.to_owned())
+}
+
+fn number(item: &Value, path: &str) -> u64 {
+    match item.lookup(path) {
+        Some(&Value::String(ref value)) => value.parse().unwrap_or(0),
+        Some(value)                     => value.as_u64().unwrap_or(0),
+        None                            => 0
+    }
+}
+
+impl Api {
+    pub fn new(root: &str, api_key: &str) -> Api {
+        Api {
+            root:    root.to_owned(),
+            api_key: api_key.to_owned()
+        }
+    }
+
+    fn request(&self, params: &[(&str, &str)]) -> Result<Value, Error> {
+        let mut url = try!(Url::parse(&self.root).map_err(|e| Error::HTTPError(e.to_string())));
+        url.query_pairs_mut()
+            .clear()
+            .append_pair("api_key", &self.api_key)
+            .append_pair("format", "json");
+
+        for &(key, value) in params {
+            url.query_pairs_mut().append_pair(key, value);
+        }
+
+        let mut response = try!(Client::new().get(url.as_str())
+            .header(Connection::close())
+            .send()
+            .map_err(|e| Error::HTTPError(e.to_string())));
+
+        if response.status == StatusCode::TooManyRequests {
+            return Err(Error::LastFMError(RATE_LIMIT_EXCEEDED, "Too many requests".to_owned()));
+        }
+
+        let mut body = String::new();
+        try!(response.read_to_string(&mut body).map_err(|e| Error::HTTPError(e.to_string())));
+
+        let document: Value = try!(serde_json::from_str(&body).map_err(|_| Error::ParsingError));
+        match document.find("error").and_then(|error| error.as_i64()) {
+            Some(error) => Err(Error::LastFMError(error, string(&document, "message").unwrap_or(String::new()))),
+            None        => Ok(document)
+        }
+    }
+
+    fn artists(&self, document: &Value, path: &str) -> Vec<Artist> {
+        items(document, path).into_iter().filter_map(|artist| string(artist, "name").map(|name| Artist {
+            name:      name,
+            playcount: number(artist, "playcount")
+        })).collect()
+    }
+
+    // Fails with LastFMError(10, _) or LastFMError(26, _) when the key is refused, without asking about any user.
+    pub fn check_key(&self) -> Result<(), Error> {
+        self.request(&[("method", "chart.gettopartists"),
+                       ("limit",  "1")]).map(|_| ())
+    }
+
+    // The user name as spelled on Last.fm, or LastFMError(6, _) when there is no such user.
+    pub fn user_name(&self, user: &str) -> Result<String, Error> {
+        let document = try!(self.request(&[("method", "user.getinfo"),
+                                           ("user",   user)]));
+        string(&document, "user.name").ok_or(Error::ParsingError)
+    }
+
+    pub fn recent_tracks(&self, user: &str, limit: u32) -> Result<Vec<RecentTrack>, Error> {
+        let document = try!(self.request(&[("method", "user.getrecenttracks"),
+                                           ("user",   user),
+                                           ("limit",  &limit.to_string())]));
+
+        Ok(items(&document, "recenttracks.track").into_iter().filter_map(|track| string(track, "name").map(|name| RecentTrack {
+            name:      name,
+            artist:    string(track, "artist.#text").unwrap_or(String::new()),
+            album:     string(track, "album.#text").unwrap_or(String::new()),
+            played_at: track.lookup("date.uts").map(|_| number(track, "date.uts") as i64)
+        })).collect())
+    }
+
+    pub fn top_artists(&self, user: &str, period: &str, limit: u32) -> Result<Vec<Artist>, Error> {
+        let document = try!(self.request(&[("method", "user.gettopartists"),
+                                           ("user",   user),
+                                           ("period", period),
+                                           ("limit",  &limit.to_string())]));
+        Ok(self.artists(&document, "topartists.artist"))
+    }
+
+    pub fn weekly_artists(&self, user: &str) -> Result<Vec<Artist>, Error> {
+        let document = try!(self.request(&[("method", "user.getweeklyartistchart"),
+                                           ("user",   user)]));
+        Ok(self.artists(&document, "weeklyartistchart.artist"))
+    }
+
+    pub fn top_tracks(&self, user: &str, period: &str, limit: u32) -> Result<Vec<Track>, Error> {
+        let document = try!(self.request(&[("method", "user.gettoptracks"),
+                                           ("user",   user),
+                                           ("period", period),
+                                           ("limit",  &limit.to_string())]));
+
+        Ok(items(&document, "toptracks.track").into_iter().filter_map(|track| string(track, "name").map(|name| Track {
+            name:      name,
+            artist:    string(track, "artist.name").unwrap_or(String::new()),
+            playcount: number(track, "playcount")
+        })).collect())
+    }
+}
+
+#[cfg(test)]
+pub mod tests {
+    use hyper::server::{Server, Request, Response, Listening};
+    use hyper::uri::RequestUri;
+    use super::{Api, Artist, Track, RecentTrack, Error};
+
+    // A fake Last.fm answering with canned charts, whatever the user.
+    pub fn mock_api() -> (Listening, Api) {
+        let listening = Server::http("127.0.0.1:0").unwrap().handle(|request: Request, response: Response| {
+            let path = match request.uri {
+                RequestUri::AbsolutePath(ref path) => path.to_owned(),
+                _                                  => String::new()
+            };
+
+            let body = if path.contains("api_key=invalid") {
+                r#"{"error": 10, "message": "Invalid API key - You must be granted a valid key by last.fm", "links": []}"#
+            }
+            else if path.contains("user=nobody") {
+                r#"{"error": 6, "message": "User not found", "links": []}"#
+            }
+            else if path.contains("method=chart.gettopartists") {
+                r#"{"artists": {"artist": {"name": "Queen", "playcount": "1000000"}}}"#
+            }
+            else if path.contains("method=user.getinfo") {
+                r#"{"user": {"name": "Gaussimandro", "playcount": "1024"}}"#
+            }
+            else if path.contains("method=user.gettopartists") && path.contains("user=holo") {
+                r#"{"topartists": {"artist": [{"name": "Rhapsody", "playcount": "80"}, {"name": "Nightwish", "playcount": "50"},
+                                              {"name": "Queen", "playcount": "10"}]}}"#
+            }
+            else if path.contains("method=user.gettopartists") {
+                r#"{"topartists": {"artist": [{"name": "Queen", "playcount": "120"}, {"name": "Muse", "playcount": "98"},
+                                              {"name": "Rhapsody", "playcount": "12"}, {"name": "Blind Guardian", "playcount": "7"}]}}"#
+            }
+            else if path.contains("method=user.getrecenttracks") && path.contains("user=holo") {
+                r##"{"recenttracks": {"track": [{"name": "Emerald Sword", "artist": {"#text": "Rhapsody"}, "album": {"#text": ""},
+                                                "date": {"uts": "1476471840", "#text": "14 Oct 2016, 19:04"}}]}}"##
+            }
+            else if path.contains("method=user.getrecenttracks") && path.contains("user=silent") {
+                r#"{"recenttracks": {"track": [], "@attr": {"user": "silent", "total": "0"}}}"#
+            }
+            else if path.contains("method=user.getrecenttracks") {
+                r##"{"recenttracks": {"track": [{"name": "Starlight", "artist": {"#text": "Muse"}, "album": {"#text": "Black Holes and Revelations"},
+                                                "@attr": {"nowplaying": "true"}}]}}"##
+            }
+            else if path.contains("method=user.gettoptracks") {
+                r#"{"toptracks": {"track": {"name": "Bohemian Rhapsody", "playcount": "42", "artist": {"name": "Queen"}}}}"#
+            }
+            else {
+                r#"{"weeklyartistchart": {"artist": [{"name": "Muse", "playcount": "30"}]}}"#
+            };
+
+            response.send(body.as_bytes()).unwrap();
+        }).unwrap();
+
+        let api = Api::new(&format!("http://{}/2.0/", listening.socket), "test");
+        (listening, api)
+    }
+
+    #[test]
+    fn test_charts() {
+        let (mut listening, api) = mock_api();
+
+        let artists = api.top_artists("gauss", "7day", 5).unwrap();
+        assert_eq!(4, artists.len());
+        assert_eq!(Artist { name: "Queen".to_owned(), playcount: 120 }, artists[0]);
+
+        let tracks = api.top_tracks("gauss", "overall", 5).unwrap();
+        assert_eq!(vec![Track { name: "Bohemian Rhapsody".to_owned(), artist: "Queen".to_owned(), playcount: 42 }], tracks);
+
+        assert_eq!(1, api.weekly_artists("gauss").unwrap().len());
+        assert_eq!("Gaussimandro", api.user_name("gaussimandro").unwrap());
+        assert!(api.check_key().is_ok());
+
+        match api.top_artists("nobody", "overall", 5) {
+            Err(Error::LastFMError(6, _)) => {},
+            other                         => panic!("unexpected {:?}", other)
+        }
+
+        let api = Api::new(&api.root, "invalid");
+        assert_eq!(Some(10), api.user_name("gauss").unwrap_err().code());
+        assert_eq!(Some(10), api.check_key().unwrap_err().code());
+
+        listening.close().unwrap();
+    }
+
+    #[test]
+    fn test_recent_tracks() {
+        let (mut listening, api) = mock_api();
+
+        assert_eq!(vec![RecentTrack { name: "Starlight".to_owned(), artist: "Muse".to_owned(),
+                                      album: "Black Holes and Revelations".to_owned(), played_at: None }],
+                   api.recent_tracks("gauss", 1).unwrap());
+        assert_eq!(Some(1476471840), api.recent_tracks("holo", 1).unwrap()[0].played_at);
+        assert!(api.recent_tracks("silent", 1).unwrap().is_empty());
+
+        listening.close().unwrap();
+    }
+}
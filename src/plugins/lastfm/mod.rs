@@ -0,0 +1,686 @@
+use std::io;
+use std::cmp;
+use std::env;
+use std::sync::Mutex;
+use irc::client::prelude::*;
+use regex::Regex;
+use time;
+use plugin::{self, Plugin};
+use redis::{Commands, RedisResult};
+use store::Store;
+
+lazy_static! {
+    static ref LINK:     Regex        = Regex::new(r"^!(?:addlastfmuser|lastfm\s+link)\s+(\S+)\s*$").unwrap();
+    static ref ACCOUNT:  Regex        = Regex::new(r"^!lastfm\s+(unlink|whoami)\s*$").unwrap();
+    static ref NP:       Regex        = Regex::new(r"^!(?:np|lastsong)(?:\s+(\S+))?\s*$").unwrap();
+    // RFC 2812
+    static ref NICKNAME: Regex        = Regex::new(r"^[A-Za-z\[\]\\`_^{|}][A-Za-z0-9\[\]\\`_^{|}-]*$").unwrap();
+    static ref STORE:    Mutex<Store> = Mutex::new(Store::open("lastfm").unwrap());
+}
+
+macro_rules! try_option {
+    ($e:expr) => {
+        match $e {
+            Some(v) => v,
+            None    => { return None; }
+        }
+    }
+}
+
+mod api;
+
+use self::api::{Api, Artist, Track};
+
+// nick -> Last.fm user, and the other way around to tell who owns an account
+const USERS:  &'static str = "users";
+const OWNERS: &'static str = "owners";
+
+fn linked_user(store: &Store, nickname: &str) -> Option<String> {
+    store.hget(store.key(USERS), plugin::nick_key(nickname)).unwrap_or(None)
+}
+
+// Before the keys were namespaced, each nick that linked an account had a bare
+// "nick -> Last.fm user" key. Only the sender's own one is moved to USERS and OWNERS,
+// never a key named in a message: nicks can't contain the ":" of namespaced keys,
+// while "!np gauss:quotes:last_id" could name any key in the database.
+fn migrate(store: &Store, nickname: &str) {
+    if !NICKNAME.is_match(nickname) || linked_user(store, nickname).is_some() {
+        return;
+    }
+
+    let legacy: Option<String> = store.get(nickname).unwrap_or(None);
+    let username = match legacy {
+        Some(username) => username,
+        None           => { return; }
+    };
+
+    let owner: Option<String> = store.hget(store.key(OWNERS), username.to_lowercase()).unwrap_or(None);
+    let migrated: RedisResult<()> = match owner {
+        // the account has been linked to someone else since
+        Some(_) => store.del(nickname),
+        None    => store.hset(store.key(USERS), plugin::nick_key(nickname), &*username)
+            .and_then(|_: ()| store.hset(store.key(OWNERS), username.to_lowercase(), nickname))
+            .and_then(|_: ()| store.del(nickname))
+    };
+
+    if let Err(e) = migrated {
+        println!("LastFM: cannot move the old association of {} ({:?})", nickname, e);
+    }
+}
+
+const CHART_COMMANDS: &'static [&'static str] = &["!topartists", "!toptracks", "!weekly", "!compare"];
+
+#[derive(PartialEq, Debug, Clone)]
+struct LastFMUser {
+    irc_username:    String,
+    lastfm_username: String
+}
+
+// Without a key, or when Last.fm rejects it, the plugin is disabled. When Last.fm
+// says we are going too fast, it is left alone for a while.
+#[derive(Debug)]
+struct Access {
+    api_key:       Option<String>,
+    limited_until: i64
+}
+
+impl Access {
+    fn new() -> Access {
+        Access {
+            api_key:       None,
+            limited_until: 0
+        }
+    }
+}
+
+const DISABLED:     &'static str = "Sorry, LastFM is not available: it needs an API key (lastfm.api_key)";
+const RATE_LIMITED: &'static str = "Sorry, LastFM asked me to slow down, try again in a minute";
+const BACKOFF:      i64          = 60;
+
+register_plugin!(LastFM, access: Access);
+
+impl LastFM {
+    fn grep_username<'a>(&self, msg: &'a str) -> Option<&'a str> {
+        match LINK.captures(&msg) {
+            Some(captures) => captures.at(1),
+            None           => None
+        }
+    }
+
+    fn link(&mut self, server: &IrcServer, message: &Message, target: &str, msg: &str) -> io::Result<()> {
+        let (nickname, username) = match (message.source_nickname(), self.grep_username(msg)) {
+            (Some(nickname), Some(username)) => (nickname, username),
+            _                                => { return Ok(()); }
+        };
+
+        let api = match self.api(server) {
+            Ok(api)     => api,
+            Err(notice) => { return server.send_privmsg(target, notice); }
+        };
+
+        // typos would otherwise only show up at the next !np
+        let username = match api.user_name(username) {
+            Ok(username) => username,
+            Err(e)       => {
+                if e.code() == Some(api::INVALID_PARAMETERS) {
+                    return server.send_privmsg(target, &*format!("There is no LastFM user called {}", username));
+                }
+
+                return self.failure(server, target, e.code(), format!("{:?}", e));
+            }
+        };
+
+        let store = STORE.lock().unwrap();
+        let owner:    Option<String> = store.hget(store.key(OWNERS), username.to_lowercase()).unwrap_or(None);
+        let previous = linked_user(&store, nickname);
+
+        match owner {
+            Some(ref owner) if plugin::nick_key(owner) != plugin::nick_key(nickname) => {
+                server.send_privmsg(target,
+                                    &*format!("The LastFM user {} is already associated to {}, who has to !lastfm unlink first",
+                                    username, owner))
+            },
+            _ => {
+                // a nick is associated to a single account, the previous one is set free
+                let freed: RedisResult<()> = match previous {
+                    Some(previous) => store.hdel(store.key(OWNERS), previous.to_lowercase()),
+                    None           => Ok(())
+                };
+
+                let saved: RedisResult<()> = freed
+                    .and_then(|_| store.hset(store.key(USERS),  plugin::nick_key(nickname), &*username))
+                    .and_then(|_: ()| store.hset(store.key(OWNERS), username.to_lowercase(), nickname));
+
+                match saved {
+                    Ok(()) => server.send_privmsg(target, &*format!("{} is now associated to the LastFM user {}", nickname, username)),
+                    Err(e) => server.send_privmsg(target, &*format!("Something bad happened: {:?}", e))
+                }
+            }
+        }
+    }
+
+    fn unlink(&mut self, server: &IrcServer, nickname: &str, target: &str) -> io::Result<()> {
+        let store = STORE.lock().unwrap();
+
+        match linked_user(&store, nickname) {
+            Some(username) => {
+                let removed: RedisResult<()> = store.hdel(store.key(USERS), plugin::nick_key(nickname))
+                    .and_then(|_: ()| store.hdel(store.key(OWNERS), username.to_lowercase()));
+
+                match removed {
+                    Ok(()) => server.send_privmsg(target, &*format!("{} is no longer associated to the LastFM user {}", nickname, username)),
+                    Err(e) => server.send_privmsg(target, &*format!("Something bad happened: {:?}", e))
+                }
+            },
+            None => server.send_privmsg(target, &*format!("{} is not associated to any LastFM user", nickname))
+        }
+    }
+
+    fn whoami(&self, server: &IrcServer, nickname: &str, target: &str) -> io::Result<()> {
+        let store = STORE.lock().unwrap();
+
+        match linked_user(&store, nickname) {
+            Some(username) => server.send_privmsg(target, &*format!("{} is associated to the LastFM user {}", nickname, username)),
+            None           => server.send_privmsg(target, &*format!("{} is not associated to any LastFM user. Try !lastfm link <username>", nickname))
+        }
+    }
+
+    fn account(&mut self, server: &IrcServer, message: &Message, target: &str, msg: &str) -> io::Result<()> {
+        let nickname = match message.source_nickname() {
+            Some(nickname) => nickname,
+            None           => { return Ok(()); }
+        };
+
+        match ACCOUNT.captures(msg).and_then(|captures| captures.at(1)) {
+            Some("unlink") => self.unlink(server, nickname, target),
+            Some(_)        => self.whoami(server, nickname, target),
+            None           => Ok(())
+        }
+    }
+
+    // The Last.fm user associated to the given nick, otherwise the nick itself.
+    fn lastfm_username(&self, name: &str) -> String {
+        let store = STORE.lock().unwrap();
+
+        linked_user(&store, name).unwrap_or(name.to_owned())
+    }
+
+    // `!np nick` looks up the Last.fm user associated to nick, falling back to nick
+    // as a Last.fm username. Without arguments, the caller is looked up.
+    fn resolve_username(&self, message: &Message, msg: &str) -> Option<String> {
+        let name = match NP.captures(msg).and_then(|captures| captures.at(1)) {
+            Some(name) => name,
+            None       => try_option!(message.source_nickname())
+        };
+
+        Some(self.lastfm_username(name))
+    }
+
+    fn api_key(&self) -> Result<String, &'static str> {
+        match self.access.api_key {
+            Some(_) if self.access.limited_until > time::get_time().sec => Err(RATE_LIMITED),
+            Some(ref api_key)                                          => Ok(api_key.clone()),
+            None                                                       => Err(DISABLED)
+        }
+    }
+
+    fn api(&self, server: &IrcServer) -> Result<Api, &'static str> {
+        let api_key = try!(self.api_key());
+        Ok(Api::new(plugin::get_option(server, "lastfm.api_root").unwrap_or(api::API_ROOT), &api_key))
+    }
+
+    fn failure(&mut self, server: &IrcServer, target: &str, code: Option<i64>, error: String) -> io::Result<()> {
+        match code {
+            Some(api::INVALID_API_KEY) | Some(api::SUSPENDED_API_KEY) => {
+                println!("LastFM: the API key has been rejected ({}), disabling the plugin", error);
+                self.access.api_key = None;
+                server.send_privmsg(target, DISABLED)
+            },
+            Some(api::RATE_LIMIT_EXCEEDED) => {
+                self.access.limited_until = time::get_time().sec + BACKOFF;
+                server.send_privmsg(target, RATE_LIMITED)
+            },
+            _ => server.send_privmsg(target, &*format!("Something bad happened: {}", error))
+        }
+    }
+
+    fn grep_chart_command<'a>(&self, msg: &'a str) -> Option<(&'a str, Vec<&'a str>)> {
+        let mut words = msg.split_whitespace();
+        let command   = try_option!(words.next());
+
+        if CHART_COMMANDS.contains(&command) {
+            Some((command, words.collect()))
+        }
+        else {
+            None
+        }
+    }
+
+    fn charts(&mut self, server: &IrcServer, message: &Message, target: &str, msg: &str) -> io::Result<()> {
+        let (command, mut args) = match self.grep_chart_command(msg) {
+            Some(command) => command,
+            None          => { return Ok(()); }
+        };
+
+        let api = match self.api(server) {
+            Ok(api)     => api,
+            Err(notice) => { return server.send_privmsg(target, notice); }
+        };
+
+        let limit = plugin::get_option(server, "lastfm.chart_size")
+            .and_then(|size| size.parse().ok())
+            .unwrap_or(5);
+
+        if command == "!compare" {
+            if args.len() != 2 {
+                return server.send_privmsg(target, "Usage: !compare nick1 nick2");
+            }
+
+            let (first, second) = (self.lastfm_username(args[0]), self.lastfm_username(args[1]));
+            return match compatibility(&api, &first, &second, limit) {
+                Ok(reply) => server.send_privmsg(target, &reply),
+                Err(e)    => self.failure(server, target, e.code(), format!("{:?}", e))
+            };
+        }
+
+        let (period, label) = match args.last().and_then(|arg| period(arg)) {
+            Some(period) => (period, args.pop().unwrap()),
+            None         => ("overall", "all")
+        };
+
+        let username = match (args.len(), args.first()) {
+            (0, _)          => match message.source_nickname() {
+                Some(nickname) => self.lastfm_username(nickname),
+                None           => { return Ok(()); }
+            },
+            (1, Some(nick)) => self.lastfm_username(nick),
+            _               => { return server.send_privmsg(target, &*format!("Usage: {} [nick] [7d|1m|3m|6m|12m|all]", command)); }
+        };
+
+        match chart(&api, command, &username, (period, label), limit) {
+            Ok(reply) => server.send_privmsg(target, &reply),
+            Err(e)    => self.failure(server, target, e.code(), format!("{:?}", e))
+        }
+    }
+
+    fn now_playing(&mut self, server: &IrcServer, message: &Message, target: &str, msg: &str) -> io::Result<()> {
+        let api = match self.api(server) {
+            Ok(api)     => api,
+            Err(notice) => { return server.send_privmsg(target, notice); }
+        };
+
+        let username = match self.resolve_username(message, msg) {
+            Some(username) => username,
+            None           => { return Ok(()); }
+        };
+
+        match last_song(&api, &username, time::get_time().sec) {
+            Ok(reply) => server.send_privmsg(target, &reply),
+            Err(e)    => self.failure(server, target, e.code(), format!("{:?}", e))
+        }
+    }
+}
+
+fn period(arg: &str) -> Option<&'static str> {
+    match arg {
+        "7d"  => Some("7day"),
+        "1m"  => Some("1month"),
+        "3m"  => Some("3month"),
+        "6m"  => Some("6month"),
+        "12m" => Some("12month"),
+        "all" => Some("overall"),
+        _     => None
+    }
+}
+
+fn format_artists(artists: &[Artist]) -> String {
+    let artists: Vec<String> = artists.iter().map(|artist| format!("{} ({})", artist.name, artist.playcount)).collect();
+    artists.join(", ")
+}
+
+fn format_tracks(tracks: &[Track]) -> String {
+    let tracks: Vec<String> = tracks.iter().map(|track| format!("{} by {} ({})", track.name, track.artist, track.playcount)).collect();
+    tracks.join(", ")
+}
+
+fn last_song(api: &Api, username: &str, now: i64) -> Result<String, api::Error> {
+    let tracks = try!(api.recent_tracks(username, 1));
+
+    Ok(match tracks.first() {
+        Some(track) => {
+            let album = if track.album.is_empty() { String::new() } else { format!(" (in {})", track.album) };
+
+            match track.played_at {
+                None            => format!("{} is now playing {} by {}{}", username, track.name, track.artist, album),
+                Some(played_at) => format!("{} last listened to {} by {}{}, {}",
+                                           username, track.name, track.artist, album, plugin::time_ago(now - played_at))
+            }
+        },
+        None => format!("I don't know what is the last song {} listened to. Try !lastfm link <username>", username)
+    })
+}
+
+// `period` is the Last.fm name of the period and how it was asked for.
+fn chart(api: &Api, command: &str, username: &str, period: (&str, &str), limit: usize) -> Result<String, api::Error> {
+    let (period, label) = period;
+    let (title, chart)  = match command {
+        "!topartists" => (format!("Top artists for {} ({})", username, label),
+                          format_artists(&try!(api.top_artists(username, period, limit as u32)))),
+        "!toptracks"  => (format!("Top tracks for {} ({})", username, label),
+                          format_tracks(&try!(api.top_tracks(username, period, limit as u32)))),
+        _             => {
+            let artists = try!(api.weekly_artists(username));
+            (format!("Weekly artists for {}", username), format_artists(&artists[..cmp::min(artists.len(), limit)]))
+        }
+    };
+
+    if chart.is_empty() {
+        Ok(format!("{}: nothing yet", title))
+    }
+    else {
+        Ok(format!("{}: {}", title, chart))
+    }
+}
+
+fn compatibility(api: &Api, first: &str, second: &str, limit: usize) -> Result<String, api::Error> {
+    let (score, shared) = compare(&try!(api.top_artists(first, "overall", 50)), &try!(api.top_artists(second, "overall", 50)));
+
+    if shared.is_empty() {
+        Ok(format!("{} and {} have nothing in common", first, second))
+    }
+    else {
+        let shared: Vec<&str> = shared.iter().take(limit).map(|artist| &artist[..]).collect();
+        Ok(format!("{} and {} are {}% compatible, both listen to {}", first, second, score, shared.join(", ")))
+    }
+}
+
+// The shared artists, best ranked first, and how much of the shorter chart they cover.
+fn compare(first: &[Artist], second: &[Artist]) -> (usize, Vec<String>) {
+    let mut shared: Vec<(usize, String)> = first.iter().enumerate().filter_map(|(i, artist)| {
+        second.iter()
+            .position(|other| other.name.to_lowercase() == artist.name.to_lowercase())
+            .map(|j| (i + j, artist.name.clone()))
+    }).collect();
+    shared.sort_by_key(|&(rank, _)| rank);
+
+    let shortest = cmp::min(first.len(), second.len());
+    let score    = if shortest == 0 { 0 } else { shared.len() * 100 / shortest };
+
+    (score, shared.into_iter().map(|(_, name)| name).collect())
+}
+
+impl Plugin for LastFM {
+    // LASTFM_API_KEY, when set, takes precedence over `lastfm.api_key`. A key is only
+    // refused when Last.fm says so, not when it can't be reached.
+    fn configure(&mut self, server: &IrcServer) {
+        let api_key = match env::var("LASTFM_API_KEY") {
+            Ok(api_key) => api_key,
+            Err(_)      => plugin::get_option(server, "lastfm.api_key").unwrap_or("").to_owned()
+        };
+
+        if api_key.is_empty() {
+            println!("LastFM: no API key in lastfm.api_key or LASTFM_API_KEY, disabling the plugin");
+            return;
+        }
+
+        let api = Api::new(plugin::get_option(server, "lastfm.api_root").unwrap_or(api::API_ROOT), &api_key);
+        match api.check_key().map_err(|e| e.code()) {
+            Err(Some(api::INVALID_API_KEY)) | Err(Some(api::SUSPENDED_API_KEY)) => {
+                println!("LastFM: the API key has been rejected, disabling the plugin");
+            },
+            _ => {
+                self.access.api_key = Some(api_key);
+            }
+        }
+    }
+
+    fn is_allowed(&self, _: &IrcServer, message: &Message) -> bool {
+        match message.command {
+            Command::PRIVMSG(_, ref msg) => {
+                NP.is_match(msg) || ACCOUNT.is_match(msg) || self.grep_username(msg).is_some() || self.grep_chart_command(msg).is_some()
+            },
+            _ => false
+        }
+    }
+
+    fn execute(&mut self, server: &IrcServer, message: &Message) -> io::Result<()> {
+        match message.command {
+            Command::PRIVMSG(ref target, ref msg) => {
+                if let Some(nickname) = message.source_nickname() {
+                    migrate(&STORE.lock().unwrap(), nickname);
+                }
+
+                if NP.is_match(msg) {
+                    self.now_playing(server, message, target, msg)
+                }
+                else if ACCOUNT.is_match(msg) {
+                    self.account(server, message, target, msg)
+                }
+                else if self.grep_username(msg).is_some() {
+                    self.link(server, message, target, msg)
+                }
+                else if self.grep_chart_command(msg).is_some() {
+                    self.charts(server, message, target, msg)
+                }
+                else {
+                    Ok(())
+                }
+            },
+            _ => Ok(())
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use ::tests::{make_server, make_server_with_options, get_server_value};
+
+    use irc::client::prelude::*;
+
+    use redis::{Commands, RedisResult};
+
+    use plugin::{self, Plugin};
+    use store::Store;
+    use super::{LastFM, DISABLED, RATE_LIMITED, NICKNAME, USERS, compare, format_artists, last_song, chart, compatibility};
+    use super::api::tests::mock_api;
+
+    #[test]
+    fn test_lastsong() {
+        let     server = make_server("PRIVMSG test :!lastsong\r\n");
+        let mut plugin = LastFM::new();
+
+        for message in server.iter() {
+            let message = message.unwrap();
+            assert!(plugin.is_allowed(&server, &message));
+            assert!(plugin.execute(&server, &message).is_ok());
+            // source_nickname() is None, this won't work
+        }
+    }
+
+    #[test]
+    fn test_np() {
+        let server = make_server("PRIVMSG test :!np RoxasShadow\r\n");
+        let plugin = LastFM::new();
+
+        for message in server.iter() {
+            let message = message.unwrap();
+            assert!(plugin.is_allowed(&server, &message));
+        }
+    }
+
+    #[test]
+    fn test_not_np() {
+        let server = make_server("PRIVMSG test :!npc RoxasShadow\r\n");
+        let plugin = LastFM::new();
+
+        for message in server.iter() {
+            let message = message.unwrap();
+            assert!(!plugin.is_allowed(&server, &message));
+        }
+    }
+
+    #[test]
+    fn test_add_user() {
+        let     server = make_server("PRIVMSG test :!addlastfmuser Gaussimandro\r\n");
+        let mut plugin = LastFM::new();
+
+        for message in server.iter() {
+            let message = message.unwrap();
+            assert!(plugin.is_allowed(&server, &message));
+            assert!(plugin.execute(&server, &message).is_ok());
+            // source_nickname() is None, this won't work
+        }
+    }
+
+    #[test]
+    fn test_compare() {
+        let (mut listening, api) = mock_api();
+
+        let first  = api.top_artists("gauss", "overall", 50).unwrap();
+        let second = api.top_artists("holo",  "overall", 50).unwrap();
+        assert_eq!((66, vec!["Queen".to_owned(), "Rhapsody".to_owned()]), compare(&first, &second));
+        assert_eq!((0,  vec![]),                                         compare(&first, &[]));
+        assert_eq!("Queen (120), Muse (98)",                              format_artists(&first[..2]));
+
+        listening.close().unwrap();
+    }
+
+    #[test]
+    fn test_last_song() {
+        let (mut listening, _) = mock_api();
+        let server = make_server_with_options("", &[("lastfm.api_root", &*format!("http://{}/2.0/", listening.socket)),
+                                                    ("lastfm.api_key",  "test")]);
+        let mut plugin = LastFM::new();
+        plugin.configure(&server);
+        let api = plugin.api(&server).unwrap();
+
+        assert_eq!("gauss is now playing Starlight by Muse (in Black Holes and Revelations)",
+                   last_song(&api, "gauss", 1476471840).unwrap());
+        assert_eq!("holo last listened to Emerald Sword by Rhapsody, 2 hours ago",
+                   last_song(&api, "holo", 1476471840 + 2 * 3600).unwrap());
+        assert_eq!("I don't know what is the last song silent listened to. Try !lastfm link <username>",
+                   last_song(&api, "silent", 1476471840).unwrap());
+        assert_eq!(Some(6), last_song(&api, "nobody", 1476471840).unwrap_err().code());
+
+        listening.close().unwrap();
+    }
+
+    #[test]
+    fn test_charts() {
+        let (mut listening, _) = mock_api();
+        let server = make_server_with_options("", &[("lastfm.api_root", &*format!("http://{}/2.0/", listening.socket)),
+                                                    ("lastfm.api_key",  "test")]);
+        let mut plugin = LastFM::new();
+        plugin.configure(&server);
+        let api = plugin.api(&server).unwrap();
+
+        assert_eq!("Top artists for gauss (7d): Queen (120), Muse (98), Rhapsody (12), Blind Guardian (7)",
+                   chart(&api, "!topartists", "gauss", ("7day", "7d"), 5).unwrap());
+        assert_eq!("Top tracks for gauss (all): Bohemian Rhapsody by Queen (42)",
+                   chart(&api, "!toptracks", "gauss", ("overall", "all"), 5).unwrap());
+        assert_eq!("Weekly artists for gauss: Muse (30)",
+                   chart(&api, "!weekly", "gauss", ("overall", "all"), 5).unwrap());
+        assert_eq!("gauss and holo are 66% compatible, both listen to Queen",
+                   compatibility(&api, "gauss", "holo", 1).unwrap());
+        assert_eq!(Some(6), chart(&api, "!topartists", "nobody", ("overall", "all"), 5).unwrap_err().code());
+
+        listening.close().unwrap();
+    }
+
+    #[test]
+    fn test_charts_allowed() {
+        let server = make_server("PRIVMSG test :!topartists Gauss 7d\r\n");
+        let plugin = LastFM::new();
+
+        for message in server.iter() {
+            let message = message.unwrap();
+            assert!(plugin.is_allowed(&server, &message));
+        }
+    }
+
+    #[test]
+    fn test_account() {
+        let     server = make_server("PRIVMSG test :!lastfm link Gaussimandro\r\nPRIVMSG test :!lastfm whoami\r\nPRIVMSG test :!lastfm unlink\r\n");
+        let mut plugin = LastFM::new();
+
+        for message in server.iter() {
+            let message = message.unwrap();
+            assert!(plugin.is_allowed(&server, &message));
+            assert!(plugin.execute(&server, &message).is_ok());
+            // source_nickname() is None, this won't work
+        }
+    }
+
+    #[test]
+    fn test_nickname() {
+        assert!(NICKNAME.is_match("Holo[]"));
+        assert!(NICKNAME.is_match("gauss_-42"));
+        assert!(!NICKNAME.is_match("gauss:quotes:last_id"));
+        assert!(!NICKNAME.is_match("42gauss"));
+    }
+
+    // Needs Redis, like the commands themselves, and is skipped without it.
+    #[test]
+    fn test_other_keys_left_alone() {
+        let store = match Store::open("lastfm-test") {
+            Ok(store) => store,
+            Err(_)    => { return; }
+        };
+
+        let key = store.key("last_id");
+        let saved: RedisResult<()> = store.set(&*key, "42");
+        assert!(saved.is_ok());
+
+        let (mut listening, _) = mock_api();
+        let     server = make_server_with_options(&format!(":Holo!holo@example.com PRIVMSG #test :!np {}\r\n\
+                                                            :Holo!holo@example.com PRIVMSG #test :!topartists {}\r\n", key, key),
+                                                  &[("lastfm.api_root", &*format!("http://{}/2.0/", listening.socket)),
+                                                    ("lastfm.api_key",  "test")]);
+        let mut plugin = LastFM::new();
+
+        plugin.configure(&server);
+        for message in server.iter() {
+            let message = message.unwrap();
+            assert!(plugin.is_allowed(&server, &message));
+            assert!(plugin.execute(&server, &message).is_ok());
+        }
+
+        let value: Option<String> = store.get(&*key).unwrap();
+        let users = Store::open("lastfm").unwrap();
+        let user: Option<String> = users.hget(users.key(USERS), plugin::nick_key(&key)).unwrap();
+        let _: RedisResult<()> = store.del(&*key);
+
+        assert_eq!(Some("42".to_owned()), value);
+        assert_eq!(None, user);
+        listening.close().unwrap();
+    }
+
+    #[test]
+    fn test_configure() {
+        let (mut listening, _) = mock_api();
+        let root = format!("http://{}/2.0/", listening.socket);
+
+        let     server = make_server_with_options("PRIVMSG test :!topartists\r\n",
+                                                  &[("lastfm.api_root", &*root), ("lastfm.api_key", "invalid")]);
+        let mut plugin = LastFM::new();
+
+        plugin.configure(&server);
+        for message in server.iter() {
+            let message = message.unwrap();
+            assert!(plugin.is_allowed(&server, &message));
+            assert!(plugin.execute(&server, &message).is_ok());
+        }
+        assert_eq!(format!("PRIVMSG test :{}\r\n", DISABLED), get_server_value(&server));
+
+        let     server = make_server_with_options("", &[("lastfm.api_root", &*root), ("lastfm.api_key", "test")]);
+        let mut plugin = LastFM::new();
+
+        plugin.configure(&server);
+        assert!(plugin.api(&server).is_ok());
+
+        assert!(plugin.failure(&server, "test", Some(29), String::new()).is_ok());
+        assert_eq!(RATE_LIMITED, plugin.api(&server).unwrap_err());
+
+        listening.close().unwrap();
+    }
+}
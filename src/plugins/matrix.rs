@@ -0,0 +1,276 @@
+use std::io;
+use irc::client::prelude::*;
+use regex::Regex;
+use plugin::Plugin;
+use plugins::calc::number::Number;
+
+lazy_static! {
+    static ref MAT: Regex = Regex::new(r"^!mat(?:\s+([A-Za-z]+))?\s+(\[.*\])\s*$").unwrap();
+}
+
+// Larger matrices are refused, their results wouldn't fit in a line anyway.
+const MAX_SIZE: usize = 8;
+
+// what's left of the 512 bytes of a line after the prefix and the target
+const MAX_REPLY: usize = 400;
+
+type Matrix = Vec<Vec<Number>>;
+
+// Entries are exact, like 2, -1.5 or 1/3.
+fn parse_entry(entry: &str) -> Result<Number, String> {
+    let (negative, entry) = if entry.starts_with('-') { (true, &entry[1..]) } else { (false, entry) };
+    let mut parts = entry.splitn(2, '/');
+    let numerator = try!(Number::parse(parts.next().unwrap_or("")));
+    let value     = match parts.next() {
+        Some(denominator) => try!(numerator.div(&try!(Number::parse(denominator)))),
+        None              => numerator
+    };
+
+    Ok(if negative { value.neg() } else { value })
+}
+
+// [[1, 2], [3, 4]]
+fn parse(input: &str) -> Result<Matrix, String> {
+    let input: String = input.chars().filter(|c| !c.is_whitespace()).collect();
+    if input.len() < 4 || !input.starts_with("[[") || !input.ends_with("]]") {
+        return Err("Matrices are written like [[1, 2], [3, 4]]".to_owned());
+    }
+
+    let mut matrix = vec![];
+    for row in input[2..input.len() - 2].split("],[") {
+        let mut entries = vec![];
+        for entry in row.split(',') {
+            entries.push(try!(parse_entry(entry)));
+        }
+
+        matrix.push(entries);
+    }
+
+    if matrix.len() > MAX_SIZE || matrix[0].len() > MAX_SIZE {
+        return Err(format!("Matrices can be at most {}×{}", MAX_SIZE, MAX_SIZE));
+    }
+
+    if matrix.iter().any(|row| row.len() != matrix[0].len()) {
+        return Err("All the rows must have the same length".to_owned());
+    }
+
+    Ok(matrix)
+}
+
+fn describe(matrix: &Matrix) -> String {
+    let rows: Vec<String> = matrix.iter().map(|row| {
+        let entries: Vec<String> = row.iter().map(|entry| entry.short()).collect();
+        format!("[{}]", entries.join(", "))
+    }).collect();
+
+    format!("[{}]", rows.join(", "))
+}
+
+// Rounding leaves tiny reals where there should be zeros.
+fn is_negligible(n: &Number) -> bool {
+    match *n {
+        Number::Exact(_)    => n.is_zero(),
+        Number::Real(value) => value.abs() < 1e-10
+    }
+}
+
+#[derive(Debug)]
+struct Elimination {
+    reduced:     Matrix,
+    rank:        usize,
+    // zero unless the matrix is square and invertible
+    determinant: Number
+}
+
+// Gauss-Jordan elimination down to the reduced row echelon form.
+fn eliminate(matrix: &Matrix) -> Result<Elimination, String> {
+    let mut m           = matrix.clone();
+    let (rows, columns) = (m.len(), m[0].len());
+    let mut determinant = Number::integer(1);
+    let mut rank        = 0;
+
+    for column in 0..columns {
+        if rank == rows {
+            break;
+        }
+
+        // the largest pivot keeps the rounding errors of reals down
+        let mut pivot: Option<usize> = None;
+        for row in rank..rows {
+            let larger = match pivot {
+                Some(pivot) => m[row][column].to_f64().abs() > m[pivot][column].to_f64().abs(),
+                None        => true
+            };
+
+            if !is_negligible(&m[row][column]) && larger {
+                pivot = Some(row);
+            }
+        }
+
+        let pivot = match pivot {
+            Some(pivot) => pivot,
+            None        => { continue; }
+        };
+
+        if pivot != rank {
+            m.swap(pivot, rank);
+            determinant = determinant.neg();
+        }
+
+        let value = m[rank][column].clone();
+        determinant = try!(determinant.mul(&value));
+        for entry in m[rank].iter_mut() {
+            *entry = try!(entry.div(&value));
+        }
+
+        let pivot_row = m[rank].clone();
+        for row in 0..rows {
+            if row == rank || is_negligible(&m[row][column]) {
+                continue;
+            }
+
+            let factor = m[row][column].clone();
+            for (entry, pivot_entry) in m[row].iter_mut().zip(pivot_row.iter()) {
+                *entry = try!(entry.sub(&try!(pivot_entry.mul(&factor))));
+            }
+        }
+
+        rank += 1;
+    }
+
+    for entry in m.iter_mut().flat_map(|row| row.iter_mut()) {
+        if is_negligible(entry) {
+            *entry = Number::integer(0);
+        }
+    }
+
+    Ok(Elimination {
+        reduced:     m,
+        rank:        rank,
+        determinant: if rank == rows && rows == columns { determinant } else { Number::integer(0) }
+    })
+}
+
+fn transpose(matrix: &Matrix) -> Matrix {
+    (0..matrix[0].len()).map(|column| matrix.iter().map(|row| row[column].clone()).collect()).collect()
+}
+
+// The right half of [A | I] once the left half has been reduced to I.
+fn inverse(matrix: &Matrix) -> Result<Matrix, String> {
+    let size = matrix.len();
+    if size != matrix[0].len() {
+        return Err("Only square matrices have an inverse".to_owned());
+    }
+
+    let augmented: Matrix = matrix.iter().enumerate().map(|(i, row)| {
+        row.iter().cloned().chain((0..size).map(|j| Number::integer(if i == j { 1 } else { 0 }))).collect()
+    }).collect();
+
+    let reduced = try!(eliminate(&augmented)).reduced;
+    if (0..size).any(|i| is_negligible(&reduced[i][i])) {
+        return Err("That matrix is not invertible".to_owned());
+    }
+
+    Ok(reduced.into_iter().map(|row| row[size..].to_vec()).collect())
+}
+
+fn run(operation: Option<&str>, matrix: &Matrix) -> Result<String, String> {
+    let square = matrix.len() == matrix[0].len();
+    match operation.map(|operation| operation.to_lowercase()) {
+        None => {
+            let elimination = try!(eliminate(matrix));
+            let determinant = if square { format!(", det {}", elimination.determinant.short()) } else { String::new() };
+            Ok(format!("{}×{}, rank {}{}", matrix.len(), matrix[0].len(), elimination.rank, determinant))
+        },
+        Some(operation) => match &*operation {
+            "det" if square   => eliminate(matrix).map(|elimination| format!("det = {}", elimination.determinant.short())),
+            "det"             => Err("Only square matrices have a determinant".to_owned()),
+            "inv" | "inverse" => inverse(matrix).map(|inverse| format!("inverse = {}", describe(&inverse))),
+            "t" | "transpose" => Ok(format!("transpose = {}", describe(&transpose(matrix)))),
+            "rank"            => eliminate(matrix).map(|elimination| format!("rank = {}", elimination.rank)),
+            "rref" | "gauss"  => eliminate(matrix).map(|elimination| format!("rref = {}", describe(&elimination.reduced))),
+            _                 => Err(format!("Unknown operation {}, try det, inv, transpose, rank or rref", operation))
+        }
+    }
+}
+
+register_plugin!(Matrices);
+
+impl Plugin for Matrices {
+    fn is_allowed(&self, _: &IrcServer, message: &Message) -> bool {
+        match message.command {
+            Command::PRIVMSG(_, ref msg) => MAT.is_match(msg),
+            _ => false
+        }
+    }
+
+    fn execute(&mut self, server: &IrcServer, message: &Message) -> io::Result<()> {
+        match message.command {
+            Command::PRIVMSG(ref target, ref msg) => {
+                let captures = MAT.captures(msg).unwrap();
+                let result   = parse(captures.at(2).unwrap()).and_then(|matrix| run(captures.at(1), &matrix));
+
+                match result {
+                    Ok(ref result) if result.len() > MAX_REPLY => server.send_privmsg(target, "The result is too long to show"),
+                    Ok(result) => server.send_privmsg(target, &result),
+                    Err(e)     => server.send_privmsg(target, &e)
+                }
+            },
+            _ => Ok(())
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use ::tests::{make_server, get_server_value};
+
+    use irc::client::prelude::*;
+
+    use plugin::Plugin;
+    use super::{Matrices, parse, run};
+
+    fn mat(operation: Option<&str>, matrix: &str) -> Result<String, String> {
+        parse(matrix).and_then(|matrix| run(operation, &matrix))
+    }
+
+    #[test]
+    fn test_mat() {
+        let     server = make_server("PRIVMSG #test :!mat inv [[1,2],[3,4]]\r\n");
+        let mut plugin = Matrices::new();
+
+        for message in server.iter() {
+            let message = message.unwrap();
+            assert!(plugin.is_allowed(&server, &message));
+            assert!(plugin.execute(&server, &message).is_ok());
+        }
+
+        assert_eq!("PRIVMSG #test :inverse = [[-2, 1], [3/2, -1/2]]\r\n", &*get_server_value(&server));
+    }
+
+    #[test]
+    fn test_operations() {
+        assert_eq!(Ok("2×2, rank 2, det -2".to_owned()), mat(None, "[[1, 2], [3, 4]]"));
+        assert_eq!(Ok("det = 0".to_owned()),             mat(Some("det"), "[[1,2,3],[4,5,6],[7,8,9]]"));
+        assert_eq!(Ok("det = -306".to_owned()),          mat(Some("det"), "[[6,1,1],[4,-2,5],[2,8,7]]"));
+        assert_eq!(Ok("rank = 2".to_owned()),            mat(Some("rank"), "[[1,2,3],[4,5,6],[7,8,9]]"));
+        assert_eq!(Ok("rref = [[1, 0, -1], [0, 1, 2], [0, 0, 0]]".to_owned()),
+                   mat(Some("rref"), "[[1,2,3],[4,5,6],[7,8,9]]"));
+        assert_eq!(Ok("transpose = [[1, 3, 5], [2, 4, 6]]".to_owned()), mat(Some("T"), "[[1,2],[3,4],[5,6]]"));
+        assert_eq!(Ok("inverse = [[1/2, 0], [0, 4]]".to_owned()),       mat(Some("inverse"), "[[2, 0], [0, 0.25]]"));
+        assert_eq!(Ok("2×3, rank 1".to_owned()),          mat(None, "[[1, 2, 3], [2, 4, 6]]"));
+    }
+
+    #[test]
+    fn test_errors() {
+        assert!(mat(Some("inv"), "[[1,2],[2,4]]").is_err());
+        assert!(mat(Some("det"), "[[1,2,3],[4,5,6]]").is_err());
+        assert!(mat(Some("frobnicate"), "[[1]]").is_err());
+        assert!(mat(None, "[[1,2],[3]]").is_err());
+        assert!(mat(None, "[[1,x]]").is_err());
+        assert!(mat(None, "[1,2]").is_err());
+        assert!(mat(None, "[[]]").is_err());
+        assert!(mat(None, "[[1/0]]").is_err());
+        assert!(mat(None, "[[1,2,3,4,5,6,7,8,9]]").is_err());
+    }
+}
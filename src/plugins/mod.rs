@@ -1,6 +1,16 @@
-pub mod h;
+pub mod rules;
 pub mod url;
 pub mod seen;
 pub mod lastfm;
 pub mod tangorin;
 pub mod currency;
+pub mod factoids;
+pub mod karma;
+pub mod quotes;
+pub mod reminders;
+pub mod tell;
+pub mod calc;
+pub mod numbers;
+pub mod matrix;
+pub mod stats;
+pub mod dice;
@@ -0,0 +1,230 @@
+use std::io;
+use irc::client::prelude::*;
+use regex::Regex;
+use plugin::{self, Plugin};
+use plugins::calc::bigint::BigInt;
+
+register_plugin!(Numbers);
+
+lazy_static! {
+    static ref COMMAND: Regex = Regex::new(r"^!(factor|isprime|nextprime|gcd|lcm|modinv|modpow|totient|legendre)((?:\s+\S+)*)\s*$").unwrap();
+}
+
+mod theory;
+
+use self::theory::{Deadline, Primality};
+
+// Larger arguments are refused before doing anything with them.
+const MAX_DIGITS: usize = 100;
+
+fn parse(argument: &str) -> Result<BigInt, String> {
+    let (negative, digits) = if argument.starts_with('-') { (true, &argument[1..]) } else { (false, argument) };
+    let n = try!(BigInt::parse(digits).ok_or(format!("{} is not an integer", argument)));
+    if n.digits() > MAX_DIGITS {
+        return Err("That number is too large".to_owned());
+    }
+
+    Ok(if negative { -&n } else { n })
+}
+
+fn positive(n: BigInt) -> Result<BigInt, String> {
+    if n.is_negative() || n.is_zero() {
+        Err(format!("{} is not a positive integer", n))
+    }
+    else {
+        Ok(n)
+    }
+}
+
+// 360 = 2^3 × 3^2 × 5
+fn describe_factors(n: &BigInt, factors: &[BigInt]) -> String {
+    let mut powers: Vec<(&BigInt, usize)> = vec![];
+    for factor in factors {
+        if powers.last().map(|&(last, _)| last == factor).unwrap_or(false) {
+            powers.last_mut().unwrap().1 += 1;
+        }
+        else {
+            powers.push((factor, 1));
+        }
+    }
+
+    let mut terms: Vec<String> = powers.iter().map(|&(factor, exponent)| match exponent {
+        1 => factor.to_string(),
+        _ => format!("{}^{}", factor, exponent)
+    }).collect();
+
+    if n.is_negative() {
+        terms.insert(0, "-1".to_owned());
+    }
+
+    if terms.is_empty() {
+        terms.push("1".to_owned());
+    }
+
+    format!("{} = {}", n, terms.join(" × "))
+}
+
+fn run(command: &str, arguments: &[BigInt], deadline: &Deadline) -> Result<String, String> {
+    let usage = match command {
+        "factor" | "isprime" | "nextprime" | "totient" => (1, "n"),
+        "gcd" | "lcm"                                   => (2, "a b..."),
+        "modinv" | "legendre"                           => (2, if command == "modinv" { "a m" } else { "a p" }),
+        _                                               => (3, "base exponent m")
+    };
+
+    let variadic = command == "gcd" || command == "lcm";
+    if arguments.len() < usage.0 || (!variadic && arguments.len() > usage.0) {
+        return Err(format!("Usage: !{} {}", command, usage.1));
+    }
+
+    let n = &arguments[0];
+    match command {
+        "factor" => {
+            if n.is_zero() {
+                return Err("0 has no factorisation".to_owned());
+            }
+
+            theory::factor(n, deadline).map(|factors| describe_factors(n, &factors))
+        },
+        "isprime" => Ok(match try!(theory::is_prime(n, deadline)) {
+            Primality::Prime         => format!("{} is prime", n),
+            Primality::ProbablyPrime => format!("{} is probably prime", n),
+            Primality::Composite     => format!("{} is not prime", n)
+        }),
+        "nextprime" => theory::next_prime(n, deadline).map(|p| format!("The next prime after {} is {}", n, p)),
+        "gcd" | "lcm" => {
+            let mut result = n.abs();
+            for argument in &arguments[1..] {
+                result = if command == "gcd" { result.gcd(argument) } else { theory::lcm(&result, argument) };
+            }
+
+            let arguments: Vec<String> = arguments.iter().map(|argument| argument.to_string()).collect();
+            Ok(format!("{}({}) = {}", command, arguments.join(", "), result))
+        },
+        "modinv" => {
+            let m = try!(positive(arguments[1].clone()));
+            match theory::mod_inverse(n, &m) {
+                Some(inverse) => Ok(format!("{}⁻¹ mod {} = {}", n, m, inverse)),
+                None          => Err(format!("{} has no inverse mod {}", n, m))
+            }
+        },
+        "modpow" => {
+            let (exponent, m) = (&arguments[1], try!(positive(arguments[2].clone())));
+
+            // a negative exponent is a power of the inverse
+            let base = if exponent.is_negative() {
+                try!(theory::mod_inverse(n, &m).ok_or(format!("{} has no inverse mod {}", n, m)))
+            }
+            else {
+                n.clone()
+            };
+
+            theory::pow_mod(&base, &exponent.abs(), &m, deadline).map(|power| format!("{}^{} mod {} = {}", n, exponent, m, power))
+        },
+        "totient" => {
+            let n = try!(positive(n.clone()));
+            theory::totient(&n, deadline).map(|phi| format!("φ({}) = {}", n, phi))
+        },
+        "legendre" => theory::legendre(n, &arguments[1], deadline).map(|symbol| format!("({}/{}) = {}", n, arguments[1], symbol)),
+        _ => Err(format!("Usage: !{} {}", command, usage.1))
+    }
+}
+
+impl Plugin for Numbers {
+    fn is_allowed(&self, _: &IrcServer, message: &Message) -> bool {
+        match message.command {
+            Command::PRIVMSG(_, ref msg) => COMMAND.is_match(msg),
+            _ => false
+        }
+    }
+
+    fn execute(&mut self, server: &IrcServer, message: &Message) -> io::Result<()> {
+        match message.command {
+            Command::PRIVMSG(ref target, ref msg) => {
+                let captures = COMMAND.captures(msg).unwrap();
+                let timeout  = plugin::get_option(server, "numbers.timeout")
+                    .and_then(|timeout| timeout.parse().ok())
+                    .unwrap_or(2000);
+
+                let mut arguments = vec![];
+                for argument in captures.at(2).unwrap_or("").split_whitespace() {
+                    match parse(argument) {
+                        Ok(n)  => arguments.push(n),
+                        Err(e) => { return server.send_privmsg(target, &e); }
+                    }
+                }
+
+                match run(captures.at(1).unwrap(), &arguments, &Deadline::after(timeout)) {
+                    Ok(result) => server.send_privmsg(target, &result),
+                    Err(e)     => server.send_privmsg(target, &e)
+                }
+            },
+            _ => Ok(())
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use ::tests::{make_server, make_server_with_options, get_server_value};
+
+    use irc::client::prelude::*;
+
+    use plugin::Plugin;
+    use plugins::calc::bigint::BigInt;
+    use super::{Numbers, describe_factors};
+
+    fn replies(server: IrcServer) -> String {
+        let mut plugin = Numbers::new();
+
+        for message in server.iter() {
+            let message = message.unwrap();
+            assert!(plugin.is_allowed(&server, &message));
+            assert!(plugin.execute(&server, &message).is_ok());
+        }
+
+        get_server_value(&server)
+    }
+
+    #[test]
+    fn test_commands() {
+        assert_eq!("PRIVMSG #test :360 = 2^3 × 3^2 × 5\r\n\
+                    PRIVMSG #test :18446744073709551617 is not prime\r\n\
+                    PRIVMSG #test :The next prime after 100 is 101\r\n\
+                    PRIVMSG #test :gcd(12, -18, 27) = 3\r\n\
+                    PRIVMSG #test :3⁻¹ mod 7 = 5\r\n\
+                    PRIVMSG #test :6 has no inverse mod 9\r\n\
+                    PRIVMSG #test :3^-1 mod 7 = 5\r\n\
+                    PRIVMSG #test :φ(36) = 12\r\n\
+                    PRIVMSG #test :(3/7) = -1\r\n\
+                    PRIVMSG #test :Usage: !modpow base exponent m\r\n\
+                    PRIVMSG #test :x is not an integer\r\n",
+                   &*replies(make_server("PRIVMSG #test :!factor 360\r\n\
+                                          PRIVMSG #test :!isprime 18446744073709551617\r\n\
+                                          PRIVMSG #test :!nextprime 100\r\n\
+                                          PRIVMSG #test :!gcd 12 -18 27\r\n\
+                                          PRIVMSG #test :!modinv 3 7\r\n\
+                                          PRIVMSG #test :!modinv 6 9\r\n\
+                                          PRIVMSG #test :!modpow 3 -1 7\r\n\
+                                          PRIVMSG #test :!totient 36\r\n\
+                                          PRIVMSG #test :!legendre 3 7\r\n\
+                                          PRIVMSG #test :!modpow 3 7\r\n\
+                                          PRIVMSG #test :!lcm 2 x\r\n")));
+    }
+
+    #[test]
+    fn test_too_large() {
+        let huge = format!("PRIVMSG #test :!factor 1{}\r\n", (0..100).map(|_| "0").collect::<String>());
+        assert_eq!("PRIVMSG #test :That number is too large\r\n", &*replies(make_server(&huge)));
+
+        let server = make_server_with_options("PRIVMSG #test :!factor 1208925819614629174706189\r\n", &[("numbers.timeout", "0")]);
+        assert_eq!("PRIVMSG #test :That number is too large\r\n", &*replies(server));
+    }
+
+    #[test]
+    fn test_describe_factors() {
+        let factors: Vec<BigInt> = vec![2, 2, 3].into_iter().map(BigInt::from_i64).collect();
+        assert_eq!("-12 = -1 × 2^2 × 3", describe_factors(&BigInt::from_i64(-12), &factors));
+        assert_eq!("1 = 1", describe_factors(&BigInt::one(), &[]));
+    }
+}
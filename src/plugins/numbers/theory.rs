@@ -0,0 +1,454 @@
+use time;
+use plugins::calc::bigint::BigInt;
+
+// Miller-Rabin with these bases never lies below 3.3 * 10^24, so it is
+// deterministic for anything that fits in 64 bits.
+const BASES: [u64; 13] = [2, 3, 5, 7, 11, 13, 17, 19, 23, 29, 31, 37, 41];
+
+// tried before Pollard's rho, which is bad at finding small factors
+const SMALL_PRIMES: [u64; 25] = [
+    2, 3, 5, 7, 11, 13, 17, 19, 23, 29, 31, 37, 41, 43, 47, 53, 59, 61, 67, 71, 73, 79, 83, 89, 97
+];
+
+// Everything that can run for long checks this, and gives up once it passes.
+#[derive(Debug, Clone, Copy)]
+pub struct Deadline {
+    at: u64
+}
+
+impl Deadline {
+    pub fn after(milliseconds: u64) -> Deadline {
+        Deadline {
+            at: time::precise_time_ns() + milliseconds * 1_000_000
+        }
+    }
+
+    fn check(&self) -> Result<(), String> {
+        if time::precise_time_ns() > self.at {
+            Err("That number is too large".to_owned())
+        }
+        else {
+            Ok(())
+        }
+    }
+}
+
+#[derive(PartialEq, Debug)]
+pub enum Primality {
+    Prime,
+    // above 3.3 * 10^24 Miller-Rabin can only tell for sure when it's not
+    ProbablyPrime,
+    Composite
+}
+
+fn big(n: u64) -> BigInt {
+    BigInt::from_u64(n)
+}
+
+// The remainder with the sign of the modulus.
+pub fn modulo(a: &BigInt, m: &BigInt) -> BigInt {
+    a.div_mod_floor(m).unwrap().1
+}
+
+fn mul_mod_u64(mut a: u64, mut b: u64, m: u64) -> u64 {
+    if a < 1 << 32 && b < 1 << 32 {
+        return a * b % m;
+    }
+
+    // double and add, so that nothing overflows
+    let mut product = 0;
+    a %= m;
+    while b > 0 {
+        if b & 1 == 1 {
+            product = if product >= m - a { product - (m - a) } else { product + a };
+        }
+
+        a = if a >= m - a { a - (m - a) } else { a + a };
+        b >>= 1;
+    }
+
+    product
+}
+
+fn pow_mod_u64(mut base: u64, mut exponent: u64, m: u64) -> u64 {
+    let mut result = 1 % m;
+    base %= m;
+    while exponent > 0 {
+        if exponent & 1 == 1 {
+            result = mul_mod_u64(result, base, m);
+        }
+
+        base = mul_mod_u64(base, base, m);
+        exponent >>= 1;
+    }
+
+    result
+}
+
+fn gcd_u64(mut a: u64, mut b: u64) -> u64 {
+    while b > 0 {
+        let rest = a % b;
+        a = b;
+        b = rest;
+    }
+
+    a
+}
+
+fn is_prime_u64(n: u64) -> bool {
+    if n < 2 {
+        return false;
+    }
+
+    for &p in SMALL_PRIMES.iter() {
+        if n % p == 0 {
+            return n == p;
+        }
+    }
+
+    let (mut d, mut s) = (n - 1, 0);
+    while d % 2 == 0 {
+        d /= 2;
+        s += 1;
+    }
+
+    BASES.iter().all(|&base| {
+        let mut x = pow_mod_u64(base, d, n);
+        if x == 1 || x == n - 1 {
+            return true;
+        }
+
+        for _ in 1..s {
+            x = mul_mod_u64(x, x, n);
+            if x == n - 1 {
+                return true;
+            }
+        }
+
+        false
+    })
+}
+
+// A non trivial factor of n, which must be odd and composite.
+fn rho_u64(n: u64, deadline: &Deadline) -> Result<u64, String> {
+    for c in 1.. {
+        let step = |x: u64| {
+            let square = mul_mod_u64(x, x, n);
+            if square >= n - c { square - (n - c) } else { square + c }
+        };
+        let (mut x, mut y, mut d) = (2, 2, 1);
+        let mut steps = 0u64;
+        while d == 1 {
+            x = step(x);
+            y = step(step(y));
+            d = gcd_u64(if x > y { x - y } else { y - x }, n);
+
+            steps += 1;
+            if steps % 256 == 0 {
+                try!(deadline.check());
+            }
+        }
+
+        if d != n {
+            return Ok(d);
+        }
+    }
+
+    unreachable!()
+}
+
+pub fn pow_mod(base: &BigInt, exponent: &BigInt, m: &BigInt, deadline: &Deadline) -> Result<BigInt, String> {
+    if let (Some(base), Some(exponent), Some(m)) = (modulo(base, m).to_u64(), exponent.to_u64(), m.to_u64()) {
+        return Ok(big(pow_mod_u64(base, exponent, m)));
+    }
+
+    let two          = big(2);
+    let mut base     = modulo(base, m);
+    let mut exponent = exponent.clone();
+    let mut result   = modulo(&BigInt::one(), m);
+    while !exponent.is_zero() {
+        try!(deadline.check());
+        if !exponent.is_even() {
+            result = modulo(&(&result * &base), m);
+        }
+
+        base     = modulo(&(&base * &base), m);
+        exponent = exponent.div_rem(&two).unwrap().0;
+    }
+
+    Ok(result)
+}
+
+pub fn is_prime(n: &BigInt, deadline: &Deadline) -> Result<Primality, String> {
+    if let Some(n) = n.to_u64() {
+        return Ok(if is_prime_u64(n) { Primality::Prime } else { Primality::Composite });
+    }
+
+    if n.is_negative() {
+        return Ok(Primality::Composite);
+    }
+
+    for &p in SMALL_PRIMES.iter() {
+        if modulo(n, &big(p)).is_zero() {
+            return Ok(Primality::Composite);
+        }
+    }
+
+    let one      = BigInt::one();
+    let two      = big(2);
+    let previous = n - &one;
+    let (mut d, mut s) = (previous.clone(), 0);
+    while d.is_even() {
+        d = d.div_rem(&two).unwrap().0;
+        s += 1;
+    }
+
+    'bases: for &base in BASES.iter() {
+        let mut x = try!(pow_mod(&big(base), &d, n, deadline));
+        if x == one || x == previous {
+            continue;
+        }
+
+        for _ in 1..s {
+            try!(deadline.check());
+            x = modulo(&(&x * &x), n);
+            if x == previous {
+                continue 'bases;
+            }
+        }
+
+        return Ok(Primality::Composite);
+    }
+
+    // 3317044064679887385961981 is the first pseudoprime to all of them
+    if n.digits() < 25 {
+        Ok(Primality::Prime)
+    }
+    else {
+        Ok(Primality::ProbablyPrime)
+    }
+}
+
+// Differences are multiplied together and only their product is checked
+// against n every BATCH steps, since a gcd costs much more than a product.
+fn rho(n: &BigInt, deadline: &Deadline) -> Result<BigInt, String> {
+    const BATCH: usize = 64;
+
+    if let Some(n) = n.to_u64() {
+        return rho_u64(n, deadline).map(big);
+    }
+
+    let one = BigInt::one();
+    for c in 1.. {
+        let c    = big(c);
+        let step = |x: &BigInt| modulo(&(&(x * x) + &c), n);
+        let (mut x, mut y) = (big(2), big(2));
+        loop {
+            try!(deadline.check());
+
+            let (saved_x, saved_y) = (x.clone(), y.clone());
+            let mut product = one.clone();
+            for _ in 0..BATCH {
+                x = step(&x);
+                y = step(&step(&y));
+                product = modulo(&(&product * &(&x - &y)), n);
+            }
+
+            let mut d = product.gcd(n);
+            if d == *n {
+                // the factors were all found in the same batch, so go over it again
+                x = saved_x;
+                y = saved_y;
+                d = one.clone();
+                while d == one {
+                    x = step(&x);
+                    y = step(&step(&y));
+                    d = (&x - &y).gcd(n);
+                }
+            }
+
+            if d != one {
+                if d != *n {
+                    return Ok(d);
+                }
+
+                break;
+            }
+        }
+    }
+
+    unreachable!()
+}
+
+// The prime factors of |n|, smallest first and repeated as needed.
+pub fn factor(n: &BigInt, deadline: &Deadline) -> Result<Vec<BigInt>, String> {
+    let mut factors = vec![];
+    let mut rest    = n.abs();
+    if rest.is_zero() {
+        return Ok(factors);
+    }
+
+    for &p in SMALL_PRIMES.iter() {
+        let p = big(p);
+        loop {
+            let (quotient, remainder) = rest.div_rem(&p).unwrap();
+            if !remainder.is_zero() {
+                break;
+            }
+
+            factors.push(p.clone());
+            rest = quotient;
+        }
+    }
+
+    let mut pending = if rest > BigInt::one() { vec![rest] } else { vec![] };
+    while let Some(n) = pending.pop() {
+        if try!(is_prime(&n, deadline)) == Primality::Composite {
+            let divisor = try!(rho(&n, deadline));
+            pending.push(n.div_rem(&divisor).unwrap().0);
+            pending.push(divisor);
+        }
+        else {
+            factors.push(n);
+        }
+    }
+
+    factors.sort();
+    Ok(factors)
+}
+
+// The smallest prime larger than n.
+pub fn next_prime(n: &BigInt, deadline: &Deadline) -> Result<BigInt, String> {
+    let mut candidate = if *n < big(2) { big(2) } else { n + &BigInt::one() };
+    while try!(is_prime(&candidate, deadline)) == Primality::Composite {
+        try!(deadline.check());
+        candidate = &candidate + &BigInt::one();
+    }
+
+    Ok(candidate)
+}
+
+pub fn lcm(a: &BigInt, b: &BigInt) -> BigInt {
+    if a.is_zero() || b.is_zero() {
+        return BigInt::zero();
+    }
+
+    (a * b).abs().div_rem(&a.gcd(b)).unwrap().0
+}
+
+// x such that a * x = 1 (mod m), by the extended Euclidean algorithm.
+pub fn mod_inverse(a: &BigInt, m: &BigInt) -> Option<BigInt> {
+    let (mut r, mut next_r) = (m.abs(), modulo(a, &m.abs()));
+    let (mut t, mut next_t) = (BigInt::zero(), BigInt::one());
+    while !next_r.is_zero() {
+        let quotient = r.div_rem(&next_r).unwrap().0;
+
+        let new_r = &r - &(&quotient * &next_r);
+        r      = next_r;
+        next_r = new_r;
+
+        let new_t = &t - &(&quotient * &next_t);
+        t      = next_t;
+        next_t = new_t;
+    }
+
+    if r == BigInt::one() {
+        Some(modulo(&t, &m.abs()))
+    }
+    else {
+        None
+    }
+}
+
+// Euler's phi, from the factorisation of n > 0.
+pub fn totient(n: &BigInt, deadline: &Deadline) -> Result<BigInt, String> {
+    let one        = BigInt::one();
+    let mut phi    = n.clone();
+    let mut primes = try!(factor(n, deadline));
+    primes.dedup();
+
+    for p in &primes {
+        phi = &phi.div_rem(p).unwrap().0 * &(p - &one);
+    }
+
+    Ok(phi)
+}
+
+// (a/p) for an odd prime p, by Euler's criterion.
+pub fn legendre(a: &BigInt, p: &BigInt, deadline: &Deadline) -> Result<i32, String> {
+    if p.is_even() || try!(is_prime(p, deadline)) == Primality::Composite {
+        return Err(format!("{} is not an odd prime", p));
+    }
+
+    let exponent = (p - &BigInt::one()).div_rem(&big(2)).unwrap().0;
+    let symbol   = try!(pow_mod(a, &exponent, p, deadline));
+    if symbol.is_zero() {
+        Ok(0)
+    }
+    else if symbol == BigInt::one() {
+        Ok(1)
+    }
+    else {
+        Ok(-1)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use plugins::calc::bigint::BigInt;
+    use super::{Deadline, Primality, is_prime, factor, next_prime, pow_mod, mod_inverse, lcm, totient, legendre};
+
+    fn big(n: &str) -> BigInt {
+        if n.starts_with('-') { -&BigInt::parse(&n[1..]).unwrap() } else { BigInt::parse(n).unwrap() }
+    }
+
+    fn factors(n: &str) -> Vec<String> {
+        factor(&big(n), &Deadline::after(5000)).unwrap().iter().map(|p| p.to_string()).collect()
+    }
+
+    #[test]
+    fn test_is_prime() {
+        let deadline = Deadline::after(5000);
+        let primality = |n: &str| is_prime(&big(n), &deadline).unwrap();
+
+        assert_eq!(Primality::Composite,     primality("1"));
+        assert_eq!(Primality::Prime,         primality("2"));
+        assert_eq!(Primality::Composite,     primality("561"));
+        assert_eq!(Primality::Composite,     primality("3215031751"));
+        assert_eq!(Primality::Prime,         primality("18446744073709551557"));
+        assert_eq!(Primality::Composite,     primality("18446744073709551617"));
+        assert_eq!(Primality::ProbablyPrime, primality("3317044064679887385961981"));
+        assert_eq!(Primality::Prime,         primality("100000000000000000039"));
+        assert_eq!(Primality::ProbablyPrime, primality("170141183460469231731687303715884105727"));
+    }
+
+    #[test]
+    fn test_factor() {
+        assert_eq!(vec!["2", "2", "2", "3", "3", "5"], factors("360"));
+        assert_eq!(vec!["3", "5", "17", "257", "65537"], factors("4294967295"));
+        assert_eq!(vec!["274177", "67280421310721"], factors("18446744073709551617"));
+        assert_eq!(vec!["1000000007", "1000000009", "1000000021"], factors("1000000037000000399000001323"));
+        assert!(factor(&big("1208925819614629174706189"), &Deadline::after(0)).is_err());
+    }
+
+    #[test]
+    fn test_modular() {
+        let deadline = Deadline::after(5000);
+
+        assert_eq!(big("101"), next_prime(&big("100"), &deadline).unwrap());
+        assert_eq!(big("2"),   next_prime(&big("-10"), &deadline).unwrap());
+        assert_eq!(big("445"), pow_mod(&big("4"), &big("13"), &big("497"), &deadline).unwrap());
+        assert_eq!(big("1"),   pow_mod(&big("2"), &big("1000000006"), &big("1000000007"), &deadline).unwrap());
+        assert_eq!(big("1"),   pow_mod(&big("3"), &big("18446744073709551556"), &big("18446744073709551557"), &deadline).unwrap());
+        assert_eq!(Some(big("5")),  mod_inverse(&big("3"), &big("7")));
+        assert_eq!(Some(big("2")),  mod_inverse(&big("-3"), &big("7")));
+        assert_eq!(None,            mod_inverse(&big("6"), &big("9")));
+        assert_eq!(big("36"),  lcm(&big("-12"), &big("18")));
+        assert_eq!(big("12"),  totient(&big("36"), &deadline).unwrap());
+        assert_eq!(big("1"),   totient(&big("1"), &deadline).unwrap());
+        assert_eq!(Ok(1),      legendre(&big("2"), &big("7"), &deadline));
+        assert_eq!(Ok(-1),     legendre(&big("3"), &big("7"), &deadline));
+        assert_eq!(Ok(0),      legendre(&big("14"), &big("7"), &deadline));
+        assert!(legendre(&big("2"), &big("9"), &deadline).is_err());
+    }
+}
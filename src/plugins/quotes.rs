@@ -0,0 +1,319 @@
+use std::io;
+use std::collections::HashMap;
+use std::sync::Mutex;
+use irc::client::prelude::*;
+use regex::Regex;
+use redis::{Commands, RedisResult};
+use time;
+use plugin::{self, Plugin};
+use store::Store;
+
+lazy_static! {
+    static ref ADD:    Regex        = Regex::new(r"^!addquote\s+(\S.*?)\s*$").unwrap();
+    static ref QUOTE:  Regex        = Regex::new(r"^!quote(?:\s+(\S.*?))?\s*$").unwrap();
+    static ref DELETE: Regex        = Regex::new(r"^!delquote\s+#?(\d+)\s*$").unwrap();
+    static ref RANDOM: Regex        = Regex::new(r"^!randquote(?:\s+(\S+))?\s*$").unwrap();
+    static ref STORE:  Mutex<Store> = Mutex::new(Store::open("quotes").unwrap());
+}
+
+// the last id given out, the ids in use, and a "quote:<id>" hash for each of them
+const LAST_ID: &'static str = "last_id";
+const IDS:     &'static str = "ids";
+
+#[derive(PartialEq, Debug, Clone)]
+struct Quote {
+    id:      u64,
+    text:    String,
+    author:  String,
+    channel: String,
+    time:    i64
+}
+
+fn words(text: &str) -> Vec<String> {
+    text.split(|c: char| !c.is_alphanumeric())
+        .filter(|word| !word.is_empty())
+        .map(|word| word.to_lowercase())
+        .collect()
+}
+
+impl Quote {
+    fn from_fields(id: u64, fields: &HashMap<String, String>) -> Option<Quote> {
+        fields.get("text").map(|text| Quote {
+            id:      id,
+            text:    text.clone(),
+            author:  fields.get("author").cloned().unwrap_or(String::new()),
+            channel: fields.get("channel").cloned().unwrap_or(String::new()),
+            time:    fields.get("time").and_then(|time| time.parse().ok()).unwrap_or(0)
+        })
+    }
+
+    fn fields(&self) -> Vec<(&'static str, String)> {
+        vec![("text",    self.text.clone()),
+             ("author",  self.author.clone()),
+             ("channel", self.channel.clone()),
+             ("time",    self.time.to_string())]
+    }
+
+    // Whole words count twice as much as words found inside other ones, and
+    // quotes missing any of the terms don't match at all.
+    fn score(&self, terms: &[String]) -> usize {
+        let words = words(&self.text);
+
+        terms.iter().fold(Some(0), |score, term| score.and_then(|score| {
+            if words.iter().any(|word| word == term) {
+                Some(score + 2)
+            }
+            else if words.iter().any(|word| word.contains(&**term)) {
+                Some(score + 1)
+            }
+            else {
+                None
+            }
+        })).unwrap_or(0)
+    }
+
+    // "<holo> hi" and "* holo waves" both feature holo.
+    fn features(&self, nickname: &str) -> bool {
+        let nickname = plugin::nick_key(nickname);
+        self.text.split(|c: char| c.is_whitespace() || "<>:,@+*".contains(c))
+            .any(|word| plugin::nick_key(word) == nickname)
+    }
+
+    fn describe(&self, now: i64) -> String {
+        format!("#{}: {} (added by {} in {}, {})",
+                self.id, self.text, self.author, self.channel, plugin::time_ago(now - self.time))
+    }
+}
+
+// The best matches first, older quotes first among equally good ones (quotes come sorted by id).
+fn search<'a>(quotes: &'a [Quote], query: &str) -> Vec<&'a Quote> {
+    let terms = words(query);
+    if terms.is_empty() {
+        return vec![];
+    }
+
+    let mut found: Vec<(usize, &Quote)> = quotes.iter()
+        .map(|quote| (quote.score(&terms), quote))
+        .filter(|&(score, _)| score > 0)
+        .collect();
+
+    found.sort_by(|&(a, _), &(b, _)| b.cmp(&a));
+    found.into_iter().map(|(_, quote)| quote).collect()
+}
+
+fn quote_key(store: &Store, id: u64) -> String {
+    store.key(&format!("quote:{}", id))
+}
+
+fn get(store: &Store, id: u64) -> Option<Quote> {
+    let fields: HashMap<String, String> = store.hgetall(quote_key(store, id)).unwrap_or(HashMap::new());
+    Quote::from_fields(id, &fields)
+}
+
+fn all(store: &Store) -> Vec<Quote> {
+    let mut ids: Vec<u64> = store.smembers(store.key(IDS)).unwrap_or(vec![]);
+    ids.sort();
+
+    ids.into_iter().filter_map(|id| get(store, id)).collect()
+}
+
+register_plugin!(Quotes);
+
+impl Quotes {
+    fn add(&self, server: &IrcServer, message: &Message, target: &str, text: &str) -> io::Result<()> {
+        let nickname = match message.source_nickname() {
+            Some(nickname) => nickname,
+            None           => { return Ok(()); }
+        };
+
+        let store = STORE.lock().unwrap();
+
+        let id: u64 = match store.incr(store.key(LAST_ID), 1) {
+            Ok(id) => id,
+            Err(e) => { return server.send_privmsg(target, &*format!("Something bad happened: {:?}", e)); }
+        };
+
+        let quote = Quote {
+            id:      id,
+            text:    text.to_owned(),
+            author:  nickname.to_owned(),
+            channel: target.to_owned(),
+            time:    time::get_time().sec
+        };
+
+        let saved: RedisResult<()> = store.hset_multiple(quote_key(&store, id), &quote.fields())
+            .and_then(|_: ()| store.sadd(store.key(IDS), id));
+
+        match saved {
+            Ok(()) => server.send_privmsg(target, &*format!("Quote #{} added", id)),
+            Err(e) => server.send_privmsg(target, &*format!("Something bad happened: {:?}", e))
+        }
+    }
+
+    // `!quote 12` shows the quote #12, `!quote some words` searches for them,
+    // and `!quote` alone picks one at random.
+    fn quote(&self, server: &IrcServer, target: &str, query: Option<&str>) -> io::Result<()> {
+        let store = STORE.lock().unwrap();
+        let now   = time::get_time().sec;
+
+        let query = match query {
+            Some(query) => query,
+            None        => {
+                let id: Option<u64> = store.srandmember(store.key(IDS)).unwrap_or(None);
+                return match id.and_then(|id| get(&store, id)) {
+                    Some(quote) => server.send_privmsg(target, &quote.describe(now)),
+                    None        => server.send_privmsg(target, "There are no quotes yet")
+                };
+            }
+        };
+
+        if let Ok(id) = query.trim_left_matches('#').parse::<u64>() {
+            return match get(&store, id) {
+                Some(quote) => server.send_privmsg(target, &quote.describe(now)),
+                None        => server.send_privmsg(target, &*format!("There is no quote #{}", id))
+            };
+        }
+
+        let quotes = all(&store);
+        let found  = search(&quotes, query);
+        match found.split_first() {
+            Some((best, others)) if others.is_empty() => server.send_privmsg(target, &best.describe(now)),
+            Some((best, others)) => {
+                let others: Vec<String> = others.iter().take(5).map(|quote| format!("#{}", quote.id)).collect();
+                server.send_privmsg(target, &*format!("{} (also {})", best.describe(now), others.join(", ")))
+            },
+            None => server.send_privmsg(target, &*format!("No quotes about {}", query))
+        }
+    }
+
+    fn random(&self, server: &IrcServer, target: &str, nickname: Option<&str>) -> io::Result<()> {
+        let nickname = match nickname {
+            Some(nickname) => nickname,
+            None           => { return self.quote(server, target, None); }
+        };
+
+        let store  = STORE.lock().unwrap();
+        let quotes = all(&store);
+
+        let featuring: Vec<&Quote> = quotes.iter().filter(|quote| quote.features(nickname)).collect();
+        if featuring.is_empty() {
+            return server.send_privmsg(target, &*format!("There are no quotes of {}", nickname));
+        }
+
+        let pick = (time::precise_time_ns() % featuring.len() as u64) as usize;
+        server.send_privmsg(target, &featuring[pick].describe(time::get_time().sec))
+    }
+
+    fn delete(&self, server: &IrcServer, message: &Message, target: &str, id: u64) -> io::Result<()> {
+        if !plugin::is_admin(server, message) {
+            return server.send_privmsg(target, "Only admins can delete quotes");
+        }
+
+        let store = STORE.lock().unwrap();
+        if get(&store, id).is_none() {
+            return server.send_privmsg(target, &*format!("There is no quote #{}", id));
+        }
+
+        let deleted: RedisResult<()> = store.srem(store.key(IDS), id)
+            .and_then(|_: ()| store.del(quote_key(&store, id)));
+
+        match deleted {
+            Ok(()) => server.send_privmsg(target, &*format!("Quote #{} deleted", id)),
+            Err(e) => server.send_privmsg(target, &*format!("Something bad happened: {:?}", e))
+        }
+    }
+}
+
+impl Plugin for Quotes {
+    fn is_allowed(&self, _: &IrcServer, message: &Message) -> bool {
+        match message.command {
+            Command::PRIVMSG(_, ref msg) => ADD.is_match(msg) || QUOTE.is_match(msg) || DELETE.is_match(msg) || RANDOM.is_match(msg),
+            _ => false
+        }
+    }
+
+    fn execute(&mut self, server: &IrcServer, message: &Message) -> io::Result<()> {
+        match message.command {
+            Command::PRIVMSG(ref target, ref msg) => {
+                if let Some(captures) = ADD.captures(msg) {
+                    self.add(server, message, target, captures.at(1).unwrap())
+                }
+                else if let Some(captures) = QUOTE.captures(msg) {
+                    self.quote(server, target, captures.at(1))
+                }
+                else if let Some(captures) = DELETE.captures(msg) {
+                    match captures.at(1).unwrap().parse() {
+                        Ok(id) => self.delete(server, message, target, id),
+                        Err(_) => Ok(())
+                    }
+                }
+                else if let Some(captures) = RANDOM.captures(msg) {
+                    self.random(server, target, captures.at(1))
+                }
+                else {
+                    Ok(())
+                }
+            },
+            _ => Ok(())
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use ::tests::{make_server, get_server_value};
+
+    use irc::client::prelude::*;
+
+    use plugin::Plugin;
+    use super::{Quotes, Quote, search};
+
+    fn quote(id: u64, text: &str) -> Quote {
+        Quote { id: id, text: text.to_owned(), author: "gauss".to_owned(), channel: "#test".to_owned(), time: 0 }
+    }
+
+    #[test]
+    fn test_search() {
+        let quotes = vec![quote(1, "<holo> pineapples are sweet"),
+                          quote(2, "<Lawrence> apples, apples everywhere"),
+                          quote(3, "<holo> I am the wise wolf of Yoitsu"),
+                          quote(4, "<holo> wheat is better than apples")];
+
+        let ids = |query: &str| search(&quotes, query).iter().map(|quote| quote.id).collect::<Vec<u64>>();
+        assert_eq!(vec![2, 4, 1], ids("apples"));
+        assert_eq!(vec![4, 1],    ids("Holo apples"));
+        assert_eq!(vec![3],       ids("WOLF"));
+        assert!(ids("dog").is_empty());
+        assert!(ids("...").is_empty());
+
+        assert!(quotes[1].features("lawrence"));
+        assert!(!quotes[1].features("holo"));
+        assert_eq!("#3: <holo> I am the wise wolf of Yoitsu (added by gauss in #test, 2 days ago)",
+                   quotes[2].describe(2 * 86400));
+    }
+
+    #[test]
+    fn test_delquote_needs_admin() {
+        let     server = make_server("PRIVMSG #test :!delquote #3\r\n");
+        let mut plugin = Quotes::new();
+
+        for message in server.iter() {
+            let message = message.unwrap();
+            assert!(plugin.is_allowed(&server, &message));
+            assert!(plugin.execute(&server, &message).is_ok());
+        }
+
+        assert_eq!("PRIVMSG #test :Only admins can delete quotes\r\n", &*get_server_value(&server));
+    }
+
+    #[test]
+    fn test_not_allowed() {
+        let server = make_server("PRIVMSG #test :!quotes\r\nPRIVMSG #test :!delquote holo\r\n");
+        let plugin = Quotes::new();
+
+        for message in server.iter() {
+            let message = message.unwrap();
+            assert!(!plugin.is_allowed(&server, &message));
+        }
+    }
+}
@@ -0,0 +1,421 @@
+use std::io;
+use std::collections::{HashMap, HashSet};
+use std::sync::Mutex;
+use std::thread::{sleep, spawn};
+use std::time::Duration;
+use irc::client::prelude::*;
+use regex::Regex;
+use redis::{Commands, RedisResult};
+use time;
+use plugin::{self, Plugin};
+use store::Store;
+
+lazy_static! {
+    static ref REMIND:   Regex        = Regex::new(r"^!remind\s+(\S+)\s+(.+?)\s+to\s+(.+?)\s*$").unwrap();
+    static ref LIST:     Regex        = Regex::new(r"^!reminders\s*$").unwrap();
+    static ref CANCEL:   Regex        = Regex::new(r"^!unremind\s+#?(\d+)\s*$").unwrap();
+    static ref TIMEZONE: Regex        = Regex::new(r"^!timezone(?:\s+(\S.*?))?\s*$").unwrap();
+    static ref STORE:    Mutex<Store> = Mutex::new(Store::open("reminders").unwrap());
+    static ref PRESENCE: Mutex<Presence> = Mutex::new(Presence::default());
+}
+
+mod when;
+mod zone;
+
+use self::zone::Zone;
+
+// The last id given out, the pending ids sorted by when they are due, a
+// "reminder:<id>" hash for each of them, and nick -> time zone, either a name
+// like Europe/Rome or an offset from UTC in seconds.
+const LAST_ID:   &'static str = "last_id";
+const DUE:       &'static str = "due";
+const TIMEZONES: &'static str = "timezones";
+
+#[derive(PartialEq, Debug, Clone)]
+struct Reminder {
+    id:     u64,
+    nick:   String,
+    by:     String,
+    target: String,
+    text:   String,
+    at:     i64
+}
+
+impl Reminder {
+    fn from_fields(id: u64, fields: &HashMap<String, String>) -> Option<Reminder> {
+        match (fields.get("nick"), fields.get("target"), fields.get("text")) {
+            (Some(nick), Some(target), Some(text)) => Some(Reminder {
+                id:     id,
+                nick:   nick.clone(),
+                by:     fields.get("by").cloned().unwrap_or(nick.clone()),
+                target: target.clone(),
+                text:   text.clone(),
+                at:     fields.get("at").and_then(|at| at.parse().ok()).unwrap_or(0)
+            }),
+            _ => None
+        }
+    }
+
+    fn fields(&self) -> Vec<(&'static str, String)> {
+        vec![("nick",   self.nick.clone()),
+             ("by",     self.by.clone()),
+             ("target", self.target.clone()),
+             ("text",   self.text.clone()),
+             ("at",     self.at.to_string())]
+    }
+
+    fn concerns(&self, nickname: &str) -> bool {
+        plugin::nick_key(&self.nick) == plugin::nick_key(nickname) || plugin::nick_key(&self.by) == plugin::nick_key(nickname)
+    }
+
+    fn message(&self) -> String {
+        if plugin::nick_key(&self.nick) == plugin::nick_key(&self.by) {
+            format!("{}, you asked me to remind you to {}", self.nick, self.text)
+        }
+        else {
+            format!("{}, {} asked me to remind you to {}", self.nick, self.by, self.text)
+        }
+    }
+}
+
+// Whether Gauss is done registering, and the channels it is in. Nothing can
+// be delivered before the end of the MOTD, and nothing in a channel before
+// joining it.
+#[derive(Debug, Default)]
+struct Presence {
+    registered: bool,
+    channels:   HashSet<String>
+}
+
+impl Presence {
+    fn can_reach(&self, target: &str) -> bool {
+        if is_channel(target) {
+            self.channels.contains(&target.to_lowercase())
+        }
+        else {
+            self.registered
+        }
+    }
+}
+
+fn reminder_key(store: &Store, id: u64) -> String {
+    store.key(&format!("reminder:{}", id))
+}
+
+fn get(store: &Store, id: u64) -> Option<Reminder> {
+    let fields: HashMap<String, String> = store.hgetall(reminder_key(store, id)).unwrap_or(HashMap::new());
+    Reminder::from_fields(id, &fields)
+}
+
+fn remove(store: &Store, id: u64) -> RedisResult<()> {
+    store.zrem(store.key(DUE), id).and_then(|_: ()| store.del(reminder_key(store, id)))
+}
+
+// Reminders in a channel are delivered there, the other ones by private message.
+// Whatever was due while Gauss was away is delivered as soon as it is back in
+// the channel, and stays pending until then.
+fn deliver(server: &IrcServer, now: i64) {
+    let store    = STORE.lock().unwrap();
+    let presence = PRESENCE.lock().unwrap();
+
+    let due: Vec<u64> = store.zrangebyscore(store.key(DUE), "-inf", now).unwrap_or(vec![]);
+    for id in due {
+        if let Some(reminder) = get(&store, id) {
+            if !presence.can_reach(&reminder.target) {
+                continue;
+            }
+
+            if let Err(e) = server.send_privmsg(&reminder.target, &reminder.message()) {
+                println!("Reminders: cannot deliver #{} ({:?})", id, e);
+                continue;
+            }
+        }
+
+        if let Err(e) = remove(&store, id) {
+            println!("Reminders: cannot remove #{} ({:?})", id, e);
+        }
+    }
+}
+
+fn is_channel(target: &str) -> bool {
+    target.starts_with('#') || target.starts_with('&')
+}
+
+// Answers to private messages go back to whoever sent them.
+fn reply_to<'a>(target: &'a str, nickname: &'a str) -> &'a str {
+    if is_channel(target) { target } else { nickname }
+}
+
+register_plugin!(Reminders);
+
+impl Reminders {
+    // `reminders.timezone` is used for whoever didn't say their own with !timezone.
+    fn zone(&self, server: &IrcServer, store: &Store, nickname: &str) -> Zone {
+        let zone: Option<String> = store.hget(store.key(TIMEZONES), plugin::nick_key(nickname)).unwrap_or(None);
+        zone.and_then(|zone| Zone::from_stored(&zone))
+            .or(plugin::get_option(server, "reminders.timezone").and_then(Zone::parse))
+            .unwrap_or(Zone::Fixed(0))
+    }
+
+    fn remind(&self, server: &IrcServer, message: &Message, target: &str, who: &str, at: &str, text: &str) -> io::Result<()> {
+        let nickname = match message.source_nickname() {
+            Some(nickname) => nickname,
+            None           => { return Ok(()); }
+        };
+
+        let reply = reply_to(target, nickname);
+        let zone  = self.zone(server, &STORE.lock().unwrap(), nickname);
+        let at    = match when::parse(at, time::get_time().sec, &zone) {
+            Ok(at) => at,
+            Err(e) => { return server.send_privmsg(reply, &e); }
+        };
+
+        let store = STORE.lock().unwrap();
+
+        let nick = if who == "me" { nickname } else { who };
+        let id: u64 = match store.incr(store.key(LAST_ID), 1) {
+            Ok(id) => id,
+            Err(e) => { return server.send_privmsg(reply, &*format!("Something bad happened: {:?}", e)); }
+        };
+
+        let reminder = Reminder {
+            id:     id,
+            nick:   nick.to_owned(),
+            by:     nickname.to_owned(),
+            target: if is_channel(target) { target } else { nick }.to_owned(),
+            text:   text.to_owned(),
+            at:     at
+        };
+
+        let saved: RedisResult<()> = store.hset_multiple(reminder_key(&store, id), &reminder.fields())
+            .and_then(|_: ()| store.zadd(store.key(DUE), id, at));
+
+        match saved {
+            Ok(()) => server.send_privmsg(reply, &*format!("Okay, I'll remind {} on {} (#{})",
+                                                           if who == "me" { "you" } else { nick },
+                                                           when::format(at, &zone), id)),
+            Err(e) => server.send_privmsg(reply, &*format!("Something bad happened: {:?}", e))
+        }
+    }
+
+    fn list(&self, server: &IrcServer, message: &Message, target: &str) -> io::Result<()> {
+        let nickname = match message.source_nickname() {
+            Some(nickname) => nickname,
+            None           => { return Ok(()); }
+        };
+
+        let reply = reply_to(target, nickname);
+        let store = STORE.lock().unwrap();
+        let zone  = self.zone(server, &store, nickname);
+
+        let ids: Vec<u64> = store.zrange(store.key(DUE), 0, -1).unwrap_or(vec![]);
+        let reminders: Vec<String> = ids.into_iter()
+            .filter_map(|id| get(&store, id))
+            .filter(|reminder| reminder.concerns(nickname))
+            .take(5)
+            .map(|reminder| format!("#{} on {}: {}", reminder.id, when::format(reminder.at, &zone), reminder.text))
+            .collect();
+
+        if reminders.is_empty() {
+            server.send_privmsg(reply, &*format!("There are no reminders for {}", nickname))
+        }
+        else {
+            server.send_privmsg(reply, &reminders.join(", "))
+        }
+    }
+
+    // Whoever asked for a reminder, and whoever is going to get it, may cancel it.
+    fn cancel(&self, server: &IrcServer, message: &Message, target: &str, id: u64) -> io::Result<()> {
+        let nickname = match message.source_nickname() {
+            Some(nickname) => nickname,
+            None           => { return Ok(()); }
+        };
+
+        let reply = reply_to(target, nickname);
+        let store = STORE.lock().unwrap();
+        match get(&store, id) {
+            Some(ref reminder) if reminder.concerns(nickname) || plugin::is_admin(server, message) => {
+                match remove(&store, id) {
+                    Ok(()) => server.send_privmsg(reply, &*format!("Reminder #{} cancelled", id)),
+                    Err(e) => server.send_privmsg(reply, &*format!("Something bad happened: {:?}", e))
+                }
+            },
+            Some(_) => server.send_privmsg(reply, &*format!("Reminder #{} is not yours", id)),
+            None    => server.send_privmsg(reply, &*format!("There is no reminder #{}", id))
+        }
+    }
+
+    fn timezone(&self, server: &IrcServer, message: &Message, target: &str, zone: Option<&str>) -> io::Result<()> {
+        let nickname = match message.source_nickname() {
+            Some(nickname) => nickname,
+            None           => { return Ok(()); }
+        };
+
+        let reply = reply_to(target, nickname);
+        let store = STORE.lock().unwrap();
+        let zone  = match zone {
+            Some(zone) => match Zone::parse(zone) {
+                Some(zone) => zone,
+                None       => { return server.send_privmsg(reply, "Time zones look like Europe/Rome, America/New_York or UTC+2"); }
+            },
+            None => {
+                let zone = self.zone(server, &store, nickname);
+                return server.send_privmsg(reply, &*format!("It is {} for {}", when::format(time::get_time().sec, &zone), nickname));
+            }
+        };
+
+        let saved: RedisResult<()> = store.hset(store.key(TIMEZONES), plugin::nick_key(nickname), zone.stored());
+        match saved {
+            Ok(()) => server.send_privmsg(reply, &*format!("Okay, it is {} for {}", when::format(time::get_time().sec, &zone), nickname)),
+            Err(e) => server.send_privmsg(reply, &*format!("Something bad happened: {:?}", e))
+        }
+    }
+
+    // Keeps track of where Gauss is, so that reminders are only sent where
+    // they can arrive.
+    fn moved(&self, server: &IrcServer, message: &Message) -> io::Result<()> {
+        let is_me        = |nickname: &str| plugin::nick_key(nickname) == plugin::nick_key(server.current_nickname());
+        let from_me      = message.source_nickname().map_or(false, |nickname| is_me(nickname));
+        let mut presence = PRESENCE.lock().unwrap();
+
+        match message.command {
+            Command::Response(Response::RPL_ENDOFMOTD, _, _) |
+            Command::Response(Response::ERR_NOMOTD, _, _) => {
+                presence.registered = true;
+            },
+            Command::JOIN(ref channels, _, _) if from_me => {
+                presence.channels.extend(channels.split(',').map(|channel| channel.to_lowercase()));
+            },
+            Command::PART(ref channels, _) if from_me => {
+                for channel in channels.split(',') {
+                    presence.channels.remove(&channel.to_lowercase());
+                }
+            },
+            Command::KICK(ref channels, ref users, _) if users.split(',').any(|user| is_me(user)) => {
+                for channel in channels.split(',') {
+                    presence.channels.remove(&channel.to_lowercase());
+                }
+            },
+            _ => {}
+        }
+
+        Ok(())
+    }
+}
+
+impl Plugin for Reminders {
+    // Due reminders are looked for every `reminders.interval` seconds.
+    fn configure(&mut self, server: &IrcServer) {
+        let interval = plugin::get_option(server, "reminders.interval")
+            .and_then(|interval| interval.parse().ok())
+            .unwrap_or(10);
+        let server   = server.clone();
+
+        spawn(move || loop {
+            deliver(&server, time::get_time().sec);
+            sleep(Duration::from_secs(interval));
+        });
+    }
+
+    fn is_allowed(&self, _: &IrcServer, message: &Message) -> bool {
+        match message.command {
+            Command::PRIVMSG(_, ref msg) => REMIND.is_match(msg) || LIST.is_match(msg) || CANCEL.is_match(msg) || TIMEZONE.is_match(msg),
+            Command::Response(Response::RPL_ENDOFMOTD, _, _) |
+            Command::Response(Response::ERR_NOMOTD, _, _) |
+            Command::JOIN(_, _, _) | Command::PART(_, _) | Command::KICK(_, _, _) => true,
+            _ => false
+        }
+    }
+
+    fn execute(&mut self, server: &IrcServer, message: &Message) -> io::Result<()> {
+        match message.command {
+            Command::PRIVMSG(ref target, ref msg) => {
+                if let Some(captures) = REMIND.captures(msg) {
+                    self.remind(server, message, target, captures.at(1).unwrap(), captures.at(2).unwrap(), captures.at(3).unwrap())
+                }
+                else if LIST.is_match(msg) {
+                    self.list(server, message, target)
+                }
+                else if let Some(captures) = CANCEL.captures(msg) {
+                    match captures.at(1).unwrap().parse() {
+                        Ok(id) => self.cancel(server, message, target, id),
+                        Err(_) => Ok(())
+                    }
+                }
+                else if let Some(captures) = TIMEZONE.captures(msg) {
+                    self.timezone(server, message, target, captures.at(1))
+                }
+                else {
+                    Ok(())
+                }
+            },
+            _ => self.moved(server, message)
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::HashMap;
+    use ::tests::make_server;
+
+    use irc::client::prelude::*;
+
+    use plugin::Plugin;
+    use super::{Reminders, Reminder, Presence, REMIND, PRESENCE};
+
+    #[test]
+    fn test_allowed() {
+        let server = make_server("PRIVMSG #test :!remind me in 2h30m to check the oven\r\nPRIVMSG #test :!reminders\r\n\
+                                  PRIVMSG #test :!unremind #3\r\nPRIVMSG #test :!timezone UTC+2\r\n");
+        let plugin = Reminders::new();
+
+        for message in server.iter() {
+            let message = message.unwrap();
+            assert!(plugin.is_allowed(&server, &message));
+        }
+
+        let captures = REMIND.captures("!remind holo on friday at 9am to go to the market").unwrap();
+        assert_eq!((Some("holo"), Some("on friday at 9am"), Some("go to the market")),
+                   (captures.at(1), captures.at(2), captures.at(3)));
+    }
+
+    #[test]
+    fn test_message() {
+        let mut fields = HashMap::new();
+        fields.insert("nick".to_owned(),   "Holo".to_owned());
+        fields.insert("target".to_owned(), "#test".to_owned());
+        fields.insert("text".to_owned(),   "buy apples".to_owned());
+        fields.insert("at".to_owned(),     "1477044000".to_owned());
+
+        let mut reminder = Reminder::from_fields(1, &fields).unwrap();
+        assert_eq!("Holo, you asked me to remind you to buy apples", reminder.message());
+
+        reminder.by = "Lawrence".to_owned();
+        assert_eq!("Holo, Lawrence asked me to remind you to buy apples", reminder.message());
+        assert!(reminder.concerns("lawrence"));
+        assert!(!reminder.concerns("Gauss"));
+        assert_eq!(Some(reminder.clone()), Reminder::from_fields(1, &reminder.fields().into_iter().map(|(k, v)| (k.to_owned(), v)).collect()));
+    }
+
+    #[test]
+    fn test_presence() {
+        assert!(!Presence::default().can_reach("#test"));
+        assert!(!Presence::default().can_reach("Holo"));
+
+        let     server = make_server(":Gauss!gauss@example.com JOIN #Test\r\n:Holo!holo@example.com JOIN #other\r\n\
+                                      :irc.test.net 376 Gauss :End of /MOTD command.\r\n");
+        let mut plugin = Reminders::new();
+
+        for message in server.iter() {
+            let message = message.unwrap();
+            assert!(plugin.is_allowed(&server, &message));
+            assert!(plugin.execute(&server, &message).is_ok());
+        }
+
+        let presence = PRESENCE.lock().unwrap();
+        assert!(presence.can_reach("#test"));
+        assert!(!presence.can_reach("#other"));
+        assert!(presence.can_reach("Holo"));
+    }
+}
@@ -0,0 +1,223 @@
+use regex::Regex;
+use time::{self, Timespec};
+use super::zone::Zone;
+
+lazy_static! {
+    static ref IN:     Regex = Regex::new(r"^in\s+((?:\d+\s*[a-z]+(?:\s*,\s*|\s+and\s+|\s*))+)$").unwrap();
+    static ref AMOUNT: Regex = Regex::new(r"(\d+)\s*([a-z]+)").unwrap();
+    static ref AT:     Regex = Regex::new(r"^(?:on\s+|at\s+)?([a-z]+|\d{4}-\d{2}-\d{2})?\s*(?:at\s+)?(\d{1,2}(?::\d{2})?\s*(?:am|pm)?)?$").unwrap();
+    static ref HOUR:   Regex = Regex::new(r"^(\d{1,2})(?::(\d{2}))?\s*(am|pm)?$").unwrap();
+}
+
+const DAY: i64 = 86400;
+
+const WEEKDAYS: &'static [&'static str] = &["monday", "tuesday", "wednesday", "thursday", "friday", "saturday", "sunday"];
+
+// when only the day is given
+const DEFAULT_HOUR: i64 = 9 * 3600;
+
+// about ten years
+const MAX_DISTANCE: i64 = 3653 * DAY;
+
+fn modulo(a: i64, b: i64) -> i64 {
+    ((a % b) + b) % b
+}
+
+fn unit(name: &str) -> Option<i64> {
+    match name {
+        "s" | "sec" | "secs" | "second" | "seconds" => Some(1),
+        "m" | "min" | "mins" | "minute" | "minutes" => Some(60),
+        "h" | "hr"  | "hrs"  | "hour"   | "hours"   => Some(3600),
+        "d" | "day" | "days"                        => Some(DAY),
+        "w" | "week" | "weeks"                      => Some(7 * DAY),
+        _                                           => None
+    }
+}
+
+// "2h30m", "1 day and 2 hours", "90 minutes", with i64::max_value() for
+// anything too long to count.
+fn duration(amounts: &str) -> Option<i64> {
+    AMOUNT.captures_iter(amounts).fold(Some(0), |total, captures| {
+        let amount = captures.at(1).unwrap().parse::<i64>().unwrap_or(i64::max_value());
+        match (total, unit(captures.at(2).unwrap())) {
+            (Some(total), Some(unit)) => Some(amount.checked_mul(unit)
+                                                    .and_then(|seconds| total.checked_add(seconds))
+                                                    .unwrap_or(i64::max_value())),
+            _                         => None
+        }
+    })
+}
+
+// Seconds since midnight, from "18:00", "9am" or "9:30pm".
+fn time_of_day(hour: &str) -> Option<i64> {
+    let captures = match HOUR.captures(hour) {
+        Some(captures) => captures,
+        None           => { return None; }
+    };
+
+    let hours   = captures.at(1).unwrap().parse::<i64>().unwrap();
+    let minutes = captures.at(2).map(|minutes| minutes.parse::<i64>().unwrap()).unwrap_or(0);
+    let hours   = match captures.at(3) {
+        Some(_) if hours < 1 || hours > 12 => { return None; },
+        Some("am")                         => hours % 12,
+        Some(_)                            => hours % 12 + 12,
+        None                               => hours
+    };
+
+    if hours < 24 && minutes < 60 {
+        Some(hours * 3600 + minutes * 60)
+    }
+    else {
+        None
+    }
+}
+
+fn weekday(name: &str) -> Option<i64> {
+    if name.len() < 3 {
+        return None;
+    }
+
+    WEEKDAYS.iter().position(|weekday| weekday.starts_with(name)).map(|weekday| weekday as i64)
+}
+
+// Times are read on the clocks of the user's time zone and turned into a UTC
+// timestamp. Days without a time mean 9am, times without a day mean the next
+// time the clock shows them.
+pub fn parse(when: &str, now: i64, zone: &Zone) -> Result<i64, String> {
+    let when = when.trim().to_lowercase();
+
+    if let Some(captures) = IN.captures(&when) {
+        return match duration(captures.at(1).unwrap()) {
+            Some(seconds) if seconds > MAX_DISTANCE => Err("That is too far away".to_owned()),
+            Some(seconds) if seconds > 0            => Ok(now + seconds),
+            _                                       => Err(format!("I don't understand \"{}\"", when))
+        };
+    }
+
+    let captures = match AT.captures(&when) {
+        Some(captures) => captures,
+        None           => { return Err(format!("I don't understand \"{}\"", when)); }
+    };
+
+    if captures.at(1).is_none() && captures.at(2).is_none() {
+        return Err(format!("I don't understand \"{}\"", when));
+    }
+
+    let hour = match captures.at(2) {
+        Some(hour) => match time_of_day(hour) {
+            Some(hour) => Some(hour),
+            None       => { return Err(format!("{} is not a time of day", hour)); }
+        },
+        None => None
+    };
+
+    let local = now + zone.offset(now);
+    let today = local - modulo(local, DAY);
+    let local = match captures.at(1) {
+        None             => {
+            let at = today + hour.unwrap_or(DEFAULT_HOUR);
+            if at > local { at } else { at + DAY }
+        },
+        Some("today")    => today + hour.unwrap_or(DEFAULT_HOUR),
+        Some("tomorrow") => today + DAY + hour.unwrap_or(DEFAULT_HOUR),
+        Some(day)        => match (weekday(day), time::strptime(day, "%Y-%m-%d")) {
+            (Some(weekday), _) => {
+                // 1970-01-01 was a thursday
+                let days = modulo(weekday - modulo(today / DAY + 3, 7), 7);
+                let at   = today + days * DAY + hour.unwrap_or(DEFAULT_HOUR);
+                if at > local { at } else { at + 7 * DAY }
+            },
+            (None, Ok(date)) => date.to_timespec().sec + hour.unwrap_or(DEFAULT_HOUR),
+            (None, Err(_))   => { return Err(format!("I don't understand \"{}\"", when)); }
+        }
+    };
+
+    let at = zone.to_utc(local);
+    if at <= now {
+        return Err("That is already in the past".to_owned());
+    }
+
+    if at - now > MAX_DISTANCE {
+        return Err("That is too far away".to_owned());
+    }
+
+    Ok(at)
+}
+
+// "Fri 21 Oct 18:00 (UTC+02:00)"
+pub fn format(at: i64, zone: &Zone) -> String {
+    let offset = zone.offset(at);
+    let local  = time::at_utc(Timespec::new(at + offset, 0));
+    let sign   = if offset < 0 { '-' } else { '+' };
+
+    format!("{} (UTC{}{:02}:{:02})",
+            local.strftime("%a %d %b %H:%M").map(|local| local.to_string()).unwrap_or(String::new()),
+            sign, offset.abs() / 3600, offset.abs() % 3600 / 60)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{parse, format};
+    use super::super::zone::Zone;
+
+    // Fri, 21 Oct 2016 10:00:00 UTC
+    const NOW: i64 = 1477044000;
+
+    const UTC: &'static Zone = &Zone::Fixed(0);
+
+    #[test]
+    fn test_in() {
+        assert_eq!(Ok(NOW + 2 * 3600 + 30 * 60), parse("in 2h30m", NOW, UTC));
+        assert_eq!(Ok(NOW + 86400 + 2 * 3600),   parse("in 1 day and 2 hours", NOW, &Zone::Fixed(3600)));
+        assert_eq!(Ok(NOW + 90 * 60),            parse("In 90 minutes", NOW, UTC));
+        assert!(parse("in 2 fortnights", NOW, UTC).is_err());
+        assert!(parse("in a while", NOW, UTC).is_err());
+    }
+
+    #[test]
+    fn test_too_far() {
+        let too_far = Err("That is too far away".to_owned());
+        assert_eq!(too_far, parse("in 9999999999999999 weeks", NOW, UTC));
+        assert_eq!(too_far, parse("in 99999999999999999999 seconds", NOW, UTC));
+        assert_eq!(too_far, parse("in 1000000000000000000s and 9000000000000000000s", NOW, UTC));
+        assert_eq!(too_far, parse("on 9999-12-31", NOW, UTC));
+        assert_eq!(Ok(NOW + 520 * 7 * 86400), parse("in 520 weeks", NOW, UTC));
+    }
+
+    #[test]
+    fn test_at() {
+        assert_eq!(Ok(NOW + 8 * 3600),           parse("at 18:00", NOW, UTC));
+        assert_eq!(Ok(NOW + 6 * 3600),           parse("at 18:00", NOW, &Zone::Fixed(2 * 3600)));
+        assert_eq!(Ok(NOW + 23 * 3600),          parse("at 9am", NOW, UTC));
+        assert_eq!(Ok(NOW + 2 * 3600 + 1800),    parse("12:30pm", NOW, UTC));
+        assert!(parse("at 25:00", NOW, UTC).is_err());
+        assert!(parse("at 13pm", NOW, UTC).is_err());
+    }
+
+    #[test]
+    fn test_on() {
+        assert_eq!(Ok(NOW + 7 * 86400 - 3600),   parse("on friday 9am", NOW, UTC));
+        assert_eq!(Ok(NOW + 3 * 86400 - 3600),   parse("on mon", NOW, UTC));
+        assert_eq!(Ok(NOW + 86400 + 8 * 3600),   parse("tomorrow at 18:00", NOW, UTC));
+        assert_eq!(Ok(NOW + 4 * 86400),          parse("on 2016-10-25 at 10:00", NOW, UTC));
+        assert_eq!(Ok(NOW + 4 * 86400 - 3600),   parse("on 2016-10-25 at 10:00", NOW, &Zone::Fixed(3600)));
+        assert!(parse("today at 8am", NOW, UTC).is_err());
+        assert!(parse("on someday", NOW, UTC).is_err());
+    }
+
+    #[test]
+    fn test_daylight_saving() {
+        // Rome is at UTC+2 until the 30th of October, then back to UTC+1
+        let rome = Zone::parse("Europe/Rome").unwrap();
+        assert_eq!(Ok(NOW + 4 * 86400 - 3 * 3600),  parse("on 2016-10-25 at 9:00", NOW, &rome));
+        assert_eq!(Ok(NOW + 12 * 86400 - 2 * 3600), parse("on 2016-11-02 at 9:00", NOW, &rome));
+        assert_eq!(Ok(NOW + 10 * 86400 - 2 * 3600), parse("on mon at 9:00", NOW + 3 * 86400, &rome));
+    }
+
+    #[test]
+    fn test_format() {
+        assert_eq!("Fri 21 Oct 12:00 (UTC+02:00)", format(NOW, &Zone::Fixed(7200)));
+        assert_eq!("Fri 21 Oct 04:30 (UTC-05:30)", format(NOW, &Zone::Fixed(-19800)));
+        assert_eq!("Wed 02 Nov 09:00 (UTC+01:00)", format(NOW + 12 * 86400 - 2 * 3600, &Zone::parse("Europe/Rome").unwrap()));
+    }
+}
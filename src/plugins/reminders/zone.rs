@@ -0,0 +1,289 @@
+use regex::Regex;
+
+lazy_static! {
+    static ref OFFSET: Regex = Regex::new(r"^(?:utc|gmt)?\s*(?:([+-])\s*(\d{1,2})(?::?(\d{2}))?)?$").unwrap();
+}
+
+const HOUR: i64 = 3600;
+const DAY:  i64 = 86400;
+
+// When the clocks go forward and back.
+#[derive(PartialEq, Debug, Clone, Copy)]
+pub enum Rule {
+    // no daylight saving time
+    Never,
+    // last sunday of March to last sunday of October, at 01:00 UTC
+    Europe,
+    // second sunday of March to first sunday of November, at 02:00 local time
+    America,
+    // first sunday of October to first sunday of April, at 02:00 standard time
+    Australia,
+    // last sunday of September to first sunday of April, at 02:00 standard time
+    NewZealand
+}
+
+// The name, the standard offset from UTC and the daylight saving rule.
+const ZONES: &'static [(&'static str, i64, Rule)] = &[
+    ("UTC",                  0,                Rule::Never),
+    ("Europe/London",        0,                Rule::Europe),
+    ("Europe/Dublin",        0,                Rule::Europe),
+    ("Europe/Lisbon",        0,                Rule::Europe),
+    ("Europe/Amsterdam",     HOUR,             Rule::Europe),
+    ("Europe/Berlin",        HOUR,             Rule::Europe),
+    ("Europe/Brussels",      HOUR,             Rule::Europe),
+    ("Europe/Copenhagen",    HOUR,             Rule::Europe),
+    ("Europe/Madrid",        HOUR,             Rule::Europe),
+    ("Europe/Oslo",          HOUR,             Rule::Europe),
+    ("Europe/Paris",         HOUR,             Rule::Europe),
+    ("Europe/Prague",        HOUR,             Rule::Europe),
+    ("Europe/Rome",          HOUR,             Rule::Europe),
+    ("Europe/Stockholm",     HOUR,             Rule::Europe),
+    ("Europe/Vienna",        HOUR,             Rule::Europe),
+    ("Europe/Warsaw",        HOUR,             Rule::Europe),
+    ("Europe/Zurich",        HOUR,             Rule::Europe),
+    ("Europe/Athens",        2 * HOUR,         Rule::Europe),
+    ("Europe/Bucharest",     2 * HOUR,         Rule::Europe),
+    ("Europe/Helsinki",      2 * HOUR,         Rule::Europe),
+    ("Europe/Kiev",          2 * HOUR,         Rule::Europe),
+    ("Europe/Istanbul",      3 * HOUR,         Rule::Never),
+    ("Europe/Moscow",        3 * HOUR,         Rule::Never),
+    ("America/St_Johns",     -3 * HOUR - 1800, Rule::America),
+    ("America/Halifax",      -4 * HOUR,        Rule::America),
+    ("America/New_York",     -5 * HOUR,        Rule::America),
+    ("America/Toronto",      -5 * HOUR,        Rule::America),
+    ("America/Chicago",      -6 * HOUR,        Rule::America),
+    ("America/Mexico_City",  -6 * HOUR,        Rule::Never),
+    ("America/Denver",       -7 * HOUR,        Rule::America),
+    ("America/Phoenix",      -7 * HOUR,        Rule::Never),
+    ("America/Los_Angeles",  -8 * HOUR,        Rule::America),
+    ("America/Vancouver",    -8 * HOUR,        Rule::America),
+    ("America/Anchorage",    -9 * HOUR,        Rule::America),
+    ("Pacific/Honolulu",     -10 * HOUR,       Rule::Never),
+    ("America/Sao_Paulo",    -3 * HOUR,        Rule::Never),
+    ("America/Buenos_Aires", -3 * HOUR,        Rule::Never),
+    ("Asia/Dubai",           4 * HOUR,         Rule::Never),
+    ("Asia/Kolkata",         5 * HOUR + 1800,  Rule::Never),
+    ("Asia/Bangkok",         7 * HOUR,         Rule::Never),
+    ("Asia/Jakarta",         7 * HOUR,         Rule::Never),
+    ("Asia/Shanghai",        8 * HOUR,         Rule::Never),
+    ("Asia/Hong_Kong",       8 * HOUR,         Rule::Never),
+    ("Asia/Singapore",       8 * HOUR,         Rule::Never),
+    ("Asia/Taipei",          8 * HOUR,         Rule::Never),
+    ("Australia/Perth",      8 * HOUR,         Rule::Never),
+    ("Asia/Seoul",           9 * HOUR,         Rule::Never),
+    ("Asia/Tokyo",           9 * HOUR,         Rule::Never),
+    ("Australia/Brisbane",   10 * HOUR,        Rule::Never),
+    ("Australia/Melbourne",  10 * HOUR,        Rule::Australia),
+    ("Australia/Sydney",     10 * HOUR,        Rule::Australia),
+    ("Pacific/Auckland",     12 * HOUR,        Rule::NewZealand)
+];
+
+#[derive(PartialEq, Debug, Clone, Copy)]
+pub enum Zone {
+    // a bare offset from UTC in seconds, the same all year long
+    Fixed(i64),
+    Named(&'static str, i64, Rule)
+}
+
+fn modulo(a: i64, b: i64) -> i64 {
+    ((a % b) + b) % b
+}
+
+// Days since 1970-01-01, from Howard Hinnant's date algorithms.
+fn days_from_civil(year: i64, month: i64, day: i64) -> i64 {
+    let year = if month <= 2 { year - 1 } else { year };
+    let era  = if year >= 0 { year } else { year - 399 } / 400;
+    let yoe  = year - era * 400;
+    let doy  = (153 * (month + if month > 2 { -3 } else { 9 }) + 2) / 5 + day - 1;
+    let doe  = yoe * 365 + yoe / 4 - yoe / 100 + doy;
+
+    era * 146097 + doe - 719468
+}
+
+fn year_of(days: i64) -> i64 {
+    let z   = days + 719468;
+    let era = if z >= 0 { z } else { z - 146096 } / 146097;
+    let doe = z - era * 146097;
+    let yoe = (doe - doe / 1460 + doe / 36524 - doe / 146096) / 365;
+    let doy = doe - (365 * yoe + yoe / 4 - yoe / 100);
+    let mp  = (5 * doy + 2) / 153;
+
+    yoe + era * 400 + if mp >= 10 { 1 } else { 0 }
+}
+
+fn day(at: i64) -> i64 {
+    (at - modulo(at, DAY)) / DAY
+}
+
+// 1970-01-01 was a thursday, so sundays are the days where this is 0.
+fn weekday(days: i64) -> i64 {
+    modulo(days + 4, 7)
+}
+
+// The nth sunday of the month, in days since 1970-01-01.
+fn sunday(year: i64, month: i64, nth: i64) -> i64 {
+    let first = days_from_civil(year, month, 1);
+    first + modulo(-weekday(first), 7) + (nth - 1) * 7
+}
+
+fn last_sunday(year: i64, month: i64) -> i64 {
+    let last = if month == 12 { days_from_civil(year + 1, 1, 1) } else { days_from_civil(year, month + 1, 1) } - 1;
+    last - weekday(last)
+}
+
+impl Rule {
+    // Whether the clocks are an hour forward at `at`, for a zone whose
+    // standard time is `standard` seconds from UTC.
+    fn is_dst(&self, at: i64, standard: i64) -> bool {
+        let year = year_of(day(at + standard));
+        match *self {
+            Rule::Never      => false,
+            Rule::Europe     => {
+                let start = last_sunday(year, 3)  * DAY + HOUR;
+                let end   = last_sunday(year, 10) * DAY + HOUR;
+                at >= start && at < end
+            },
+            Rule::America    => {
+                let start = sunday(year, 3, 2)  * DAY + 2 * HOUR - standard;
+                let end   = sunday(year, 11, 1) * DAY + 2 * HOUR - standard - HOUR;
+                at >= start && at < end
+            },
+            Rule::Australia  => {
+                let end   = sunday(year, 4, 1)  * DAY + 2 * HOUR - standard;
+                let start = sunday(year, 10, 1) * DAY + 2 * HOUR - standard;
+                at < end || at >= start
+            },
+            Rule::NewZealand => {
+                let end   = sunday(year, 4, 1)     * DAY + 2 * HOUR - standard;
+                let start = last_sunday(year, 9)   * DAY + 2 * HOUR - standard;
+                at < end || at >= start
+            }
+        }
+    }
+}
+
+// "+2", "UTC+02:00", "gmt-5:30" or just "UTC", in seconds.
+fn parse_offset(offset: &str) -> Option<i64> {
+    OFFSET.captures(offset).and_then(|captures| {
+        let sign    = if captures.at(1) == Some("-") { -1 } else { 1 };
+        let hours   = captures.at(2).map(|hours| hours.parse::<i64>().unwrap()).unwrap_or(0);
+        let minutes = captures.at(3).map(|minutes| minutes.parse::<i64>().unwrap()).unwrap_or(0);
+
+        if hours <= 14 && minutes < 60 {
+            Some(sign * (hours * HOUR + minutes * 60))
+        }
+        else {
+            None
+        }
+    })
+}
+
+impl Zone {
+    // "Europe/Rome", "europe/rome" or an offset like "UTC+2".
+    pub fn parse(zone: &str) -> Option<Zone> {
+        let zone = zone.trim().to_lowercase();
+        if zone.is_empty() {
+            return None;
+        }
+
+        match ZONES.iter().find(|&&(name, _, _)| name.to_lowercase() == zone) {
+            Some(&(name, offset, rule)) => Some(Zone::Named(name, offset, rule)),
+            None                        => parse_offset(&zone).map(Zone::Fixed)
+        }
+    }
+
+    // Fixed zones are saved as seconds, like they always were.
+    pub fn from_stored(zone: &str) -> Option<Zone> {
+        match zone.parse() {
+            Ok(offset) => Some(Zone::Fixed(offset)),
+            Err(_)     => Zone::parse(zone)
+        }
+    }
+
+    pub fn stored(&self) -> String {
+        match *self {
+            Zone::Fixed(offset)     => offset.to_string(),
+            Zone::Named(name, _, _) => name.to_owned()
+        }
+    }
+
+    // Seconds from UTC at the given moment.
+    pub fn offset(&self, at: i64) -> i64 {
+        match *self {
+            Zone::Fixed(offset)                                         => offset,
+            Zone::Named(_, standard, rule) if rule.is_dst(at, standard) => standard + HOUR,
+            Zone::Named(_, standard, _)                                 => standard
+        }
+    }
+
+    // The moment the clocks in this zone show `local`. Times skipped when the
+    // clocks go forward come out an hour later, and times that happen twice
+    // when they go back are taken the first time.
+    pub fn to_utc(&self, local: i64) -> i64 {
+        let standard = match *self {
+            Zone::Fixed(offset)         => { return local - offset; },
+            Zone::Named(_, standard, _) => standard
+        };
+
+        let summer = local - standard - HOUR;
+        if self.offset(summer) == standard + HOUR { summer } else { local - standard }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::Zone;
+
+    // Fri, 21 Oct 2016 10:00:00 UTC
+    const NOW: i64 = 1477044000;
+
+    #[test]
+    fn test_parse() {
+        assert_eq!(Some("Europe/Rome".to_owned()), Zone::parse("europe/rome").map(|zone| zone.stored()));
+        assert_eq!(Some(Zone::Fixed(7200)),        Zone::parse("+2"));
+        assert_eq!(Some(Zone::Fixed(-19800)),      Zone::parse("UTC-05:30"));
+        assert_eq!(Some(Zone::Fixed(0)),           Zone::parse("gmt"));
+        assert_eq!(None,                           Zone::parse("+15"));
+        assert_eq!(None,                           Zone::parse("Mars/Olympus_Mons"));
+        assert_eq!(Zone::parse("Europe/Rome"),     Zone::from_stored("Europe/Rome"));
+        assert_eq!(Some(Zone::Fixed(3600)),        Zone::from_stored("3600"));
+    }
+
+    #[test]
+    fn test_offset() {
+        let rome = Zone::parse("Europe/Rome").unwrap();
+        assert_eq!(7200, rome.offset(NOW));
+        // 30 Oct 2016, the clocks went back at 01:00 UTC
+        assert_eq!(7200, rome.offset(1477789199));
+        assert_eq!(3600, rome.offset(1477789200));
+        // 26 Mar 2017, forward at 01:00 UTC
+        assert_eq!(3600, rome.offset(1490489999));
+        assert_eq!(7200, rome.offset(1490490000));
+
+        // 6 Nov 2016, back at 06:00 UTC
+        let new_york = Zone::parse("America/New_York").unwrap();
+        assert_eq!(-4 * 3600, new_york.offset(1478411999));
+        assert_eq!(-5 * 3600, new_york.offset(1478412000));
+
+        // 2 Oct 2016, forward at 16:00 UTC the day before
+        let sydney = Zone::parse("Australia/Sydney").unwrap();
+        assert_eq!(10 * 3600, sydney.offset(1475337599));
+        assert_eq!(11 * 3600, sydney.offset(1475337600));
+        assert_eq!(11 * 3600, sydney.offset(1483228800));
+
+        assert_eq!(9 * 3600, Zone::parse("Asia/Tokyo").unwrap().offset(NOW));
+    }
+
+    #[test]
+    fn test_to_utc() {
+        let rome = Zone::parse("Europe/Rome").unwrap();
+        // 9:00 on 25 Oct and on 2 Nov 2016
+        assert_eq!(1477378800, rome.to_utc(1477386000));
+        assert_eq!(1478073600, rome.to_utc(1478077200));
+        // 02:30 on 26 Mar 2017 never happened, it is 03:30 instead
+        assert_eq!(1490491800, rome.to_utc(1490495400));
+        // 02:30 on 30 Oct 2016 happened twice
+        assert_eq!(1477787400, rome.to_utc(1477794600));
+        assert_eq!(NOW, Zone::Fixed(-3600).to_utc(NOW - 3600));
+    }
+}
@@ -0,0 +1,404 @@
+use std::io;
+use std::collections::{BTreeMap, HashMap};
+use irc::client::prelude::*;
+use regex::{self, Regex, Captures};
+use redis::Commands;
+use time;
+use plugin::{self, Plugin};
+use store::Store;
+
+lazy_static! {
+    static ref PLACEHOLDER: Regex = Regex::new(r"\{(\w+)\}").unwrap();
+}
+
+#[derive(PartialEq, Debug)]
+enum Trigger {
+    Exact,
+    Glob,
+    Regex
+}
+
+// A trigger and the response it gets. Both may mention {botnick}, and the response
+// {nick}, {channel} and the capture groups, {1} to {9}. Globs capture each * and ?.
+// The trigger is compiled for one bot nick at a time, and again when the nick changes.
+#[derive(Debug)]
+pub struct Rule {
+    name:        String,
+    kind:        Trigger,
+    trigger:     String,
+    ignore_case: bool,
+    response:    String,
+    cooldown:    i64,
+    channels:    Vec<String>,
+    probability: f64,
+    fired_at:    HashMap<String, i64>,
+    compiled:    Option<(String, Regex)>
+}
+
+impl Rule {
+    // `fields` are trigger, response, match (exact, glob or regex), ignore_case (true or false),
+    // cooldown (seconds), channels (comma separated, all of them when missing) and probability (0 to 1).
+    fn new(name: &str, fields: &HashMap<String, String>) -> Result<Rule, String> {
+        let field = |key: &str| fields.get(key).map(|value| &value[..]);
+
+        let mut rule = Rule {
+            name:        name.to_owned(),
+            kind:        match field("match").unwrap_or("exact") {
+                "exact" => Trigger::Exact,
+                "glob"  => Trigger::Glob,
+                "regex" => Trigger::Regex,
+                other   => { return Err(format!("unknown match {}", other)); }
+            },
+            trigger:     try!(field("trigger").ok_or("no trigger".to_owned())).to_owned(),
+            ignore_case: try!(field("ignore_case").unwrap_or("false").parse().map_err(|_| "invalid ignore_case".to_owned())),
+            response:    try!(field("response").ok_or("no response".to_owned())).to_owned(),
+            cooldown:    try!(field("cooldown").unwrap_or("0").parse().map_err(|_| "invalid cooldown".to_owned())),
+            channels:    field("channels").unwrap_or("")
+                .split(',')
+                .map(|channel| channel.trim().to_lowercase())
+                .filter(|channel| !channel.is_empty())
+                .collect(),
+            probability: try!(field("probability").unwrap_or("1").parse().map_err(|_| "invalid probability".to_owned())),
+            fired_at:    HashMap::new(),
+            compiled:    None
+        };
+
+        // a broken regex is better found out now than at every message
+        try!(rule.compile("Gauss"));
+        Ok(rule)
+    }
+
+    fn compile(&mut self, botnick: &str) -> Result<(), String> {
+        if !self.is_compiled(botnick) {
+            self.compiled = Some((botnick.to_owned(), try!(self.pattern(botnick))));
+        }
+
+        Ok(())
+    }
+
+    fn is_compiled(&self, botnick: &str) -> bool {
+        match self.compiled {
+            Some((ref nick, _)) => nick == botnick,
+            None                => false
+        }
+    }
+
+    fn pattern(&self, botnick: &str) -> Result<Regex, String> {
+        let parts: Vec<String> = self.trigger.split("{botnick}").map(|part| match self.kind {
+            Trigger::Exact => regex::quote(part),
+            Trigger::Glob  => regex::quote(part).replace(r"\*", "(.*)").replace(r"\?", "(.)"),
+            Trigger::Regex => part.to_owned()
+        }).collect();
+
+        let source = parts.join(&regex::quote(botnick));
+        let source = match self.kind {
+            Trigger::Regex => source,
+            _              => format!("^{}$", source)
+        };
+
+        if self.ignore_case {
+            Regex::new(&format!("(?i){}", source)).map_err(|e| e.to_string())
+        }
+        else {
+            Regex::new(&source).map_err(|e| e.to_string())
+        }
+    }
+
+    fn matches(&self, botnick: &str, channel: &str, msg: &str) -> Option<Vec<String>> {
+        if !self.channels.is_empty() && !self.channels.contains(&channel.to_lowercase()) {
+            return None;
+        }
+
+        let groups = |pattern: &Regex| pattern.captures(msg.trim()).map(|captures| {
+            (0..captures.len()).map(|i| captures.at(i).unwrap_or("").to_owned()).collect()
+        });
+
+        // until it is compiled again, a rule can still be matched against a new nick
+        match self.compiled {
+            Some((ref nick, ref pattern)) if nick == botnick => groups(pattern),
+            _                                                => self.pattern(botnick).ok().and_then(|pattern| groups(&pattern))
+        }
+    }
+
+    fn respond(&self, nickname: &str, channel: &str, botnick: &str, groups: &[String]) -> String {
+        PLACEHOLDER.replace_all(&self.response, |captures: &Captures| {
+            let placeholder = captures.at(1).unwrap_or("");
+            match placeholder {
+                "nick"    => nickname.to_owned(),
+                "channel" => channel.to_owned(),
+                "botnick" => botnick.to_owned(),
+                _         => match placeholder.parse::<usize>().ok().and_then(|i| groups.get(i)) {
+                    Some(group) => group.clone(),
+                    None        => captures.at(0).unwrap_or("").to_owned()
+                }
+            }
+        })
+    }
+
+    // Rules that fired less than `cooldown` seconds ago in a channel stay quiet there,
+    // the others only fire with their probability.
+    fn ready(&self, channel: &str, now: i64, chance: f64) -> bool {
+        let cooled_down = match self.fired_at.get(channel) {
+            Some(&fired_at) => now - fired_at >= self.cooldown,
+            None            => true
+        };
+
+        cooled_down && chance < self.probability
+    }
+}
+
+#[derive(Debug)]
+pub struct RuleSet {
+    rules: Vec<Rule>
+}
+
+impl RuleSet {
+    // "h Gauss" gets "h <nick>" back, like it always did.
+    pub fn new() -> RuleSet {
+        let mut fields = HashMap::new();
+        fields.insert("trigger".to_owned(),  "h {botnick}".to_owned());
+        fields.insert("response".to_owned(), "h {nick}".to_owned());
+
+        RuleSet { rules: vec![Rule::new("h", &fields).unwrap()] }
+    }
+
+    fn compile(&mut self, botnick: &str) {
+        for rule in &mut self.rules {
+            if let Err(e) = rule.compile(botnick) {
+                println!("Rules: cannot compile the rule {} for {} ({})", rule.name, botnick, e);
+            }
+        }
+    }
+
+    fn is_compiled(&self, botnick: &str) -> bool {
+        self.rules.iter().all(|rule| rule.is_compiled(botnick))
+    }
+
+    fn add(&mut self, name: &str, fields: &HashMap<String, String>) {
+        match Rule::new(name, fields) {
+            Ok(rule) => {
+                self.rules.retain(|rule| rule.name != name);
+                self.rules.push(rule);
+            },
+            Err(e) => println!("Rules: skipping the rule {} ({})", name, e)
+        }
+    }
+}
+
+register_plugin!(Rules, rules: RuleSet);
+
+impl Rules {
+    // Options look like `rules.<name>.<field>`, e.g. `rules.h.trigger`.
+    fn from_config(&mut self, server: &IrcServer) {
+        let mut rules: BTreeMap<String, HashMap<String, String>> = BTreeMap::new();
+
+        if let Some(ref options) = server.config().options {
+            for (key, value) in options {
+                let parts: Vec<&str> = key.splitn(3, '.').collect();
+                if parts.len() == 3 && parts[0] == "rules" {
+                    rules.entry(parts[1].to_owned()).or_insert(HashMap::new()).insert(parts[2].to_owned(), value.clone());
+                }
+            }
+        }
+
+        for (name, fields) in rules {
+            self.rules.add(&name, &fields);
+        }
+    }
+
+    // The names are in the "names" set and each rule in the "rule:<name>" hash.
+    fn from_store(&mut self) {
+        let store = match Store::open("rules") {
+            Ok(store) => store,
+            Err(e)    => {
+                println!("Rules: cannot read the rules from Redis ({:?})", e);
+                return;
+            }
+        };
+
+        let names: Vec<String> = store.smembers(store.key("names")).unwrap_or(vec![]);
+        for name in names {
+            let fields: HashMap<String, String> = store.hgetall(store.key(&format!("rule:{}", name))).unwrap_or(HashMap::new());
+            self.rules.add(&name, &fields);
+        }
+    }
+
+    fn grep_rule(&self, server: &IrcServer, channel: &str, msg: &str) -> Option<(usize, Vec<String>)> {
+        let botnick = server.current_nickname();
+
+        self.rules.rules.iter()
+            .enumerate()
+            .filter_map(|(i, rule)| rule.matches(botnick, channel, msg).map(|groups| (i, groups)))
+            .next()
+    }
+
+    fn respond(&mut self, server: &IrcServer, message: &Message, target: &str, msg: &str) -> io::Result<()> {
+        let (i, groups) = match self.grep_rule(server, target, msg) {
+            Some(rule) => rule,
+            None       => { return Ok(()); }
+        };
+
+        let now    = time::get_time().sec;
+        let chance = (time::precise_time_ns() % 1000) as f64 / 1000.0;
+        let rule   = &mut self.rules.rules[i];
+        if !rule.ready(target, now, chance) {
+            return Ok(());
+        }
+
+        rule.fired_at.insert(target.to_owned(), now);
+        server.send_privmsg(target, &rule.respond(message.source_nickname().unwrap_or(""), target,
+                                                  server.current_nickname(), &groups))
+    }
+}
+
+impl Plugin for Rules {
+    // Rules from Redis win over the ones in the config, which win over the default one.
+    fn configure(&mut self, server: &IrcServer) {
+        self.from_config(server);
+
+        if plugin::get_option(server, "rules.storage") != Some("false") {
+            self.from_store();
+        }
+
+        self.rules.compile(server.current_nickname());
+    }
+
+    // The nick changes when it is taken or refused, and back at the end of the MOTD
+    // when the bot ghosts whoever had it, which is when the rules are compiled again.
+    fn is_allowed(&self, server: &IrcServer, message: &Message) -> bool {
+        match message.command {
+            Command::PRIVMSG(ref target, ref msg) => self.grep_rule(server, target, msg).is_some(),
+            Command::Response(Response::ERR_NICKNAMEINUSE, _, _) |
+            Command::Response(Response::ERR_ERRONEOUSNICKNAME, _, _) |
+            Command::Response(Response::RPL_ENDOFMOTD, _, _) |
+            Command::Response(Response::ERR_NOMOTD, _, _) => !self.rules.is_compiled(server.current_nickname()),
+            _ => false
+        }
+    }
+
+    fn execute(&mut self, server: &IrcServer, message: &Message) -> io::Result<()> {
+        self.rules.compile(server.current_nickname());
+
+        match message.command {
+            Command::PRIVMSG(ref target, ref msg) => self.respond(server, message, target, msg),
+            _ => Ok(())
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::HashMap;
+    use ::tests::{make_server, make_server_with_options, get_server_value};
+
+    use irc::client::prelude::*;
+
+    use plugin::Plugin;
+    use super::{Rules, Rule, RuleSet};
+
+    #[test]
+    fn test_allowed() {
+        let     server = make_server("PRIVMSG test :h Gauss\r\n");
+        let mut plugin  = Rules::new();
+
+        for message in server.iter() {
+            let message = message.unwrap();
+            assert!(plugin.is_allowed(&server, &message));
+            assert!(plugin.execute(&server, &message).is_ok());
+        }
+
+        assert_eq!("PRIVMSG test :h \r\n", &*get_server_value(&server));
+    }
+
+    #[test]
+    fn test_not_allowed() {
+        let server = make_server("PRIVMSG test :h Holo\r\n");
+        let plugin  = Rules::new();
+
+        for message in server.iter() {
+            let message = message.unwrap();
+            assert!(!plugin.is_allowed(&server, &message));
+        }
+    }
+
+    #[test]
+    fn test_rules() {
+        let     server = make_server_with_options("PRIVMSG #test :good night Gauss\r\nPRIVMSG #test :good night Gauss\r\n\
+                                                   PRIVMSG #test :!weather in Tokyo\r\nPRIVMSG #other :!weather in Tokyo\r\n",
+                                                  &[("rules.storage",              "false"),
+                                                    ("rules.night.trigger",        "good night {botnick}"),
+                                                    ("rules.night.response",       "sweet dreams {nick}, from {channel}"),
+                                                    ("rules.night.cooldown",       "3600"),
+                                                    ("rules.weather.trigger",      r"^!weather in (\w+)$"),
+                                                    ("rules.weather.match",        "regex"),
+                                                    ("rules.weather.response",     "{botnick} has no idea about the weather in {1}"),
+                                                    ("rules.weather.channels",     "#test")]);
+        let mut plugin = Rules::new();
+
+        plugin.configure(&server);
+        for message in server.iter() {
+            let message = message.unwrap();
+            if plugin.is_allowed(&server, &message) {
+                assert!(plugin.execute(&server, &message).is_ok());
+            }
+        }
+
+        assert_eq!("PRIVMSG #test :sweet dreams , from #test\r\n\
+                    PRIVMSG #test :Gauss has no idea about the weather in Tokyo\r\n",
+                   &*get_server_value(&server));
+    }
+
+    #[test]
+    fn test_glob() {
+        let mut fields = HashMap::new();
+        fields.insert("trigger".to_owned(),  "* is ?".to_owned());
+        fields.insert("match".to_owned(),    "glob".to_owned());
+        fields.insert("response".to_owned(), "{1} is {2}? {3}".to_owned());
+        fields.insert("probability".to_owned(), "0.5".to_owned());
+
+        let rule = Rule::new("glob", &fields).unwrap();
+        let groups = rule.matches("Gauss", "#test", "Gauss is a").unwrap();
+        assert_eq!("Gauss is a? {3}", rule.respond("holo", "#test", "Gauss", &groups));
+        assert_eq!(None, rule.matches("Gauss", "#test", "Gauss is ab"));
+
+        assert!(rule.ready("#test", 0, 0.2));
+        assert!(!rule.ready("#test", 0, 0.7));
+
+        fields.insert("match".to_owned(), "regex".to_owned());
+        fields.insert("trigger".to_owned(), "(".to_owned());
+        assert!(Rule::new("broken", &fields).is_err());
+    }
+
+    #[test]
+    fn test_ignore_case() {
+        let rules = RuleSet::new();
+        assert!(rules.rules[0].matches("Gauss", "#test", "h Gauss").is_some());
+        assert!(rules.rules[0].matches("Gauss", "#test", "H gauss").is_none());
+
+        let mut fields = HashMap::new();
+        fields.insert("trigger".to_owned(),     "hello {botnick}".to_owned());
+        fields.insert("response".to_owned(),    "hello {nick}".to_owned());
+        fields.insert("ignore_case".to_owned(), "true".to_owned());
+
+        let rule = Rule::new("hello", &fields).unwrap();
+        assert!(rule.matches("Gauss", "#test", "HELLO gauss").is_some());
+
+        fields.insert("ignore_case".to_owned(), "maybe".to_owned());
+        assert!(Rule::new("hello", &fields).is_err());
+    }
+
+    #[test]
+    fn test_nick_change() {
+        let mut rules = RuleSet::new();
+        assert!(rules.is_compiled("Gauss"));
+        assert!(!rules.is_compiled("Gauss_"));
+
+        // still matched before the rules are compiled again
+        assert!(rules.rules[0].matches("Gauss_", "#test", "h Gauss_").is_some());
+
+        rules.compile("Gauss_");
+        assert!(rules.is_compiled("Gauss_"));
+        assert!(rules.rules[0].matches("Gauss_", "#test", "h Gauss_").is_some());
+        assert!(rules.rules[0].matches("Gauss_", "#test", "h Gauss").is_none());
+    }
+}
@@ -1,7 +1,7 @@
 use std::io;
 use irc::client::prelude::*;
 use regex::Regex;
-use plugin::Plugin;
+use plugin::{self, Plugin};
 use time::{self, Tm};
 
 lazy_static! {
@@ -65,7 +65,7 @@ impl Seen {
     fn parted(&mut self, nickname: Option<String>) -> io::Result<()> {
         match nickname {
             Some(nickname) => {
-                self.users.iter_mut().find(|u| u.name == nickname).map(|mut u| u.parted_at = Some(time::now()));
+                self.users.iter_mut().find(|u| plugin::nick_key(&u.name) == plugin::nick_key(&nickname)).map(|mut u| u.parted_at = Some(time::now()));
             },
             None => {}
         }
@@ -79,17 +79,17 @@ impl Seen {
             None      => { return Ok(()); }
         };
 
-        if username == server.current_nickname() {
+        if plugin::nick_key(username) == plugin::nick_key(server.current_nickname()) {
             return server.send_privmsg(target, "That's me!");
         }
 
         let requester = message.source_nickname();
-        if requester.is_some() && username == requester.unwrap() {
+        if requester.is_some() && plugin::nick_key(username) == plugin::nick_key(requester.unwrap()) {
             return server.send_privmsg(target, "That's you!");
         }
 
         for user in &self.users {
-            if user.name == username {
+            if plugin::nick_key(&user.name) == plugin::nick_key(username) {
                 return server.send_privmsg(target, &user.to_string());
             }
         }
@@ -120,7 +120,7 @@ mod tests {
 
     use irc::client::prelude::*;
 
-    use plugin::Plugin;
+    use plugin::{self, Plugin};
     use time::{self, Tm};
     use super::{Seen, User};
 
@@ -161,6 +161,18 @@ mod tests {
         assert_eq!("PRIVMSG test :That's me!\r\n", &*get_server_value(&server));
     }
 
+    #[test]
+    fn test_seen_casemapping() {
+        let     server = make_server("PRIVMSG test :!seen holo{}\r\n");
+        let mut plugin = Seen::new();
+        plugin.users.push(User { name: "Holo[]".to_owned(), joined_at: get_time(), parted_at: None });
+
+        let message = server.iter().last().unwrap().unwrap();
+        assert!(plugin.execute(&server, &message).is_ok());
+
+        assert_eq!("PRIVMSG test :Holo[] joined here on Sat, 01 Oct 2016 12:58:34 GMT\r\n", &*get_server_value(&server));
+    }
+
     #[test]
     fn test_not_seen() {
         let     server = make_server("PRIVMSG test :!seen Holo\r\n");
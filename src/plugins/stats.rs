@@ -0,0 +1,228 @@
+use std::io;
+use std::f64::consts;
+use irc::client::prelude::*;
+use regex::Regex;
+use plugin::Plugin;
+
+lazy_static! {
+    static ref STATS: Regex = Regex::new(r"^!stats\s+(.+?)(?:\s+cdf\s+(\S+))?\s*$").unwrap();
+}
+
+// more than fit in a line, really
+const MAX_VALUES: usize = 500;
+
+// what's left of the 512 bytes of a line after the prefix and the target
+const MAX_REPLY: usize = 400;
+
+// values are shown with this many significant digits
+const DIGITS: i32 = 4;
+
+#[derive(PartialEq, Debug)]
+struct Summary {
+    count:     usize,
+    mean:      f64,
+    median:    f64,
+    // of the sample, so divided by n - 1
+    deviation: f64,
+    quartiles: (f64, f64),
+    min:       f64,
+    max:       f64
+}
+
+// Linear interpolation between the closest ranks, like most spreadsheets do.
+fn quantile(sorted: &[f64], q: f64) -> f64 {
+    let position = q * (sorted.len() - 1) as f64;
+    let lower    = position.floor() as usize;
+    match sorted.get(lower + 1) {
+        Some(upper) => sorted[lower] + (upper - sorted[lower]) * (position - lower as f64),
+        None        => sorted[lower]
+    }
+}
+
+// None without values.
+fn summarize(values: &[f64]) -> Option<Summary> {
+    if values.is_empty() {
+        return None;
+    }
+
+    let mut sorted = values.to_vec();
+    sorted.sort_by(|a, b| a.partial_cmp(b).unwrap());
+
+    let count    = values.len();
+    let mean     = values.iter().fold(0.0, |sum, value| sum + value) / count as f64;
+    let variance = if count > 1 {
+        values.iter().fold(0.0, |sum, value| sum + (value - mean).powi(2)) / (count - 1) as f64
+    }
+    else {
+        0.0
+    };
+
+    Some(Summary {
+        count:     count,
+        mean:      mean,
+        median:    quantile(&sorted, 0.5),
+        deviation: variance.sqrt(),
+        quartiles: (quantile(&sorted, 0.25), quantile(&sorted, 0.75)),
+        min:       sorted[0],
+        max:       sorted[count - 1]
+    })
+}
+
+// Chebyshev fitting from Numerical Recipes, the error is below 1.2e-7.
+fn erfc(x: f64) -> f64 {
+    let z = x.abs();
+    let t = 1.0 / (1.0 + 0.5 * z);
+    let r = t * (-z * z - 1.26551223 + t * (1.00002368 + t * (0.37409196 + t * (0.09678418 +
+            t * (-0.18628806 + t * (0.27886807 + t * (-1.13520398 + t * (1.48851587 +
+            t * (-0.82215223 + t * 0.17087277))))))))).exp();
+
+    if x >= 0.0 { r } else { 2.0 - r }
+}
+
+// P(X <= x) for X normally distributed with the given mean and deviation.
+fn normal_cdf(x: f64, mean: f64, deviation: f64) -> f64 {
+    if deviation == 0.0 {
+        return if x >= mean { 1.0 } else { 0.0 };
+    }
+
+    0.5 * erfc((mean - x) / (deviation * consts::SQRT_2))
+}
+
+// Very large and very small values are shown in scientific notation, like 1.5e300 or 2e-5.
+fn format(value: f64) -> String {
+    if value == 0.0 {
+        return "0".to_owned();
+    }
+
+    let magnitude = value.abs().log10().floor() as i32;
+    if magnitude >= 6 || magnitude < -4 {
+        let formatted = format!("{:.*e}", (DIGITS - 1) as usize, value);
+        let mut parts = formatted.splitn(2, 'e');
+        let mantissa  = parts.next().unwrap().trim_right_matches('0').trim_right_matches('.');
+        return format!("{}e{}", mantissa, parts.next().unwrap_or("0"));
+    }
+
+    let decimals  = if magnitude < DIGITS - 1 { (DIGITS - 1 - magnitude) as usize } else { 0 };
+    let formatted = format!("{:.*}", decimals, value);
+    let formatted = if formatted.contains('.') { formatted.trim_right_matches('0').trim_right_matches('.') } else { &formatted[..] };
+    if formatted == "-0" { "0".to_owned() } else { formatted.to_owned() }
+}
+
+fn parse(input: &str) -> Result<Vec<f64>, String> {
+    let mut values = vec![];
+    for value in input.split(|c: char| c.is_whitespace() || c == ',').filter(|value| !value.is_empty()) {
+        match value.parse::<f64>() {
+            Ok(value) if value.is_finite() => values.push(value),
+            _                              => { return Err(format!("{} is not a number", value)); }
+        }
+    }
+
+    if values.len() > MAX_VALUES {
+        return Err(format!("I can only look at {} values at a time", MAX_VALUES));
+    }
+
+    Ok(values)
+}
+
+fn run(values: &str, cdf: Option<&str>) -> Result<String, String> {
+    let summary = try!(parse(values).and_then(|values| summarize(&values).ok_or("I need some values".to_owned())));
+    let mut description = format!("n {}, mean {}, median {}, sd {}, Q1 {}, Q3 {}, min {}, max {}",
+                                  summary.count, format(summary.mean), format(summary.median), format(summary.deviation),
+                                  format(summary.quartiles.0), format(summary.quartiles.1), format(summary.min), format(summary.max));
+
+    if let Some(x) = cdf {
+        let x = try!(parse(x).ok().and_then(|x| x.first().cloned()).ok_or(format!("{} is not a number", x)));
+        description.push_str(&format!(", P(X ≤ {}) = {}", format(x), format(normal_cdf(x, summary.mean, summary.deviation))));
+    }
+
+    Ok(description)
+}
+
+register_plugin!(Stats);
+
+impl Plugin for Stats {
+    fn is_allowed(&self, _: &IrcServer, message: &Message) -> bool {
+        match message.command {
+            Command::PRIVMSG(_, ref msg) => STATS.is_match(msg),
+            _ => false
+        }
+    }
+
+    fn execute(&mut self, server: &IrcServer, message: &Message) -> io::Result<()> {
+        match message.command {
+            Command::PRIVMSG(ref target, ref msg) => {
+                let captures = STATS.captures(msg).unwrap();
+                match run(captures.at(1).unwrap(), captures.at(2)) {
+                    Ok(ref result) if result.len() > MAX_REPLY => server.send_privmsg(target, "The result is too long to show"),
+                    Ok(result) => server.send_privmsg(target, &result),
+                    Err(e)     => server.send_privmsg(target, &e)
+                }
+            },
+            _ => Ok(())
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use ::tests::{make_server, get_server_value};
+
+    use irc::client::prelude::*;
+
+    use plugin::Plugin;
+    use super::{Stats, Summary, summarize, normal_cdf, format, run};
+
+    #[test]
+    fn test_stats() {
+        let     server = make_server("PRIVMSG #test :!stats 1 2 3 4 cdf 3\r\n");
+        let mut plugin = Stats::new();
+
+        for message in server.iter() {
+            let message = message.unwrap();
+            assert!(plugin.is_allowed(&server, &message));
+            assert!(plugin.execute(&server, &message).is_ok());
+        }
+
+        assert_eq!("PRIVMSG #test :n 4, mean 2.5, median 2.5, sd 1.291, Q1 1.75, Q3 3.25, min 1, max 4, P(X ≤ 3) = 0.6507\r\n",
+                   &*get_server_value(&server));
+    }
+
+    #[test]
+    fn test_summarize() {
+        assert_eq!(Some(Summary { count: 1, mean: 5.0, median: 5.0, deviation: 0.0, quartiles: (5.0, 5.0), min: 5.0, max: 5.0 }),
+                   summarize(&[5.0]));
+        assert_eq!(Some(Summary { count: 3, mean: 3.0, median: 3.0, deviation: 2.0, quartiles: (2.0, 4.0), min: 1.0, max: 5.0 }),
+                   summarize(&[5.0, 1.0, 3.0]));
+        assert_eq!(None, summarize(&[]));
+    }
+
+    #[test]
+    fn test_normal_cdf() {
+        assert!((normal_cdf(0.0, 0.0, 1.0) - 0.5).abs() < 1e-7);
+        assert!((normal_cdf(1.96, 0.0, 1.0) - 0.9750021).abs() < 1e-6);
+        assert!((normal_cdf(-1.0, 0.0, 1.0) - 0.1586553).abs() < 1e-6);
+        assert_eq!(1.0, normal_cdf(2.0, 2.0, 0.0));
+    }
+
+    #[test]
+    fn test_format() {
+        assert_eq!("1.291",    format(1.2909944));
+        assert_eq!("12346",    format(12345.6));
+        assert_eq!("1.235e6",  format(1234567.0));
+        assert_eq!("1e300",    format(1e300));
+        assert_eq!("-2.5e-5",  format(-0.000025));
+        assert_eq!("0.0001",   format(0.0001));
+        assert_eq!("10",       format(9.99996));
+        assert_eq!("0",        format(-0.0));
+        assert_eq!(Ok("n 2, mean 2e-5, median 2e-5, sd 1.414e-5, Q1 1.5e-5, Q3 2.5e-5, min 1e-5, max 3e-5".to_owned()),
+                   run("0.00001 0.00003", None));
+    }
+
+    #[test]
+    fn test_errors() {
+        assert!(run("1 2 x", None).is_err());
+        assert!(run("1 2 nan", None).is_err());
+        assert!(run("1 2 3", Some("x")).is_err());
+        assert_eq!(Ok("n 3, mean 2, median 2, sd 1, Q1 1.5, Q3 2.5, min 1, max 3".to_owned()), run("1, 2, 3", None));
+    }
+}
@@ -0,0 +1,81 @@
+use std::fmt;
+use super::kana;
+
+#[derive(PartialEq, Debug, Clone)]
+pub struct Entry {
+    pub writing: Option<String>,
+    pub reading: String,
+    pub romaji:  Option<String>,
+    pub meaning: String,
+    pub info:    Option<String>
+}
+
+impl fmt::Display for Entry {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let reading = match self.romaji {
+            Some(ref romaji) => format!("{} - {}", self.reading, romaji),
+            None             => self.reading.clone()
+        };
+
+        let info = match self.info {
+            Some(ref info) => format!(" ({})", info),
+            None           => String::new()
+        };
+
+        match self.writing {
+            Some(ref writing) => write!(f, "{} ({}): {}{}", writing, reading, self.meaning, info),
+            None              => write!(f, "{}: {}{}",      reading,          self.meaning, info)
+        }
+    }
+}
+
+// Somewhere words can be looked up, by their writing or their reading, or by
+// their English meaning. Backends are asked in order until one knows the word.
+pub trait Dictionary: fmt::Debug + Send + Sync {
+    fn name(&self) -> &str;
+    fn lookup(&self, word: &str) -> Vec<Entry>;
+
+    fn search(&self, _: &str) -> Vec<Entry> {
+        vec![]
+    }
+}
+
+#[derive(Debug)]
+pub struct Dictionaries {
+    pub backends: Vec<Box<Dictionary>>
+}
+
+impl Dictionaries {
+    pub fn new() -> Dictionaries {
+        Dictionaries { backends: vec![Box::new(super::scraper::Scraper)] }
+    }
+
+    // Queries written in latin characters may be romaji, and are looked up as
+    // hiragana and katakana first, then as they are, and then among the meanings.
+    pub fn lookup(&self, query: &str) -> Option<(&str, Vec<Entry>)> {
+        let latin = kana::is_latin(query);
+        let kana  = if latin { kana::to_hiragana(query) } else { None };
+
+        self.backends.iter()
+            .map(|backend| {
+                let mut entries = vec![];
+                if let Some(ref hiragana) = kana {
+                    entries = backend.lookup(hiragana);
+                    if entries.is_empty() {
+                        entries = backend.lookup(&kana::to_katakana(hiragana));
+                    }
+                }
+
+                if entries.is_empty() {
+                    entries = backend.lookup(query);
+                }
+
+                if entries.is_empty() && latin {
+                    entries = backend.search(query);
+                }
+
+                (backend.name(), entries)
+            })
+            .find(|&(_, ref entries)| !entries.is_empty())
+    }
+}
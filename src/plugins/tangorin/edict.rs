@@ -0,0 +1,257 @@
+use std::fs::File;
+use std::io::Read;
+use std::collections::HashMap;
+use regex::Regex;
+use super::dictionary::{Dictionary, Entry};
+use super::kana;
+
+lazy_static! {
+    static ref TAG:         Regex = Regex::new(r"^(?:\(([^)]*)\)|\{([^}]*)\})\s*").unwrap();
+    static ref PARENTHESES: Regex = Regex::new(r"\([^)]*\)").unwrap();
+}
+
+#[derive(PartialEq, Debug)]
+struct Word {
+    writings: Vec<String>,
+    readings: Vec<String>,
+    glosses:  Vec<String>,
+    tags:     Vec<String>,
+    common:   bool
+}
+
+// JMdict in the EDICT2 format (UTF-8), one entry per line:
+//   桜;櫻 [さくら] /(n) (1) cherry tree/(2) cherry blossom/(P)/EntL1381410X/
+// Loaded once and indexed by writing and reading, so lookups need no network.
+#[derive(Debug)]
+pub struct Edict {
+    words: Vec<Word>,
+    index: HashMap<String, Vec<usize>>
+}
+
+// Drops the "(P)", "(iK)" or "(桜)" annotations of writings and readings.
+fn strip_annotations(field: &str) -> Vec<String> {
+    field.split(';')
+        .map(|item| item.split('(').next().unwrap_or("").trim().to_owned())
+        .filter(|item| !item.is_empty())
+        .collect()
+}
+
+fn parse_line(line: &str) -> Option<Word> {
+    // the first line credits the authors
+    if line.starts_with("　？？？") {
+        return None;
+    }
+
+    let slash    = try_option!(line.find('/'));
+    let headword = line[..slash].trim();
+
+    let (writings, readings) = match (headword.find('['), headword.find(']')) {
+        (Some(open), Some(close)) if open < close => {
+            (strip_annotations(&headword[..open]), strip_annotations(&headword[open + 1..close]))
+        },
+        _ => (vec![], strip_annotations(headword))
+    };
+
+    if readings.is_empty() {
+        return None;
+    }
+
+    let mut word = Word {
+        writings: writings,
+        readings: readings,
+        glosses:  vec![],
+        tags:     vec![],
+        common:   false
+    };
+
+    for field in line[slash..].split('/').map(|field| field.trim()).filter(|field| !field.is_empty()) {
+        if field == "(P)" {
+            word.common = true;
+            continue;
+        }
+        else if field.starts_with("EntL") {
+            continue;
+        }
+
+        // "(n,uk) (1) {comp} text": part of speech, sense number and field tags come first
+        let mut gloss = field;
+        while let Some(captures) = TAG.captures(gloss) {
+            if let Some(tags) = captures.at(1).or(captures.at(2)) {
+                word.tags.extend(tags.split(',').map(|tag| tag.trim().to_owned()));
+            }
+
+            gloss = &gloss[captures.pos(0).unwrap().1..];
+        }
+
+        if !gloss.is_empty() {
+            word.glosses.push(gloss.to_owned());
+        }
+    }
+
+    if word.glosses.is_empty() { None } else { Some(word) }
+}
+
+fn words(text: &str) -> Vec<String> {
+    text.split(|c: char| !c.is_alphanumeric())
+        .filter(|word| !word.is_empty())
+        .map(|word| word.to_lowercase())
+        .collect()
+}
+
+// 0 when the gloss is just the query ("to persevere" for "persevere" too),
+// 1 when the gloss contains its words in a row, None otherwise.
+fn relevance(gloss: &str, query: &[String]) -> Option<u8> {
+    let gloss = words(&PARENTHESES.replace_all(gloss, ""));
+
+    if gloss == query || (gloss.len() == query.len() + 1 && gloss[0] == "to" && &gloss[1..] == query) {
+        Some(0)
+    }
+    else if gloss.windows(query.len()).any(|window| window == query) {
+        Some(1)
+    }
+    else {
+        None
+    }
+}
+
+fn entry(word: &Word, writing: Option<String>) -> Entry {
+    Entry {
+        writing: writing,
+        reading: word.readings[0].clone(),
+        romaji:  Some(kana::to_romaji(&word.readings[0])),
+        meaning: word.glosses.join("; "),
+        info:    if word.tags.iter().any(|tag| tag == "uk") {
+            Some("usually written using kana alone".to_owned())
+        }
+        else {
+            None
+        }
+    }
+}
+
+impl Edict {
+    pub fn load(path: &str) -> Result<Edict, String> {
+        let mut content = String::new();
+        try!(File::open(path)
+             .and_then(|mut file| file.read_to_string(&mut content))
+             .map_err(|e| e.to_string()));
+
+        Ok(Edict::parse(&content))
+    }
+
+    pub fn parse(content: &str) -> Edict {
+        let mut edict = Edict {
+            words: vec![],
+            index: HashMap::new()
+        };
+
+        for word in content.lines().filter_map(parse_line) {
+            let id = edict.words.len();
+            for key in word.writings.iter().chain(word.readings.iter()) {
+                let ids = edict.index.entry(key.clone()).or_insert(vec![]);
+                if !ids.contains(&id) {
+                    ids.push(id);
+                }
+            }
+
+            edict.words.push(word);
+        }
+
+        edict
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.words.is_empty()
+    }
+}
+
+impl Dictionary for Edict {
+    fn name(&self) -> &str {
+        "JMdict"
+    }
+
+    // Common words first, the order of the file otherwise.
+    fn lookup(&self, word: &str) -> Vec<Entry> {
+        let mut words: Vec<&Word> = match self.index.get(word) {
+            Some(ids) => ids.iter().map(|&id| &self.words[id]).collect(),
+            None      => { return vec![]; }
+        };
+        words.sort_by_key(|entry| !entry.common);
+
+        words.into_iter().map(|entry| {
+            if entry.writings.iter().any(|writing| writing == word) {
+                self::entry(entry, Some(word.to_owned()))
+            }
+            else {
+                self::entry(entry, entry.writings.first().cloned())
+            }
+        }).collect()
+    }
+
+    // Exact meanings first, then common words.
+    fn search(&self, query: &str) -> Vec<Entry> {
+        let query = words(query);
+        if query.is_empty() {
+            return vec![];
+        }
+
+        let mut words: Vec<(u8, &Word)> = self.words.iter().filter_map(|word| {
+            word.glosses.iter().filter_map(|gloss| relevance(gloss, &query)).min().map(|relevance| (relevance, word))
+        }).collect();
+        words.sort_by_key(|&(relevance, word)| (relevance, !word.common));
+
+        words.into_iter().map(|(_, word)| entry(word, word.writings.first().cloned())).collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{Edict, parse_line};
+    use super::super::dictionary::Dictionary;
+
+    #[test]
+    fn test_parse_line() {
+        let word = parse_line("桜;櫻(oK) [さくら(桜);サクラ] /(n) (1) cherry tree/(2) cherry blossom/(P)/EntL1381410X/").unwrap();
+        assert_eq!(vec!["桜", "櫻"],           word.writings);
+        assert_eq!(vec!["さくら", "サクラ"],   word.readings);
+        assert_eq!(vec!["cherry tree", "cherry blossom"], word.glosses);
+        assert!(word.common);
+
+        let word = parse_line("さくらんぼ /(n,uk) cherry (fruit)/EntL1381420X/").unwrap();
+        assert!(word.writings.is_empty());
+        assert_eq!(vec!["さくらんぼ"], word.readings);
+        assert_eq!(vec!["n", "uk"],    word.tags);
+
+        assert_eq!(None, parse_line("　？？？ /EDICT, EDICT2 and the JMdict are the property of the EDRDG/"));
+    }
+
+    #[test]
+    fn test_lookup() {
+        let edict = Edict::load("fixtures/edict2").unwrap();
+        assert!(!edict.is_empty());
+
+        let entries = edict.lookup("桜");
+        assert_eq!(1, entries.len());
+        assert_eq!("桜 (さくら - sakura): cherry tree; cherry blossom", entries[0].to_string());
+
+        assert_eq!("頑な (かたくな - katakuna): obstinate (usually written using kana alone)", edict.lookup("かたくな")[0].to_string());
+        assert!(edict.lookup("ぬ").is_empty());
+        assert!(Edict::load("fixtures/missing").is_err());
+    }
+
+    #[test]
+    fn test_search() {
+        let edict = Edict::load("fixtures/edict2").unwrap();
+
+        let entries: Vec<String> = edict.search("Cherry").iter().map(|entry| entry.to_string()).collect();
+        assert_eq!(vec!["さくらんぼ - sakuranbo: cherry (fruit) (usually written using kana alone)",
+                        "桜桃 (おうとう - outou): cherry (fruit)",
+                        "桜 (さくら - sakura): cherry tree; cherry blossom"], entries);
+
+        assert_eq!("頑張る (がんばる - ganbaru): to persevere; to persist; to keep at it; to insist on; to stick to",
+                   edict.search("persevere")[0].to_string());
+        assert_eq!(1, edict.search("keep at it").len());
+        assert!(edict.search("keep it").is_empty());
+        assert!(edict.search("   ").is_empty());
+    }
+}
@@ -0,0 +1,107 @@
+use std::fs::File;
+use std::io::Read;
+use std::collections::HashMap;
+use regex::Regex;
+
+lazy_static! {
+    // reading, sense number, form in the sentence and checked mark
+    static ref ANNOTATIONS: Regex = Regex::new(r"\([^)]*\)|\[[^\]]*\]|\{[^}]*\}|~").unwrap();
+    static ref READING:     Regex = Regex::new(r"\(([^)]*)\)").unwrap();
+}
+
+#[derive(PartialEq, Debug)]
+pub struct Sentence {
+    pub japanese: String,
+    pub english:  String
+}
+
+// The Tanaka corpus as exported by Tatoeba, each sentence followed by its headwords:
+//   A: 桜が咲いた。<TAB>The cherry blossoms have come out.#ID=73530_181013
+//   B: 桜(さくら){桜}~ が 咲く{咲いた}
+#[derive(Debug)]
+pub struct Examples {
+    sentences: Vec<Sentence>,
+    index:     HashMap<String, Vec<usize>>
+}
+
+impl Examples {
+    pub fn new() -> Examples {
+        Examples {
+            sentences: vec![],
+            index:     HashMap::new()
+        }
+    }
+
+    pub fn load(path: &str) -> Result<Examples, String> {
+        let mut content = String::new();
+        try!(File::open(path)
+             .and_then(|mut file| file.read_to_string(&mut content))
+             .map_err(|e| e.to_string()));
+
+        Ok(Examples::parse(&content))
+    }
+
+    pub fn parse(content: &str) -> Examples {
+        let mut examples = Examples::new();
+
+        for line in content.lines() {
+            if line.starts_with("A: ") {
+                let mut fields = line[3..].splitn(2, '\t');
+                let japanese   = fields.next().unwrap_or("").trim();
+                let english    = fields.next().unwrap_or("").split("#ID=").next().unwrap_or("").trim();
+
+                examples.sentences.push(Sentence {
+                    japanese: japanese.to_owned(),
+                    english:  english.to_owned()
+                });
+            }
+            else if line.starts_with("B: ") && !examples.sentences.is_empty() {
+                let id = examples.sentences.len() - 1;
+
+                // headwords are found by their reading too
+                for headword in line[3..].split_whitespace() {
+                    let reading = READING.captures(headword).and_then(|captures| captures.at(1)).map(|reading| reading.to_owned());
+
+                    for key in Some(ANNOTATIONS.replace_all(headword, "")).into_iter().chain(reading) {
+                        let ids = examples.index.entry(key).or_insert(vec![]);
+                        if !ids.contains(&id) {
+                            ids.push(id);
+                        }
+                    }
+                }
+            }
+        }
+
+        examples
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.sentences.is_empty()
+    }
+
+    pub fn get(&self, headword: &str) -> Vec<&Sentence> {
+        match self.index.get(headword) {
+            Some(ids) => ids.iter().map(|&id| &self.sentences[id]).collect(),
+            None      => vec![]
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{Examples, Sentence};
+
+    #[test]
+    fn test_examples() {
+        let examples = Examples::load("fixtures/examples.utf").unwrap();
+
+        assert_eq!(vec![&Sentence { japanese: "桜が咲いた。".to_owned(), english: "The cherry blossoms have come out.".to_owned() },
+                        &Sentence { japanese: "公園の桜は満開だ。".to_owned(), english: "The cherry trees in the park are in full bloom.".to_owned() }],
+                   examples.get("桜"));
+        assert_eq!(2, examples.get("犬").len());
+        assert_eq!(2, examples.get("いぬ").len());
+        assert_eq!(1, examples.get("頑張る").len());
+        assert!(examples.get("頑張った").is_empty());
+        assert!(Examples::load("fixtures/missing.utf").is_err());
+    }
+}
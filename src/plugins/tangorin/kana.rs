@@ -0,0 +1,266 @@
+// Hepburn comes first, so that it is what kana are turned back into.
+// Kunrei and Nihon-shiki spellings follow, then what IMEs accept.
+const SYLLABLES: &'static [(&'static str, &'static str)] = &[
+    ("a",   "あ"), ("i",   "い"), ("u",   "う"), ("e",   "え"), ("o",   "お"),
+    ("ka",  "か"), ("ki",  "き"), ("ku",  "く"), ("ke",  "け"), ("ko",  "こ"),
+    ("sa",  "さ"), ("shi", "し"), ("su",  "す"), ("se",  "せ"), ("so",  "そ"),
+    ("ta",  "た"), ("chi", "ち"), ("tsu", "つ"), ("te",  "て"), ("to",  "と"),
+    ("na",  "な"), ("ni",  "に"), ("nu",  "ぬ"), ("ne",  "ね"), ("no",  "の"),
+    ("ha",  "は"), ("hi",  "ひ"), ("fu",  "ふ"), ("he",  "へ"), ("ho",  "ほ"),
+    ("ma",  "ま"), ("mi",  "み"), ("mu",  "む"), ("me",  "め"), ("mo",  "も"),
+    ("ya",  "や"),                ("yu",  "ゆ"),                ("yo",  "よ"),
+    ("ra",  "ら"), ("ri",  "り"), ("ru",  "る"), ("re",  "れ"), ("ro",  "ろ"),
+    ("wa",  "わ"),                                              ("wo",  "を"),
+    ("ga",  "が"), ("gi",  "ぎ"), ("gu",  "ぐ"), ("ge",  "げ"), ("go",  "ご"),
+    ("za",  "ざ"), ("ji",  "じ"), ("zu",  "ず"), ("ze",  "ぜ"), ("zo",  "ぞ"),
+    ("da",  "だ"), ("ji",  "ぢ"), ("zu",  "づ"), ("de",  "で"), ("do",  "ど"),
+    ("ba",  "ば"), ("bi",  "び"), ("bu",  "ぶ"), ("be",  "べ"), ("bo",  "ぼ"),
+    ("pa",  "ぱ"), ("pi",  "ぴ"), ("pu",  "ぷ"), ("pe",  "ぺ"), ("po",  "ぽ"),
+    ("kya", "きゃ"), ("kyu", "きゅ"), ("kyo", "きょ"),
+    ("sha", "しゃ"), ("shu", "しゅ"), ("sho", "しょ"), ("she", "しぇ"),
+    ("cha", "ちゃ"), ("chu", "ちゅ"), ("cho", "ちょ"), ("che", "ちぇ"),
+    ("nya", "にゃ"), ("nyu", "にゅ"), ("nyo", "にょ"),
+    ("hya", "ひゃ"), ("hyu", "ひゅ"), ("hyo", "ひょ"),
+    ("mya", "みゃ"), ("myu", "みゅ"), ("myo", "みょ"),
+    ("rya", "りゃ"), ("ryu", "りゅ"), ("ryo", "りょ"),
+    ("gya", "ぎゃ"), ("gyu", "ぎゅ"), ("gyo", "ぎょ"),
+    ("ja",  "じゃ"), ("ju",  "じゅ"), ("jo",  "じょ"), ("je",  "じぇ"),
+    ("ja",  "ぢゃ"), ("ju",  "ぢゅ"), ("jo",  "ぢょ"),
+    ("bya", "びゃ"), ("byu", "びゅ"), ("byo", "びょ"),
+    ("pya", "ぴゃ"), ("pyu", "ぴゅ"), ("pyo", "ぴょ"),
+    ("fa",  "ふぁ"), ("fi",  "ふぃ"), ("fe",  "ふぇ"), ("fo",  "ふぉ"), ("vu",  "ゔ"),
+
+    // Kunrei and Nihon-shiki
+    ("si",  "し"), ("ti",  "ち"), ("tu",  "つ"), ("hu",  "ふ"), ("zi",  "じ"),
+    ("di",  "ぢ"), ("du",  "づ"),
+    ("sya", "しゃ"), ("syu", "しゅ"), ("syo", "しょ"),
+    ("tya", "ちゃ"), ("tyu", "ちゅ"), ("tyo", "ちょ"),
+    ("zya", "じゃ"), ("zyu", "じゅ"), ("zyo", "じょ"),
+    ("dya", "ぢゃ"), ("dyu", "ぢゅ"), ("dyo", "ぢょ"),
+
+    // Hepburn for loanwords, after Kunrei so that "ti" is still ち when typed
+    ("ti",  "てぃ"), ("di",  "でぃ"), ("tu",  "とぅ"),
+
+    // IMEs
+    ("thi", "てぃ"), ("dhi", "でぃ"), ("twu", "とぅ"),
+    ("jya", "じゃ"), ("jyu", "じゅ"), ("jyo", "じょ"),
+    ("cya", "ちゃ"), ("cyu", "ちゅ"), ("cyo", "ちょ"),
+    ("xa",  "ぁ"), ("xi",  "ぃ"), ("xu",  "ぅ"), ("xe",  "ぇ"), ("xo",  "ぉ"),
+    ("la",  "ぁ"), ("li",  "ぃ"), ("lu",  "ぅ"), ("le",  "ぇ"), ("lo",  "ぉ"),
+    ("xya", "ゃ"), ("xyu", "ゅ"), ("xyo", "ょ"),
+    ("lya", "ゃ"), ("lyu", "ゅ"), ("lyo", "ょ"),
+    ("xtsu", "っ"), ("xtu", "っ"), ("ltu", "っ"), ("xwa", "ゎ"),
+    ("-",   "ー")
+];
+
+const LONG_VOWELS: &'static str = "āīūēōâîûêôĀĪŪĒŌÂÎÛÊÔ";
+
+// Whether the text could be romaji, macrons and circumflexes included.
+pub fn is_latin(text: &str) -> bool {
+    text.chars().all(|c| (c as u32) < 0x80 || LONG_VOWELS.contains(c))
+}
+
+fn is_vowel(c: char) -> bool {
+    "aeiou".contains(c)
+}
+
+// Long vowels may be written with macrons (Hepburn) or circumflexes (Kunrei).
+fn expand_long_vowels(romaji: &str) -> String {
+    romaji.to_lowercase().chars().map(|c| match c {
+        'ā' | 'â' => "aa".to_owned(),
+        'ī' | 'î' => "ii".to_owned(),
+        'ū' | 'û' => "uu".to_owned(),
+        'ē' | 'ê' => "ee".to_owned(),
+        'ō' | 'ô' => "ou".to_owned(),
+        c         => c.to_string()
+    }).collect()
+}
+
+// None when something that is not romaji is found.
+pub fn to_hiragana(romaji: &str) -> Option<String> {
+    let romaji = expand_long_vowels(romaji);
+    let chars: Vec<char> = romaji.chars().collect();
+
+    let mut kana = String::new();
+    let mut i    = 0;
+    while i < chars.len() {
+        let c    = chars[i];
+        let next = chars.get(i + 1).cloned();
+
+        if c == ' ' {
+            kana.push(' ');
+            i += 1;
+        }
+        // n' keeps "kan'i" from being read as "kani"
+        else if c == 'n' && next == Some('\'') {
+            kana.push('ん');
+            i += 2;
+        }
+        else if c == 'n' && next.map(|next| !is_vowel(next) && next != 'y').unwrap_or(true) {
+            kana.push('ん');
+            i += 1;
+        }
+        // Hepburn writes "shimbun" for しんぶん
+        else if c == 'm' && next.map(|next| "bmp".contains(next)).unwrap_or(false) {
+            kana.push('ん');
+            i += 1;
+        }
+        // small tsu: doubled consonants, and "tch" in "matcha"
+        else if (Some(c) == next && !is_vowel(c) && c.is_alphabetic()) || (c == 't' && next == Some('c')) {
+            kana.push('っ');
+            i += 1;
+        }
+        else {
+            let syllable = (1..5).rev()
+                .filter(|&length| i + length <= chars.len())
+                .filter_map(|length| {
+                    let candidate: String = chars[i..i + length].iter().cloned().collect();
+                    SYLLABLES.iter().find(|&&(romaji, _)| romaji == candidate).map(|&(_, kana)| (length, kana))
+                })
+                .next();
+
+            match syllable {
+                Some((length, syllable)) => {
+                    kana.push_str(syllable);
+                    i += length;
+                },
+                None => { return None; }
+            }
+        }
+    }
+
+    Some(kana)
+}
+
+pub fn to_katakana(hiragana: &str) -> String {
+    hiragana.chars().map(|c| match c {
+        'ぁ'...'ゖ' => ::std::char::from_u32(c as u32 + 0x60).unwrap_or(c),
+        c           => c
+    }).collect()
+}
+
+pub fn to_hiragana_from_katakana(katakana: &str) -> String {
+    katakana.chars().map(|c| match c {
+        'ァ'...'ヶ' => ::std::char::from_u32(c as u32 - 0x60).unwrap_or(c),
+        c           => c
+    }).collect()
+}
+
+// Hepburn, with long vowels written twice ("raamen", "toukyou").
+// Anything that is not kana, like kanji, is left as it is.
+pub fn to_romaji(kana: &str) -> String {
+    let chars: Vec<char> = to_hiragana_from_katakana(kana).chars().collect();
+
+    let mut romaji     = String::new();
+    let mut small_tsu  = false;
+    let mut i          = 0;
+    while i < chars.len() {
+        let syllable = (1..3).rev()
+            .filter(|&length| i + length <= chars.len())
+            .filter_map(|length| {
+                let candidate: String = chars[i..i + length].iter().cloned().collect();
+                SYLLABLES.iter()
+                    .find(|&&(romaji, kana)| kana == candidate && romaji != "-")
+                    .map(|&(romaji, _)| (length, romaji))
+            })
+            .next();
+
+        match (chars[i], syllable) {
+            ('っ', _) => {
+                small_tsu = true;
+                i += 1;
+                continue;
+            },
+            ('ん', _) => {
+                romaji.push('n');
+
+                // "kan'i" and "kin'yuu"
+                let next = chars.get(i + 1).map(|c| to_romaji(&c.to_string()));
+                if next.map(|next| next.starts_with(|c: char| is_vowel(c) || c == 'y')).unwrap_or(false) {
+                    romaji.push('\'');
+                }
+
+                i += 1;
+            },
+            ('ー', _) => {
+                let vowel = romaji.chars().rev().find(|&c| is_vowel(c));
+                if let Some(vowel) = vowel {
+                    romaji.push(vowel);
+                }
+
+                i += 1;
+            },
+            (_, Some((length, syllable))) => {
+                if small_tsu {
+                    romaji.push(if syllable.starts_with("ch") { 't' } else { syllable.chars().next().unwrap() });
+                }
+
+                romaji.push_str(syllable);
+                i += length;
+            },
+            (c, None) => {
+                romaji.push(c);
+                i += 1;
+            }
+        }
+
+        small_tsu = false;
+    }
+
+    romaji
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{to_hiragana, to_katakana, to_romaji, is_latin};
+
+    #[test]
+    fn test_to_hiragana() {
+        assert_eq!(Some("さくら".to_owned()),       to_hiragana("sakura"));
+        assert_eq!(Some("しんぶん".to_owned()),     to_hiragana("shimbun"));
+        assert_eq!(Some("しんぶん".to_owned()),     to_hiragana("sinbun"));
+        assert_eq!(Some("きって".to_owned()),       to_hiragana("kitte"));
+        assert_eq!(Some("まっちゃ".to_owned()),     to_hiragana("matcha"));
+        assert_eq!(Some("かんい".to_owned()),       to_hiragana("kan'i"));
+        assert_eq!(Some("かに".to_owned()),         to_hiragana("kani"));
+        assert_eq!(Some("こんにちは".to_owned()),   to_hiragana("konnichiha"));
+        assert_eq!(Some("とうきょう".to_owned()),   to_hiragana("Tōkyō"));
+        assert_eq!(Some("ちゃ".to_owned()),         to_hiragana("tya"));
+        assert_eq!(Some("らーめん".to_owned()),     to_hiragana("ra-men"));
+        assert_eq!(Some("ちから".to_owned()),       to_hiragana("tikara"));
+        assert_eq!(Some("つくえ".to_owned()),       to_hiragana("tukue"));
+        assert_eq!(Some("はなぢ".to_owned()),       to_hiragana("hanadi"));
+        assert_eq!(Some("ぱーてぃー".to_owned()),   to_hiragana("pa-thi-"));
+        assert_eq!(None,                            to_hiragana("dog"));
+        assert_eq!(None,                            to_hiragana("cherry blossom"));
+    }
+
+    #[test]
+    fn test_to_katakana() {
+        assert_eq!("ラーメン", to_katakana("らーめん"));
+        assert_eq!("サクラ 桜", to_katakana("さくら 桜"));
+    }
+
+    #[test]
+    fn test_to_romaji() {
+        assert_eq!("sakura",     to_romaji("さくら"));
+        assert_eq!("kitte",      to_romaji("きって"));
+        assert_eq!("matcha",     to_romaji("まっちゃ"));
+        assert_eq!("kan'i",      to_romaji("かんい"));
+        assert_eq!("shinbun",    to_romaji("しんぶん"));
+        assert_eq!("raamen",     to_romaji("ラーメン"));
+        assert_eq!("toukyou",    to_romaji("とうきょう"));
+        assert_eq!("ganbaru",    to_romaji("がんばる"));
+        assert_eq!("桜 sakura",  to_romaji("桜 さくら"));
+        assert_eq!("paatii",     to_romaji("パーティー"));
+    }
+
+    #[test]
+    fn test_is_latin() {
+        assert!(is_latin("sakura"));
+        assert!(is_latin("Tōkyō"));
+        assert!(is_latin("kan'i"));
+        assert!(!is_latin("桜"));
+        assert!(!is_latin("さくら"));
+    }
+}
@@ -0,0 +1,171 @@
+use std::fmt;
+use std::fs::File;
+use std::io::Read;
+use std::char;
+use std::collections::HashMap;
+use regex::Regex;
+
+lazy_static! {
+    static ref CHARACTER: Regex = Regex::new(r"(?s)<character>(.*?)</character>").unwrap();
+    static ref LITERAL:   Regex = Regex::new(r"<literal>([^<]+)</literal>").unwrap();
+    static ref READING:   Regex = Regex::new(r#"<reading r_type="ja_(on|kun)">([^<]+)</reading>"#).unwrap();
+    static ref MEANING:   Regex = Regex::new(r"<meaning>([^<]+)</meaning>").unwrap();
+    static ref RADICAL:   Regex = Regex::new(r#"<rad_value rad_type="classical">(\d+)</rad_value>"#).unwrap();
+    static ref STROKES:   Regex = Regex::new(r"<stroke_count>(\d+)</stroke_count>").unwrap();
+    static ref GRADE:     Regex = Regex::new(r"<grade>(\d+)</grade>").unwrap();
+    static ref JLPT:      Regex = Regex::new(r"<jlpt>(\d+)</jlpt>").unwrap();
+}
+
+#[derive(PartialEq, Debug)]
+pub struct Kanji {
+    pub literal:  char,
+    pub on:       Vec<String>,
+    pub kun:      Vec<String>,
+    pub meanings: Vec<String>,
+    pub strokes:  Option<u32>,
+    pub radical:  Option<u32>,
+    pub grade:    Option<u32>,
+    pub jlpt:     Option<u32>
+}
+
+impl fmt::Display for Kanji {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        try!(write!(f, "{}: {}", self.literal, self.meanings.join(", ")));
+
+        if !self.on.is_empty() {
+            try!(write!(f, " | on: {}", self.on.join(", ")));
+        }
+
+        if !self.kun.is_empty() {
+            try!(write!(f, " | kun: {}", self.kun.join(", ")));
+        }
+
+        let mut details = vec![];
+        if let Some(strokes) = self.strokes {
+            details.push(format!("{} stroke{}", strokes, if strokes == 1 { "" } else { "s" }));
+        }
+
+        if let Some(radical) = self.radical {
+            // the Kangxi Radicals block follows the classical numbering
+            let symbol = if radical >= 1 && radical <= 214 { char::from_u32(0x2F00 + radical - 1) } else { None };
+            match symbol {
+                Some(symbol) => details.push(format!("radical {} ({})", radical, symbol)),
+                None         => details.push(format!("radical {}", radical))
+            }
+        }
+
+        match self.grade {
+            Some(grade @ 1...6) => details.push(format!("grade {}", grade)),
+            Some(8)             => details.push("jōyō".to_owned()),
+            Some(9) | Some(10)  => details.push("jinmeiyō".to_owned()),
+            _                   => {}
+        }
+
+        if let Some(jlpt) = self.jlpt {
+            details.push(format!("JLPT {}", jlpt));
+        }
+
+        if details.is_empty() {
+            Ok(())
+        }
+        else {
+            write!(f, " | {}", details.join(", "))
+        }
+    }
+}
+
+fn unescape(text: &str) -> String {
+    text.replace("&lt;", "<").replace("&gt;", ">").replace("&quot;", "\"").replace("&apos;", "'").replace("&amp;", "&")
+}
+
+fn number(regex: &Regex, xml: &str) -> Option<u32> {
+    regex.captures(xml).and_then(|captures| captures.at(1)).and_then(|value| value.parse().ok())
+}
+
+fn parse_character(xml: &str) -> Option<Kanji> {
+    let literal = try_option!(LITERAL.captures(xml).and_then(|captures| captures.at(1)));
+
+    let mut kanji = Kanji {
+        literal:  try_option!(literal.chars().next()),
+        on:       vec![],
+        kun:      vec![],
+        meanings: MEANING.captures_iter(xml).filter_map(|captures| captures.at(1)).map(unescape).collect(),
+        strokes:  number(&STROKES, xml),
+        radical:  number(&RADICAL, xml),
+        grade:    number(&GRADE,   xml),
+        jlpt:     number(&JLPT,    xml)
+    };
+
+    for captures in READING.captures_iter(xml) {
+        let reading = unescape(captures.at(2).unwrap_or(""));
+        match captures.at(1) {
+            Some("on") => kanji.on.push(reading),
+            _          => kanji.kun.push(reading)
+        }
+    }
+
+    Some(kanji)
+}
+
+// KANJIDIC2, parsed once and indexed by character.
+#[derive(Debug)]
+pub struct Kanjidic {
+    characters: HashMap<char, Kanji>
+}
+
+impl Kanjidic {
+    pub fn new() -> Kanjidic {
+        Kanjidic { characters: HashMap::new() }
+    }
+
+    pub fn load(path: &str) -> Result<Kanjidic, String> {
+        let mut content = String::new();
+        try!(File::open(path)
+             .and_then(|mut file| file.read_to_string(&mut content))
+             .map_err(|e| e.to_string()));
+
+        Ok(Kanjidic::parse(&content))
+    }
+
+    pub fn parse(xml: &str) -> Kanjidic {
+        Kanjidic {
+            characters: CHARACTER.captures_iter(xml)
+                .filter_map(|captures| captures.at(1))
+                .filter_map(parse_character)
+                .map(|kanji| (kanji.literal, kanji))
+                .collect()
+        }
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.characters.is_empty()
+    }
+
+    pub fn get(&self, literal: char) -> Option<&Kanji> {
+        self.characters.get(&literal)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::Kanjidic;
+
+    #[test]
+    fn test_kanjidic() {
+        let kanjidic = Kanjidic::load("fixtures/kanjidic2.xml").unwrap();
+
+        let kanji = kanjidic.get('日').unwrap();
+        assert_eq!(vec!["ニチ", "ジツ"],         kanji.on);
+        assert_eq!(vec!["ひ", "-び", "-か"],     kanji.kun);
+        assert_eq!(Some(4),                      kanji.strokes);
+        assert_eq!(Some(72),                     kanji.radical);
+
+        assert_eq!("桜: cherry | on: オウ, ヨウ | kun: さくら | 10 strokes, radical 75 (⽊), grade 5, JLPT 2",
+                   kanjidic.get('桜').unwrap().to_string());
+        assert_eq!("乂: mow, cut grass, subdue & govern | on: ガイ | kun: か.る | 2 strokes, radical 4 (⼃)",
+                   kanjidic.get('乂').unwrap().to_string());
+
+        assert_eq!(None, kanjidic.get('猫'));
+        assert!(Kanjidic::load("fixtures/missing.xml").is_err());
+    }
+}
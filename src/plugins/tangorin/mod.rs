@@ -0,0 +1,379 @@
+use std::io;
+use irc::client::prelude::*;
+use regex::Regex;
+use time;
+use plugin::{self, Plugin};
+
+lazy_static! {
+    static ref RE:      Regex = Regex::new(r"^!tangorin\s+(\S.*?)\s*$").unwrap();
+    static ref KANJI:   Regex = Regex::new(r"^!kanji\s+(\S)\s*$").unwrap();
+    static ref MORE:    Regex = Regex::new(r"^!more\s*$").unwrap();
+    static ref KANA:    Regex = Regex::new(r"^!(kana|romaji)\s+(\S.*?)\s*$").unwrap();
+    static ref EXAMPLE: Regex = Regex::new(r"^!example\s+(\S+)(?:\s+(\d+))?\s*$").unwrap();
+}
+
+macro_rules! try_option {
+    ($e:expr) => {
+        match $e {
+            Some(v) => v,
+            None    => { return None; }
+        }
+    }
+}
+
+mod dictionary;
+mod scraper;
+mod edict;
+mod kanjidic;
+mod pages;
+mod kana;
+mod examples;
+
+use self::dictionary::Dictionaries;
+use self::edict::Edict;
+use self::kanjidic::Kanjidic;
+use self::pages::Pages;
+use self::examples::Examples;
+
+register_plugin!(Tangorin, dictionaries: Dictionaries, kanjidic: Kanjidic, pages: Pages, examples: Examples);
+
+impl Tangorin {
+    fn grep_query(&self, msg: &str) -> Option<String> {
+        match RE.captures(msg) {
+            Some(captures) => captures.at(1).map(|e| e.to_owned()),
+            None           => None
+        }
+    }
+
+    // How many results are shown at once, and for how many seconds !more remembers the rest.
+    fn page_size(&self, server: &IrcServer) -> (usize, i64) {
+        (plugin::get_option(server, "tangorin.results").and_then(|results| results.parse().ok()).unwrap_or(3),
+         plugin::get_option(server, "tangorin.more_ttl").and_then(|ttl| ttl.parse().ok()).unwrap_or(300))
+    }
+
+    fn tangorin(&mut self, server: &IrcServer, message: &Message, target: &str, msg: &str) -> io::Result<()> {
+        let query = match self.grep_query(msg) {
+            Some(query) => query,
+            None        => { return Ok(()); }
+        };
+
+        let nickname = message.source_nickname().unwrap_or("");
+        let (_, ttl) = self.page_size(server);
+
+        match self.dictionaries.lookup(&query) {
+            Some((name, entries)) => self.pages.insert(target, nickname, name, entries, time::get_time().sec, ttl),
+            None                  => { return Ok(()); }
+        }
+
+        self.more(server, message, target)
+    }
+
+    fn more(&mut self, server: &IrcServer, message: &Message, target: &str) -> io::Result<()> {
+        let nickname     = message.source_nickname().unwrap_or("");
+        let (count, ttl) = self.page_size(server);
+
+        let (name, entries, left) = match self.pages.next(target, nickname, count, time::get_time().sec, ttl) {
+            Some(page) => page,
+            None       => { return server.send_privmsg(target, "There is nothing more to show"); }
+        };
+
+        for (i, entry) in entries.iter().enumerate() {
+            let more = if i + 1 == entries.len() && left > 0 { format!(" ({} more, !more)", left) } else { String::new() };
+            try!(server.send_privmsg(target, &format!("[{}] {}{}", name, entry, more)));
+        }
+
+        Ok(())
+    }
+
+    fn kana(&self, server: &IrcServer, target: &str, msg: &str) -> io::Result<()> {
+        let (command, text) = match KANA.captures(msg) {
+            Some(captures) => (captures.at(1).unwrap_or(""), captures.at(2).unwrap_or("")),
+            None           => { return Ok(()); }
+        };
+
+        if command == "romaji" {
+            return server.send_privmsg(target, &kana::to_romaji(text));
+        }
+
+        match kana::to_hiragana(text) {
+            Some(hiragana) => server.send_privmsg(target, &format!("{} / {}", hiragana, kana::to_katakana(&hiragana))),
+            None           => server.send_privmsg(target, &format!("Sorry, I can't read {} as romaji", text))
+        }
+    }
+
+    // `!example 桜` picks a sentence at random, `!example 桜 2` the second one.
+    fn example(&self, server: &IrcServer, target: &str, msg: &str) -> io::Result<()> {
+        let (word, number) = match EXAMPLE.captures(msg) {
+            Some(captures) => (captures.at(1).unwrap_or(""), captures.at(2).and_then(|number| number.parse::<usize>().ok())),
+            None           => { return Ok(()); }
+        };
+
+        if self.examples.is_empty() {
+            return server.send_privmsg(target, "Sorry, no example sentences have been loaded (tangorin.examples)");
+        }
+
+        let mut sentences = self.examples.get(word);
+        if sentences.is_empty() {
+            if let Some(hiragana) = kana::to_hiragana(word) {
+                sentences = self.examples.get(&hiragana);
+            }
+        }
+
+        if sentences.is_empty() {
+            return server.send_privmsg(target, &format!("[Tatoeba] No examples for {}", word));
+        }
+
+        let i = match number {
+            Some(number) if number >= 1 && number <= sentences.len() => number - 1,
+            Some(_) => { return server.send_privmsg(target, &format!("[Tatoeba] There are {} examples for {}", sentences.len(), word)); },
+            None    => time::precise_time_ns() as usize % sentences.len()
+        };
+
+        server.send_privmsg(target, &format!("[Tatoeba] {} — {} ({}/{})",
+                                             sentences[i].japanese, sentences[i].english, i + 1, sentences.len()))
+    }
+
+    fn kanji(&self, server: &IrcServer, target: &str, msg: &str) -> io::Result<()> {
+        let literal = match KANJI.captures(msg).and_then(|captures| captures.at(1)).and_then(|kanji| kanji.chars().next()) {
+            Some(literal) => literal,
+            None          => { return Ok(()); }
+        };
+
+        if self.kanjidic.is_empty() {
+            return server.send_privmsg(target, "Sorry, no KANJIDIC has been loaded (tangorin.kanjidic)");
+        }
+
+        match self.kanjidic.get(literal) {
+            Some(kanji) => server.send_privmsg(target, &format!("[KANJIDIC] {}", kanji)),
+            None        => server.send_privmsg(target, &format!("[KANJIDIC] {} is not a kanji I know", literal))
+        }
+    }
+}
+
+impl Plugin for Tangorin {
+    // `tangorin.edict` points to a JMdict file in the EDICT2 format, which is looked up
+    // before tangorin.com. `tangorin.online = false` leaves the local dictionary alone.
+    // `tangorin.kanjidic` points to KANJIDIC2, for !kanji, and `tangorin.examples`
+    // to the Tanaka corpus from Tatoeba (examples.utf), for !example.
+    fn configure(&mut self, server: &IrcServer) {
+        if plugin::get_option(server, "tangorin.online") == Some("false") {
+            self.dictionaries.backends.clear();
+        }
+
+        if let Some(path) = plugin::get_option(server, "tangorin.edict") {
+            match Edict::load(path) {
+                Ok(edict) => self.dictionaries.backends.insert(0, Box::new(edict)),
+                Err(e)    => println!("Tangorin: cannot load {} ({}), skipping it", path, e)
+            }
+        }
+
+        if let Some(path) = plugin::get_option(server, "tangorin.kanjidic") {
+            match Kanjidic::load(path) {
+                Ok(kanjidic) => self.kanjidic = kanjidic,
+                Err(e)       => println!("Tangorin: cannot load {} ({}), skipping it", path, e)
+            }
+        }
+
+        if let Some(path) = plugin::get_option(server, "tangorin.examples") {
+            match Examples::load(path) {
+                Ok(examples) => self.examples = examples,
+                Err(e)       => println!("Tangorin: cannot load {} ({}), skipping it", path, e)
+            }
+        }
+    }
+
+    fn is_allowed(&self, _: &IrcServer, message: &Message) -> bool {
+        match message.command {
+            Command::PRIVMSG(_, ref msg) => {
+                RE.is_match(msg) || KANJI.is_match(msg) || MORE.is_match(msg) || KANA.is_match(msg) || EXAMPLE.is_match(msg)
+            },
+            _ => false
+        }
+    }
+
+    fn execute(&mut self, server: &IrcServer, message: &Message) -> io::Result<()> {
+        match message.command {
+            Command::PRIVMSG(ref target, ref msg) => {
+                if KANJI.is_match(msg) {
+                    self.kanji(server, target, msg)
+                }
+                else if EXAMPLE.is_match(msg) {
+                    self.example(server, target, msg)
+                }
+                else if KANA.is_match(msg) {
+                    self.kana(server, target, msg)
+                }
+                else if MORE.is_match(msg) {
+                    self.more(server, message, target)
+                }
+                else {
+                    self.tangorin(server, message, target, msg)
+                }
+            },
+            _ => Ok(())
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use ::tests::{make_server, make_server_with_options, get_server_value};
+
+    use irc::client::prelude::*;
+
+    use plugin::Plugin;
+    use super::Tangorin;
+
+    #[test]
+    fn test_tangorin() {
+        let     server = make_server("PRIVMSG test :!tangorin 桜\r\n");
+        let mut plugin = Tangorin::new();
+
+        for message in server.iter() {
+            let message = message.unwrap();
+            assert!(plugin.is_allowed(&server, &message));
+            assert!(plugin.execute(&server, &message).is_ok());
+        }
+
+        assert_eq!("PRIVMSG test :[Tangorin] 桜 (さくら - sakura): cherry tree;  cherry blossom\r\n",
+                   &*get_server_value(&server));
+    }
+
+    #[test]
+    fn test_tangorin_write_explanation() {
+        let     server = make_server("PRIVMSG test :!tangorin 頑\r\n");
+        let mut plugin = Tangorin::new();
+
+        for message in server.iter() {
+            let message = message.unwrap();
+            assert!(plugin.is_allowed(&server, &message));
+            assert!(plugin.execute(&server, &message).is_ok());
+        }
+        assert_eq!("PRIVMSG test :[Tangorin] 頑な (かたくな - katakuna): obstinate (usually written using kana alone)\r\n",
+                   &*get_server_value(&server));
+    }
+
+    #[test]
+    fn test_tangorin_offline() {
+        let     server = make_server_with_options("PRIVMSG test :!tangorin 頑張る\r\nPRIVMSG test :!tangorin ぬ\r\n",
+                                                  &[("tangorin.edict", "fixtures/edict2"), ("tangorin.online", "false")]);
+        let mut plugin = Tangorin::new();
+
+        plugin.configure(&server);
+        for message in server.iter() {
+            let message = message.unwrap();
+            assert!(plugin.is_allowed(&server, &message));
+            assert!(plugin.execute(&server, &message).is_ok());
+        }
+
+        assert_eq!("PRIVMSG test :[JMdict] 頑張る (がんばる - ganbaru): to persevere; to persist; to keep at it; to insist on; to stick to\r\n",
+                   &*get_server_value(&server));
+    }
+
+    #[test]
+    fn test_tangorin_english() {
+        let     server = make_server_with_options("PRIVMSG test :!tangorin cherry\r\nPRIVMSG test :!more\r\nPRIVMSG test :!more\r\n",
+                                                  &[("tangorin.edict", "fixtures/edict2"), ("tangorin.online", "false"),
+                                                    ("tangorin.results", "2")]);
+        let mut plugin = Tangorin::new();
+
+        plugin.configure(&server);
+        for message in server.iter() {
+            let message = message.unwrap();
+            assert!(plugin.is_allowed(&server, &message));
+            assert!(plugin.execute(&server, &message).is_ok());
+        }
+
+        assert_eq!("PRIVMSG test :[JMdict] さくらんぼ - sakuranbo: cherry (fruit) (usually written using kana alone)\r\n\
+                    PRIVMSG test :[JMdict] 桜桃 (おうとう - outou): cherry (fruit) (1 more, !more)\r\n\
+                    PRIVMSG test :[JMdict] 桜 (さくら - sakura): cherry tree; cherry blossom\r\n\
+                    PRIVMSG test :There is nothing more to show\r\n",
+                   &*get_server_value(&server));
+    }
+
+    #[test]
+    fn test_tangorin_romaji() {
+        let     server = make_server_with_options("PRIVMSG test :!tangorin sakura\r\nPRIVMSG test :!tangorin Tōkyō\r\n\
+                                                   PRIVMSG test :!kana Tōkyō\r\nPRIVMSG test :!kana tikara\r\n\
+                                                   PRIVMSG test :!kana dog\r\nPRIVMSG test :!romaji ラーメン\r\n",
+                                                  &[("tangorin.edict", "fixtures/edict2"), ("tangorin.online", "false")]);
+        let mut plugin = Tangorin::new();
+
+        plugin.configure(&server);
+        for message in server.iter() {
+            let message = message.unwrap();
+            assert!(plugin.is_allowed(&server, &message));
+            assert!(plugin.execute(&server, &message).is_ok());
+        }
+
+        assert_eq!("PRIVMSG test :[JMdict] 桜 (さくら - sakura): cherry tree; cherry blossom\r\n\
+                    PRIVMSG test :[JMdict] 東京 (とうきょう - toukyou): Tokyo\r\n\
+                    PRIVMSG test :とうきょう / トウキョウ\r\n\
+                    PRIVMSG test :ちから / チカラ\r\n\
+                    PRIVMSG test :Sorry, I can't read dog as romaji\r\n\
+                    PRIVMSG test :raamen\r\n",
+                   &*get_server_value(&server));
+    }
+
+    #[test]
+    fn test_example() {
+        let     server = make_server_with_options("PRIVMSG test :!example 桜 2\r\nPRIVMSG test :!example inu 1\r\n\
+                                                   PRIVMSG test :!example 桜 3\r\nPRIVMSG test :!example 花\r\n",
+                                                  &[("tangorin.examples", "fixtures/examples.utf")]);
+        let mut plugin = Tangorin::new();
+
+        plugin.configure(&server);
+        for message in server.iter() {
+            let message = message.unwrap();
+            assert!(plugin.is_allowed(&server, &message));
+            assert!(plugin.execute(&server, &message).is_ok());
+        }
+
+        assert_eq!("PRIVMSG test :[Tatoeba] 公園の桜は満開だ。 — The cherry trees in the park are in full bloom. (2/2)\r\n\
+                    PRIVMSG test :[Tatoeba] 犬が好きです。 — I like dogs. (1/2)\r\n\
+                    PRIVMSG test :[Tatoeba] There are 2 examples for 桜\r\n\
+                    PRIVMSG test :[Tatoeba] No examples for 花\r\n",
+                   &*get_server_value(&server));
+    }
+
+    #[test]
+    fn test_kanji() {
+        let     server = make_server_with_options("PRIVMSG test :!kanji 犬\r\nPRIVMSG test :!kanji 猫\r\nPRIVMSG test :!kanji 犬猫\r\n",
+                                                  &[("tangorin.kanjidic", "fixtures/kanjidic2.xml")]);
+        let mut plugin = Tangorin::new();
+
+        plugin.configure(&server);
+        for message in server.iter() {
+            let message = message.unwrap();
+            if plugin.is_allowed(&server, &message) {
+                assert!(plugin.execute(&server, &message).is_ok());
+            }
+        }
+
+        assert_eq!("PRIVMSG test :[KANJIDIC] 犬: dog | on: ケン | kun: いぬ, いぬ- | 4 strokes, radical 94 (⽝), grade 1, JLPT 3\r\n\
+                    PRIVMSG test :[KANJIDIC] 猫 is not a kanji I know\r\n",
+                   &*get_server_value(&server));
+    }
+
+    #[test]
+    fn test_tangorin_missing_argument() {
+        let server = make_server("PRIVMSG test :!tangorin            \r\n");
+        let plugin = Tangorin::new();
+
+        for message in server.iter() {
+            let message = message.unwrap();
+            assert!(!plugin.is_allowed(&server, &message));
+        }
+    }
+
+    #[test]
+    fn test_tangorin_not_called() {
+        let server = make_server("PRIVMSG test :httplol\r\n");
+        let plugin = Tangorin::new();
+
+        for message in server.iter() {
+            let message = message.unwrap();
+            assert!(!plugin.is_allowed(&server, &message));
+        }
+    }
+}
@@ -0,0 +1,89 @@
+use std::cmp;
+use std::collections::HashMap;
+use super::dictionary::Entry;
+
+#[derive(Debug)]
+struct Page {
+    name:       String,
+    entries:    Vec<Entry>,
+    expires_at: i64
+}
+
+// What is left of the last lookup of each nick in each channel, until nobody
+// asks for !more for a while.
+#[derive(Debug)]
+pub struct Pages {
+    pages: HashMap<(String, String), Page>
+}
+
+impl Pages {
+    pub fn new() -> Pages {
+        Pages { pages: HashMap::new() }
+    }
+
+    pub fn insert(&mut self, target: &str, nickname: &str, name: &str, entries: Vec<Entry>, now: i64, ttl: i64) {
+        self.pages.retain(|_, page| page.expires_at > now);
+        self.pages.insert((target.to_owned(), nickname.to_owned()), Page {
+            name:       name.to_owned(),
+            entries:    entries,
+            expires_at: now + ttl
+        });
+    }
+
+    // The next `count` entries, the dictionary they come from and how many are left.
+    pub fn next(&mut self, target: &str, nickname: &str, count: usize, now: i64, ttl: i64) -> Option<(String, Vec<Entry>, usize)> {
+        let key = (target.to_owned(), nickname.to_owned());
+
+        let (result, exhausted) = match self.pages.get_mut(&key) {
+            Some(page) => {
+                if page.expires_at <= now || page.entries.is_empty() {
+                    (None, true)
+                }
+                else {
+                    let count   = cmp::min(count, page.entries.len());
+                    let entries: Vec<Entry> = page.entries.drain(..count).collect();
+
+                    page.expires_at = now + ttl;
+                    (Some((page.name.clone(), entries, page.entries.len())), page.entries.is_empty())
+                }
+            },
+            None => (None, true)
+        };
+
+        if exhausted {
+            self.pages.remove(&key);
+        }
+
+        result
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::Pages;
+    use super::super::dictionary::Entry;
+
+    fn entry(reading: &str) -> Entry {
+        Entry {
+            writing: None,
+            reading: reading.to_owned(),
+            romaji:  None,
+            meaning: String::new(),
+            info:    None
+        }
+    }
+
+    #[test]
+    fn test_pages() {
+        let mut pages = Pages::new();
+        pages.insert("#test", "Gauss", "JMdict", vec![entry("い"), entry("ろ"), entry("は")], 0, 300);
+
+        assert_eq!(None, pages.next("#test", "holo", 2, 0, 300));
+        assert_eq!(Some(("JMdict".to_owned(), vec![entry("い"), entry("ろ")], 1)), pages.next("#test", "Gauss", 2, 100, 300));
+        assert_eq!(Some(("JMdict".to_owned(), vec![entry("は")], 0)),              pages.next("#test", "Gauss", 2, 350, 300));
+        assert_eq!(None,                                                         pages.next("#test", "Gauss", 2, 350, 300));
+
+        pages.insert("#test", "Gauss", "JMdict", vec![entry("に")], 0, 300);
+        assert_eq!(None, pages.next("#test", "Gauss", 2, 300, 300));
+    }
+}
@@ -0,0 +1,88 @@
+use kuchiki::{self, NodeRef};
+use kuchiki::traits::*;
+use super::dictionary::{Dictionary, Entry};
+
+// Scrapes tangorin.com, so it needs the network and a page layout that doesn't change.
+#[derive(Debug)]
+pub struct Scraper;
+
+impl Scraper {
+    fn retrieve_from_selector(&self, doc: &NodeRef, selector: &str) -> Option<String> {
+        doc.select(selector).unwrap().next().map(|match_| {
+            let node          = match_.as_node().first_child().unwrap();
+            let borrowed_text = node.as_text().unwrap().borrow();
+            borrowed_text.to_owned().trim().to_string()
+        })
+    }
+
+    fn retrieve_meaning(&self, doc: &NodeRef) -> Option<String> {
+        doc.select("span[class=eng]").unwrap().next().map(|match_| {
+            let mut meaning = String::new();
+            let     node_children = match_.as_node().children();
+            for child in node_children {
+                if let Some(text) = child.as_text() {
+                    meaning.push_str(text.borrow().as_str());
+                }
+                else if let Some(text) = self.inner_text(&child) {
+                    meaning.push_str(text.as_str());
+                }
+            }
+
+            meaning
+        })
+    }
+
+    fn retrieve_info(&self, doc: &NodeRef) -> Option<String> {
+        match doc.select("span[class=eng]").unwrap().next() {
+            Some(match_) => {
+                let node = match_.as_node();
+                let following_siblings = node.following_siblings();
+
+                match following_siblings.select("i[class=d-info]") {
+                    Err(_) => None,
+                    Ok(mut info_sibs) => info_sibs.next().map(|first_sibling|
+                        self.inner_text(first_sibling.as_node()).unwrap()
+                    )
+                }
+            },
+            None => None,
+        }
+    }
+
+    fn inner_text(&self, root: &NodeRef) -> Option<String> {
+        match root.first_child() {
+            Some(match_) => match match_.as_text() {
+                Some(result_str) => Some(result_str.borrow().to_owned()),
+                None             => None
+            },
+            None => None
+        }
+    }
+
+    fn entry(&self, doc: &NodeRef) -> Option<Entry> {
+        Some(Entry {
+            writing: Some(try_option!(self.retrieve_from_selector(doc, "span[class=writing]"))),
+            reading: try_option!(self.retrieve_from_selector(doc, "rb")),
+            romaji:  Some(try_option!(self.retrieve_from_selector(doc, "rt"))),
+            meaning: try_option!(self.retrieve_meaning(doc)),
+            info:    self.retrieve_info(doc)
+                .map(|info| info.replace("\u{2014}", "").replace(".", "").to_lowercase())
+        })
+    }
+}
+
+impl Dictionary for Scraper {
+    fn name(&self) -> &str {
+        "Tangorin"
+    }
+
+    fn lookup(&self, word: &str) -> Vec<Entry> {
+        // tangorin.com understands English as well
+        let url = format!("http://tangorin.com/general/{}", word.replace(" ", "%20"));
+
+        match kuchiki::parse_html().from_http(&url) {
+            Ok(doc) => self.entry(&doc).into_iter().collect(),
+            Err(_)  => vec![]
+        }
+    }
+}
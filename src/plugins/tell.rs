@@ -0,0 +1,221 @@
+use std::io;
+use std::sync::Mutex;
+use irc::client::prelude::*;
+use regex::Regex;
+use redis::{Commands, RedisResult};
+use time;
+use plugin::{self, Plugin};
+use store::Store;
+
+lazy_static! {
+    static ref TELL:  Regex        = Regex::new(r"^!tell\s+(\S+)\s+(\S.*?)\s*$").unwrap();
+    static ref TELLS: Regex        = Regex::new(r"^!tells\s*$").unwrap();
+    static ref STORE: Mutex<Store> = Mutex::new(Store::open("tell").unwrap());
+}
+
+// the nicks with memos waiting for them, and a "memos:<nick>" list for each of them
+const RECIPIENTS: &'static str = "recipients";
+
+#[derive(PartialEq, Debug)]
+struct Memo {
+    sender: String,
+    text:   String,
+    time:   i64
+}
+
+impl Memo {
+    // Memos are kept as "<time>\t<sender>\t<text>".
+    fn encode(&self) -> String {
+        format!("{}\t{}\t{}", self.time, self.sender, self.text)
+    }
+
+    fn decode(memo: &str) -> Option<Memo> {
+        let fields: Vec<&str> = memo.splitn(3, '\t').collect();
+        if fields.len() < 3 {
+            return None;
+        }
+
+        Some(Memo {
+            sender: fields[1].to_owned(),
+            text:   fields[2].to_owned(),
+            time:   fields[0].parse().unwrap_or(0)
+        })
+    }
+
+    fn describe(&self, recipient: &str, now: i64) -> String {
+        format!("{}, {} told me {}: {}", recipient, self.sender, plugin::time_ago(now - self.time), self.text)
+    }
+}
+
+fn memos_key(store: &Store, nickname: &str) -> String {
+    store.key(&format!("memos:{}", plugin::nick_key(nickname)))
+}
+
+fn is_channel(target: &str) -> bool {
+    target.starts_with('#') || target.starts_with('&')
+}
+
+register_plugin!(Tell);
+
+impl Tell {
+    fn tell(&self, server: &IrcServer, message: &Message, target: &str, recipient: &str, text: &str) -> io::Result<()> {
+        if plugin::nick_key(recipient) == plugin::nick_key(server.current_nickname()) {
+            return server.send_privmsg(target, "I'm right here!");
+        }
+
+        let sender = match message.source_nickname() {
+            Some(sender) => sender,
+            None         => { return Ok(()); }
+        };
+
+        let limit = plugin::get_option(server, "tell.limit")
+            .and_then(|limit| limit.parse().ok())
+            .unwrap_or(10);
+
+        let store = STORE.lock().unwrap();
+        let key   = memos_key(&store, recipient);
+
+        let pending: usize = store.llen(&*key).unwrap_or(0);
+        if pending >= limit {
+            return server.send_privmsg(target, &*format!("{} already has too many memos waiting", recipient));
+        }
+
+        let memo = Memo {
+            sender: sender.to_owned(),
+            text:   text.to_owned(),
+            time:   time::get_time().sec
+        };
+
+        let saved: RedisResult<()> = store.rpush(&*key, memo.encode())
+            .and_then(|_: ()| store.sadd(store.key(RECIPIENTS), plugin::nick_key(recipient)));
+
+        match saved {
+            Ok(()) => server.send_privmsg(target, &*format!("Okay, I'll tell {} when I see them", recipient)),
+            Err(e) => server.send_privmsg(target, &*format!("Something bad happened: {:?}", e))
+        }
+    }
+
+    // The memos the sender left that nobody has read yet.
+    fn tells(&self, server: &IrcServer, message: &Message, target: &str) -> io::Result<()> {
+        let sender = match message.source_nickname() {
+            Some(sender) => sender,
+            None         => { return Ok(()); }
+        };
+
+        let store = STORE.lock().unwrap();
+        let now   = time::get_time().sec;
+
+        let recipients: Vec<String> = store.smembers(store.key(RECIPIENTS)).unwrap_or(vec![]);
+        let mut pending = vec![];
+        for recipient in recipients {
+            let memos: Vec<String> = store.lrange(memos_key(&store, &recipient), 0, -1).unwrap_or(vec![]);
+            pending.extend(memos.iter()
+                .filter_map(|memo| Memo::decode(memo))
+                .filter(|memo| plugin::nick_key(&memo.sender) == plugin::nick_key(sender))
+                .map(|memo| format!("for {} ({}): {}", recipient, plugin::time_ago(now - memo.time), memo.text)));
+        }
+
+        if pending.is_empty() {
+            server.send_privmsg(target, "You have no memos waiting to be delivered")
+        }
+        else {
+            server.send_privmsg(target, &pending.join(", "))
+        }
+    }
+
+    // Memos are delivered where their recipient joins or speaks.
+    fn deliver(&self, server: &IrcServer, message: &Message, target: &str) -> io::Result<()> {
+        let recipient = match message.source_nickname() {
+            Some(recipient) => recipient,
+            None            => { return Ok(()); }
+        };
+
+        let target = if is_channel(target) { target } else { recipient };
+        let store  = STORE.lock().unwrap();
+        let key    = memos_key(&store, recipient);
+
+        let pending: usize = store.llen(&*key).unwrap_or(0);
+        if pending == 0 {
+            return Ok(());
+        }
+
+        let memos: Vec<String> = store.lrange(&*key, 0, -1).unwrap_or(vec![]);
+        let removed: RedisResult<()> = store.del(&*key)
+            .and_then(|_: ()| store.srem(store.key(RECIPIENTS), plugin::nick_key(recipient)));
+
+        if let Err(e) = removed {
+            println!("Tell: cannot remove the memos for {} ({:?})", recipient, e);
+            return Ok(());
+        }
+
+        let now = time::get_time().sec;
+        for memo in memos.iter().filter_map(|memo| Memo::decode(memo)) {
+            try!(server.send_privmsg(target, &memo.describe(recipient, now)));
+        }
+
+        Ok(())
+    }
+}
+
+impl Plugin for Tell {
+    fn is_allowed(&self, _: &IrcServer, message: &Message) -> bool {
+        match message.command {
+            Command::PRIVMSG(_, _) | Command::JOIN(_, _, _) => true,
+            _ => false
+        }
+    }
+
+    fn execute(&mut self, server: &IrcServer, message: &Message) -> io::Result<()> {
+        match message.command {
+            Command::PRIVMSG(ref target, ref msg) => {
+                try!(self.deliver(server, message, target));
+
+                if let Some(captures) = TELL.captures(msg) {
+                    self.tell(server, message, target, captures.at(1).unwrap(), captures.at(2).unwrap())
+                }
+                else if TELLS.is_match(msg) {
+                    self.tells(server, message, target)
+                }
+                else {
+                    Ok(())
+                }
+            },
+            Command::JOIN(ref channel, _, _) => self.deliver(server, message, channel),
+            _ => Ok(())
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use ::tests::{make_server, get_server_value};
+
+    use irc::client::prelude::*;
+
+    use plugin::Plugin;
+    use super::{Tell, Memo};
+
+    #[test]
+    fn test_tell_gauss() {
+        let     server = make_server("PRIVMSG #test :!tell gauss hello\r\n");
+        let mut plugin = Tell::new();
+
+        for message in server.iter() {
+            let message = message.unwrap();
+            assert!(plugin.is_allowed(&server, &message));
+            assert!(plugin.execute(&server, &message).is_ok());
+        }
+
+        assert_eq!("PRIVMSG #test :I'm right here!\r\n", &*get_server_value(&server));
+    }
+
+    #[test]
+    fn test_memo() {
+        let memo = Memo { sender: "Lawrence".to_owned(), text: "the wheat\tis ready".to_owned(), time: 0 };
+
+        assert_eq!(Some(Memo { sender: "Lawrence".to_owned(), text: "the wheat\tis ready".to_owned(), time: 0 }),
+                   Memo::decode(&memo.encode()));
+        assert_eq!("Holo, Lawrence told me 2 hours ago: the wheat\tis ready", memo.describe("Holo", 7200));
+        assert_eq!(None, Memo::decode("broken"));
+    }
+}
@@ -1,62 +1,234 @@
 use std::io;
+use std::cmp;
+use std::collections::HashMap;
 use irc::client::prelude::*;
 use regex::Regex;
-use plugin::Plugin;
+use hyper::client::Client;
+use hyper::header::{CacheControl, CacheDirective, Connection};
+use time;
+use plugin::{self, Plugin};
 
 extern crate kuchiki;
 use kuchiki::traits::*;
 
-register_plugin!(Url);
+register_plugin!(Url, cache: TitleCache);
+
+const DEFAULT_TTL:        i64   = 3600;
+const DEFAULT_CACHE_SIZE: usize = 256;
 
 lazy_static! {
     static ref RE: Regex = Regex::new(r"http(s)?://(\S+)").unwrap();
 }
 
+#[derive(Debug)]
+struct CacheEntry {
+    title:      Option<String>,
+    expires_at: i64,
+    used_at:    u64
+}
+
+// Titles of the last fetched pages, keyed by normalised URL.
+// Pages without a title are cached too, so they are not fetched over and over.
+#[derive(Debug)]
+struct TitleCache {
+    entries: HashMap<String, CacheEntry>,
+    clock:   u64
+}
+
+impl TitleCache {
+    fn new() -> TitleCache {
+        TitleCache {
+            entries: HashMap::new(),
+            clock:   0
+        }
+    }
+
+    fn get(&mut self, url: &str, now: i64) -> Option<Option<String>> {
+        self.clock += 1;
+
+        let expired = match self.entries.get_mut(url) {
+            Some(entry) => {
+                if entry.expires_at > now {
+                    entry.used_at = self.clock;
+                    return Some(entry.title.clone());
+                }
+
+                true
+            },
+            None => false
+        };
+
+        if expired {
+            self.entries.remove(url);
+        }
+
+        None
+    }
+
+    fn insert(&mut self, url: String, title: Option<String>, expires_at: i64, capacity: usize) {
+        if capacity == 0 {
+            return;
+        }
+
+        self.clock += 1;
+
+        while self.entries.len() >= capacity && !self.entries.contains_key(&url) {
+            let lru = self.entries.iter()
+                .min_by_key(|&(_, entry)| entry.used_at)
+                .map(|(url, _)| url.to_owned());
+
+            match lru {
+                Some(lru) => { self.entries.remove(&lru); },
+                None      => break
+            }
+        }
+
+        self.entries.insert(url, CacheEntry {
+            title:      title,
+            expires_at: expires_at,
+            used_at:    self.clock
+        });
+    }
+
+    fn flush(&mut self) -> usize {
+        let size = self.entries.len();
+        self.entries.clear();
+        size
+    }
+}
+
 impl Url {
     fn grep_url(&self, msg: &str) -> Option<String> {
         match RE.captures(msg) {
             Some(captures) => {
-                if captures.len() == 3 {
-                    Some(format!("https://{}", captures.at(2).unwrap()))
-                }
-                else {
-                    Some(format!("http://{}", captures.at(1).unwrap()))
-                }
+                let scheme = if captures.at(1).is_some() { "https" } else { "http" };
+                Some(self.normalise(scheme, captures.at(2).unwrap()))
             },
             None => None
         }
     }
 
-    fn url(&self, server: &IrcServer, _: &Message, target: &str, msg: &str) -> io::Result<()> {
+    // Lowercases the host, drops the default port and the fragment,
+    // so that different spellings of the same link share the same cache entry.
+    fn normalise(&self, scheme: &str, url: &str) -> String {
+        let url = url.split('#').next().unwrap_or("");
+
+        let (host, path) = match url.find(|c: char| c == '/' || c == '?') {
+            Some(i) => url.split_at(i),
+            None    => (url, "")
+        };
+
+        let mut host = host.to_lowercase();
+        let default_port = if scheme == "https" { ":443" } else { ":80" };
+        if host.ends_with(default_port) {
+            let len = host.len() - default_port.len();
+            host.truncate(len);
+        }
+
+        let path = if path.starts_with('/') { path.to_owned() } else { format!("/{}", path) };
+        format!("{}://{}{}", scheme, host, path)
+    }
+
+    // How long a page can stay in the cache: the configured TTL,
+    // shortened by max-age and disabled by no-cache, no-store and private.
+    fn ttl(&self, server: &IrcServer, cache_control: Option<&CacheControl>) -> Option<i64> {
+        let mut ttl = plugin::get_option(server, "url.cache_ttl")
+            .and_then(|ttl| ttl.parse().ok())
+            .unwrap_or(DEFAULT_TTL);
+
+        if let Some(cache_control) = cache_control {
+            for directive in cache_control.iter() {
+                match *directive {
+                    CacheDirective::NoCache | CacheDirective::NoStore | CacheDirective::Private => { return None; },
+                    CacheDirective::MaxAge(age) | CacheDirective::SMaxAge(age) => ttl = cmp::min(ttl, age as i64),
+                    _ => {}
+                }
+            }
+        }
+
+        if ttl > 0 { Some(ttl) } else { None }
+    }
+
+    fn fetch_title(&self, url: &str) -> Option<(Option<String>, Option<CacheControl>)> {
+        let response = match Client::new().get(url).header(Connection::close()).send() {
+            Ok(response) => response,
+            Err(_)       => { return None; }
+        };
+
+        let cache_control = response.headers.get::<CacheControl>().cloned();
+
+        match kuchiki::parse_html().from_http(response) {
+            Ok(doc) => {
+                let title = doc.select("title").unwrap().last().and_then(|match_| {
+                    match_.as_node().first_child()
+                        .and_then(|node| node.as_text().map(|text| text.borrow().to_owned()))
+                });
+
+                Some((title, cache_control))
+            },
+            Err(_) => None
+        }
+    }
+
+    fn url(&mut self, server: &IrcServer, _: &Message, target: &str, msg: &str) -> io::Result<()> {
         let url = match self.grep_url(msg) {
             Some(url) => url,
             None      => { return Ok(()); }
         };
 
-        if let Ok(doc) = kuchiki::parse_html().from_http(&url) {
-            if let Some(match_) = doc.select("title").unwrap().last() {
-                let node    = match_.as_node().first_child().unwrap();
-                let title   = node.as_text().unwrap().borrow();
-                return server.send_privmsg(target,
-                                           &format!("[URL] {}", &*title))
+        let now = time::get_time().sec;
+        let title = match self.cache.get(&url, now) {
+            Some(title) => title,
+            None        => match self.fetch_title(&url) {
+                Some((title, cache_control)) => {
+                    if let Some(ttl) = self.ttl(server, cache_control.as_ref()) {
+                        let capacity = plugin::get_option(server, "url.cache_size")
+                            .and_then(|size| size.parse().ok())
+                            .unwrap_or(DEFAULT_CACHE_SIZE);
+                        self.cache.insert(url, title.clone(), now + ttl, capacity);
+                    }
+
+                    title
+                },
+                None => None
             }
+        };
+
+        match title {
+            Some(title) => server.send_privmsg(target,
+                                               &format!("[URL] {}", title)),
+            None        => Ok(())
+        }
+    }
+
+    fn flush(&mut self, server: &IrcServer, message: &Message, target: &str) -> io::Result<()> {
+        if !plugin::is_admin(server, message) {
+            return server.send_privmsg(target, "Only admins can flush the URL cache");
         }
 
-        Ok(())
+        let flushed = self.cache.flush();
+        server.send_privmsg(target, &format!("Flushed {} cached URLs", flushed))
     }
 }
 
 impl Plugin for Url {
     fn is_allowed(&self, _: &IrcServer, message: &Message) -> bool {
         match message.command {
-            Command::PRIVMSG(_, ref msg) => RE.is_match(msg),
+            Command::PRIVMSG(_, ref msg) => msg.trim() == "!flushurlcache" || RE.is_match(msg),
             _ => false
         }
     }
 
     fn execute(&mut self, server: &IrcServer, message: &Message) -> io::Result<()> {
         match message.command {
-            Command::PRIVMSG(ref target, ref msg) => self.url(server, message, target, msg),
+            Command::PRIVMSG(ref target, ref msg) => {
+                if msg.trim() == "!flushurlcache" {
+                    self.flush(server, message, target)
+                }
+                else {
+                    self.url(server, message, target, msg)
+                }
+            },
             _ => Ok(())
         }
     }
@@ -69,7 +241,7 @@ mod tests {
     use irc::client::prelude::*;
 
     use plugin::Plugin;
-    use super::Url;
+    use super::{Url, TitleCache};
 
     #[test]
     fn test_url() {
@@ -111,4 +283,43 @@ mod tests {
             assert!(!plugin.is_allowed(&server, &message));
         }
     }
+
+    #[test]
+    fn test_normalise() {
+        let plugin = Url::new();
+
+        assert_eq!(Some("https://github.com/".to_owned()), plugin.grep_url("look https://GitHub.com:443#top"));
+        assert_eq!(Some("http://example.com/a?b=C".to_owned()), plugin.grep_url("http://Example.COM:80/a?b=C"));
+        assert_eq!(Some("http://example.com:8080/".to_owned()), plugin.grep_url("http://example.com:8080"));
+    }
+
+    #[test]
+    fn test_cache() {
+        let mut cache = TitleCache::new();
+        cache.insert("a".to_owned(), Some("A".to_owned()), 100, 2);
+        cache.insert("b".to_owned(), None, 100, 2);
+
+        assert_eq!(Some(Some("A".to_owned())), cache.get("a", 50));
+        cache.insert("c".to_owned(), Some("C".to_owned()), 100, 2);
+
+        assert_eq!(None, cache.get("b", 50));
+        assert_eq!(Some(Some("C".to_owned())), cache.get("c", 50));
+        assert_eq!(None, cache.get("a", 100));
+        assert_eq!(1,    cache.flush());
+    }
+
+    #[test]
+    fn test_flush_not_admin() {
+        let     server = make_server("PRIVMSG test :!flushurlcache\r\n");
+        let mut plugin = Url::new();
+
+        for message in server.iter() {
+            let message = message.unwrap();
+            assert!(plugin.is_allowed(&server, &message));
+            assert!(plugin.execute(&server, &message).is_ok());
+        }
+
+        assert_eq!("PRIVMSG test :Only admins can flush the URL cache\r\n",
+                   &*get_server_value(&server));
+    }
 }
@@ -0,0 +1,49 @@
+use std::env;
+use std::ops::Deref;
+use redis::{Client, Connection, RedisResult};
+
+// A Redis connection whose keys live under "gauss:<namespace>:", so that plugins
+// neither step on each other's keys nor on anything else stored in the same database.
+// The server is read from REDIS_URL, defaulting to a local Redis.
+pub struct Store {
+    namespace:  String,
+    connection: Connection
+}
+
+pub fn namespaced(namespace: &str, key: &str) -> String {
+    format!("gauss:{}:{}", namespace, key)
+}
+
+impl Store {
+    pub fn open(namespace: &str) -> RedisResult<Store> {
+        let url    = env::var("REDIS_URL").unwrap_or("redis://127.0.0.1/".to_owned());
+        let client = try!(Client::open(&*url));
+
+        Ok(Store {
+            namespace:  namespace.to_owned(),
+            connection: try!(client.get_connection())
+        })
+    }
+
+    pub fn key(&self, key: &str) -> String {
+        namespaced(&self.namespace, key)
+    }
+}
+
+impl Deref for Store {
+    type Target = Connection;
+
+    fn deref(&self) -> &Connection {
+        &self.connection
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::namespaced;
+
+    #[test]
+    fn test_namespaced() {
+        assert_eq!("gauss:lastfm:users", namespaced("lastfm", "users"));
+    }
+}